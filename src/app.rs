@@ -1,25 +1,148 @@
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use winit::application::ApplicationHandler;
-use winit::event::{ElementState, KeyEvent, MouseButton, WindowEvent};
+use winit::dpi::PhysicalSize;
+use winit::event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent};
 use winit::event_loop::ActiveEventLoop;
-use winit::keyboard::{KeyCode, PhysicalKey};
+use winit::keyboard::{KeyCode, ModifiersState, PhysicalKey};
 use winit::window::{Window, WindowId};
 
-mod quad;
-mod renderer;
-mod shader;
+use fractal::fractals::Custom;
+use fractal::named::Named;
+use fractal::shader_watcher::{SOURCE_DIRECTORY, ShaderWatcher};
+use fractal::uniforms::MAX_PARAMS;
+use fractal::{FRACTALS, Viewport};
+
 mod state;
-mod uniforms;
-mod vertex;
 
-use state::State;
+pub use state::Colours;
+
+use state::{State, aspect_ratio};
+
+/// Touchpads scroll in pixels; this many count as one mouse wheel notch.
+const PIXELS_PER_NOTCH: f64 = 50.0;
+/// Factor the [ and ] keys change the palette density by.
+const PALETTE_DENSITY_STEP: f32 = 2.0;
+/// Degrees the comma and full stop keys turn the light by.
+const LIGHT_ANGLE_STEP: f32 = 15.0;
 
 #[derive(Default)]
 pub struct App<'a> {
     window: Option<Arc<Window>>,
     state: Option<State<'a>>,
+    /// Size of images saved with the P key. Defaults to the window size.
+    export_size: Option<PhysicalSize<u32>>,
+    /// Fractal shown when the window opens.
+    bundle_idx: usize,
+    /// View to open at instead of the fractal's default.
+    view: Option<Viewport>,
+    /// Parameters of the fractal shown when the window opens.
+    params: [f32; MAX_PARAMS],
+    /// Whether to load the shaders from the source tree and keep them up
+    /// to date.
+    watch_shaders: bool,
+    /// Colouring when the window opens.
+    colours: Colours,
+    /// What the formula fractal iterates.
+    formula: Custom,
+    shader_watcher: Option<ShaderWatcher>,
+    /// Held modifier keys; shift+left drag is box zoom and ctrl+left click
+    /// picks a Julia set.
+    modifiers: ModifiersState,
+    /// Last status shown in the window title.
+    status: String,
+}
+
+impl App<'_> {
+    pub fn new(
+        export_size: Option<PhysicalSize<u32>>,
+        bundle_idx: usize,
+        view: Option<Viewport>,
+        params: [f32; MAX_PARAMS],
+        watch_shaders: bool,
+        colours: Colours,
+        formula: Custom,
+    ) -> Self {
+        Self {
+            export_size,
+            bundle_idx,
+            view,
+            params,
+            watch_shaders,
+            colours,
+            formula,
+            ..Default::default()
+        }
+    }
+
+    fn export_png(&mut self) {
+        let (Some(window), Some(state)) = (self.window.as_ref(), self.state.as_mut()) else {
+            return;
+        };
+
+        let size = self.export_size.unwrap_or(window.inner_size());
+        let path = PathBuf::from(format!("fractal_{}.png", timestamp()));
+
+        match state.export_png(&path, size) {
+            Ok(()) => println!(
+                "Saved {}x{} image to {}",
+                size.width,
+                size.height,
+                path.display()
+            ),
+            Err(e) => eprintln!("Export failed: {e}"),
+        }
+    }
+
+    /// Start watching the shader files, switching to the ones on disk.
+    fn start_watching_shaders(&mut self) {
+        let Some(state) = self.state.as_mut() else {
+            return;
+        };
+
+        match ShaderWatcher::new(Path::new(SOURCE_DIRECTORY)) {
+            Ok(watcher) => {
+                println!("Watching shaders in {SOURCE_DIRECTORY}");
+                state.reload_shaders();
+                self.shader_watcher = Some(watcher);
+            }
+            Err(e) => eprintln!("{e}"),
+        }
+    }
+
+    /// Rebuild the shaders saved since the last frame. One that fails to
+    /// compile keeps drawing with its last good pipeline.
+    fn reload_changed_shaders(&mut self) {
+        let (Some(watcher), Some(state)) = (self.shader_watcher.as_ref(), self.state.as_mut())
+        else {
+            return;
+        };
+
+        match watcher.poll() {
+            Ok(true) => {
+                if state.reload_shaders() {
+                    println!("Shaders reloaded");
+                }
+            }
+            Ok(false) => (),
+            Err(e) => eprintln!("{e}"),
+        }
+    }
+
+    /// Write the current view to a file that `--view` loads.
+    fn save_view(&self) {
+        let Some(state) = self.state.as_ref() else {
+            return;
+        };
+
+        let path = PathBuf::from(format!("fractal_{}.view", timestamp()));
+        match std::fs::write(&path, state.app_state.viewport.to_string()) {
+            Ok(()) => println!("Saved view to {}", path.display()),
+            Err(e) => eprintln!("Saving view failed: {e}"),
+        }
+    }
 }
 
 impl ApplicationHandler for App<'_> {
@@ -32,8 +155,18 @@ impl ApplicationHandler for App<'_> {
             );
             self.window = Some(window.clone());
 
-            let state = pollster::block_on(State::new(window.clone()));
+            let mut state = pollster::block_on(State::new(window.clone()));
+            state.set_formula(self.formula.clone());
+            state.app_state.select_fractal(self.bundle_idx);
+            state.app_state.params = self.params;
+            state.app_state.colours = self.colours.clone();
+            if let Some(view) = self.view.take() {
+                state.app_state.viewport = view;
+            }
             self.state = Some(state);
+            if self.watch_shaders {
+                self.start_watching_shaders();
+            }
             window.request_redraw();
         }
     }
@@ -55,9 +188,17 @@ impl ApplicationHandler for App<'_> {
                 }
             }
             WindowEvent::RedrawRequested => {
+                self.reload_changed_shaders();
                 if let Some(state) = self.state.as_mut() {
                     state.draw();
-                    self.window.as_ref().unwrap().request_redraw();
+
+                    let window = self.window.as_ref().unwrap();
+                    let status = state.status();
+                    if status != self.status {
+                        window.set_title(&format!("fractal - {status}"));
+                        self.status = status;
+                    }
+                    window.request_redraw();
                 }
             }
             WindowEvent::KeyboardInput {
@@ -69,6 +210,15 @@ impl ApplicationHandler for App<'_> {
                     },
                 ..
             } => {
+                if key == KeyCode::KeyP && state == ElementState::Pressed {
+                    self.export_png();
+                    return;
+                }
+                if key == KeyCode::KeyV && state == ElementState::Pressed {
+                    self.save_view();
+                    return;
+                }
+
                 if let Some(app_state) = self.state.as_mut() {
                     match (key, state) {
                         (KeyCode::Space, ElementState::Pressed) => {
@@ -77,22 +227,83 @@ impl ApplicationHandler for App<'_> {
                         (KeyCode::Enter, ElementState::Pressed) => {
                             app_state.app_state.paused = false;
                         }
-                        (KeyCode::Digit1, ElementState::Pressed) => {
-                            app_state.app_state.bundle_idx = 0;
-                            app_state.app_state.reset_time();
+                        (KeyCode::KeyZ, ElementState::Pressed) => {
+                            app_state.app_state.zoom_decay = !app_state.app_state.zoom_decay;
+                        }
+                        (KeyCode::KeyA, ElementState::Pressed) => {
+                            match app_state.app_state.toggle_demo() {
+                                Some(true) => println!("Demo auto-zoom on"),
+                                Some(false) => println!("Demo auto-zoom off"),
+                                None => (),
+                            }
+                        }
+                        (KeyCode::KeyJ, ElementState::Pressed) => {
+                            let preview = &mut app_state.app_state.julia_preview;
+                            *preview = !*preview;
+                            println!("Julia preview {}", if *preview { "on" } else { "off" });
+                        }
+                        (KeyCode::KeyD, ElementState::Pressed) => {
+                            app_state.app_state.cycle_precision();
+                            match app_state.app_state.precision {
+                                Some(precision) => println!("Precision: {}", precision.name()),
+                                None => println!("Precision: automatic"),
+                            }
+                        }
+                        (KeyCode::KeyC, ElementState::Pressed) => {
+                            println!("Palette: {}", app_state.app_state.next_palette());
+                        }
+                        (KeyCode::KeyI, ElementState::Pressed) => {
+                            let interpolation = app_state.app_state.next_interpolation();
+                            println!("Palette interpolation: {}", interpolation.name());
+                        }
+                        (KeyCode::KeyO, ElementState::Pressed) => {
+                            let cycling = &mut app_state.app_state.palette_cycling;
+                            *cycling = !*cycling;
+                            println!("Palette cycling {}", if *cycling { "on" } else { "off" });
+                        }
+                        (KeyCode::KeyH, ElementState::Pressed) => {
+                            let colouring = &mut app_state.app_state.colours.colouring;
+                            *colouring = colouring.next();
+                            println!("Colouring: {}", colouring.name());
                         }
-                        (KeyCode::Digit2, ElementState::Pressed) => {
-                            app_state.app_state.bundle_idx = 1;
-                            app_state.app_state.reset_time();
+                        (KeyCode::Tab, ElementState::Pressed) => {
+                            let backwards = self.modifiers.shift_key();
+                            println!("Fractal: {}", app_state.app_state.step_fractal(backwards));
                         }
-                        (KeyCode::Digit3, ElementState::Pressed) => {
-                            app_state.app_state.bundle_idx = 2;
-                            app_state.app_state.reset_time();
+                        (KeyCode::KeyL, ElementState::Pressed) => {
+                            let shading = app_state.app_state.next_shading();
+                            println!("Shading: {}", shading.name());
+                        }
+                        (KeyCode::Comma | KeyCode::Period, ElementState::Pressed) => {
+                            let angle = &mut app_state.app_state.colours.light_angle;
+                            let step = LIGHT_ANGLE_STEP.to_radians();
+                            *angle += if key == KeyCode::Period { -step } else { step };
+                            *angle = angle.rem_euclid(std::f32::consts::TAU);
+                            println!("Light angle: {:.0} degrees", angle.to_degrees());
+                        }
+                        (KeyCode::BracketLeft | KeyCode::BracketRight, ElementState::Pressed) => {
+                            let density = &mut app_state.app_state.colours.palette_density;
+                            if key == KeyCode::BracketRight {
+                                *density *= PALETTE_DENSITY_STEP;
+                            } else {
+                                *density /= PALETTE_DENSITY_STEP;
+                            }
+                            println!("Palette density: {density}");
+                        }
+                        (_, ElementState::Pressed) => {
+                            if let Some(bundle_idx) =
+                                digit_index(key).filter(|idx| *idx < FRACTALS.len())
+                            {
+                                app_state.app_state.select_fractal(bundle_idx);
+                            }
                         }
                         _ => (),
                     }
                 }
             }
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers.state();
+            }
             WindowEvent::CursorMoved { position, .. } => {
                 if let Some(app_state) = self.state.as_mut() {
                     let size = self.window.as_ref().unwrap().inner_size();
@@ -103,10 +314,10 @@ impl ApplicationHandler for App<'_> {
 
                         let diff_x = ((new_pos.x - old_pos.x) / size.width as f64) as f32;
                         let diff_y = ((new_pos.y - old_pos.y) / size.height as f64) as f32;
-                        app_state.app_state.offset[0] +=
-                            diff_x * 2.0 * 1.5 / app_state.app_state.zoom;
-                        app_state.app_state.offset[1] +=
-                            diff_y * 2.0 * 1.5 / app_state.app_state.zoom;
+                        app_state
+                            .app_state
+                            .viewport
+                            .pan(diff_x, diff_y, aspect_ratio(size));
                     }
 
                     // update mouse pos only after calculations are done
@@ -120,10 +331,23 @@ impl ApplicationHandler for App<'_> {
                             ElementState::Pressed => {
                                 app_state.app_state.mouse_click_point =
                                     app_state.app_state.mouse_pos;
-                                app_state.app_state.follow_mouse = true;
+                                if self.modifiers.control_key() {
+                                    let size = self.window.as_ref().unwrap().inner_size();
+                                    if let Some([x, y]) = app_state.app_state.pick_julia(size) {
+                                        println!("Julia c = {x} + {y}i");
+                                    }
+                                } else if self.modifiers.shift_key() {
+                                    app_state.app_state.selecting = true;
+                                } else {
+                                    app_state.app_state.follow_mouse = true;
+                                }
                             }
                             ElementState::Released => {
                                 app_state.app_state.follow_mouse = false;
+                                if app_state.app_state.selecting {
+                                    let size = self.window.as_ref().unwrap().inner_size();
+                                    app_state.app_state.zoom_to_selection(size);
+                                }
                             }
                         },
                         MouseButton::Right => {
@@ -133,6 +357,18 @@ impl ApplicationHandler for App<'_> {
                     }
                 }
             }
+            WindowEvent::MouseWheel { delta, .. } => {
+                if let Some(app_state) = self.state.as_mut() {
+                    let size = self.window.as_ref().unwrap().inner_size();
+                    let notches = match delta {
+                        MouseScrollDelta::LineDelta(_, y) => y,
+                        MouseScrollDelta::PixelDelta(position) => {
+                            (position.y / PIXELS_PER_NOTCH) as f32
+                        }
+                    };
+                    app_state.app_state.zoom_wheel(notches, size);
+                }
+            }
             _ => {}
         }
     }
@@ -145,3 +381,28 @@ impl ApplicationHandler for App<'_> {
         println!("App exiting");
     }
 }
+
+/// Seconds since the Unix epoch, to name saved files.
+fn timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Maps the 1-9 keys to fractal indices, then 0 to the tenth.
+fn digit_index(key: KeyCode) -> Option<usize> {
+    const DIGITS: [KeyCode; 10] = [
+        KeyCode::Digit1,
+        KeyCode::Digit2,
+        KeyCode::Digit3,
+        KeyCode::Digit4,
+        KeyCode::Digit5,
+        KeyCode::Digit6,
+        KeyCode::Digit7,
+        KeyCode::Digit8,
+        KeyCode::Digit9,
+        KeyCode::Digit0,
+    ];
+    DIGITS.iter().position(|digit| *digit == key)
+}
@@ -1,58 +0,0 @@
-use wgpu::{Device, ShaderModuleDescriptor, VertexBufferLayout};
-
-use super::uniforms;
-
-pub struct ShaderBundle {
-    _shader: wgpu::ShaderModule,
-    pub pipeline: wgpu::RenderPipeline,
-}
-
-impl ShaderBundle {
-    pub fn new(
-        device: &Device,
-        surface_format: &wgpu::TextureFormat,
-        buffer_layout: &VertexBufferLayout,
-        shader_module_descriptor: ShaderModuleDescriptor,
-    ) -> Self {
-        let shader = device.create_shader_module(shader_module_descriptor);
-        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Fractal shader layout"),
-            bind_group_layouts: &[&uniforms::UniformData::bind_group_layout(&device)],
-            push_constant_ranges: &[],
-        });
-
-        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Fractal shader render pipeline"),
-            layout: Some(&pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: Some("vs_main"),
-                compilation_options: Default::default(),
-                buffers: &[buffer_layout.clone()],
-            },
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                ..Default::default()
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState::default(),
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: Some("fs_main"),
-                compilation_options: Default::default(),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: *surface_format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            multiview: None,
-            cache: None,
-        });
-
-        Self {
-            _shader: shader,
-            pipeline,
-        }
-    }
-}
@@ -1,19 +1,46 @@
+use std::path::Path;
 use std::sync::Arc;
 
 use winit::dpi::PhysicalSize;
 use winit::window::Window;
 
+pub use app_state::{Colours, aspect_ratio};
+
 use app_state::AppState;
 
-use crate::app::state::render_state::RenderState;
-use crate::app::uniforms::Uniforms;
+use fractal::FRACTALS;
+use fractal::export::{ExportError, check_size, write_png};
+use fractal::fractals::Custom;
+use fractal::perturbation::{GlitchSearch, Orbit, Reference};
+use fractal::tiled::TileGrid;
+use fractal::uniforms::{UniformData, Uniforms};
+
+use crate::app::state::render_state::{Preview, RenderState};
 
 mod app_state;
 mod render_state;
+mod selection_overlay;
+
+/// Side of the Julia preview as a fraction of the window's shorter side.
+const PREVIEW_FRACTION: f32 = 0.3;
+/// Gap in pixels between the Julia preview and the window corner.
+const PREVIEW_MARGIN: f32 = 16.0;
+
 pub struct State<'a> {
     render_state: RenderState<'a>,
     pub app_state: AppState,
     uniforms: Uniforms,
+    /// Uniforms of the Julia preview.
+    preview_uniforms: Uniforms,
+    /// The reference whose orbit is uploaded in `uniforms`.
+    reference: Option<Reference>,
+    /// Iterations the uploaded orbit's series lets every pixel skip.
+    skipped_iterations: u32,
+    /// References the uploaded orbit has for glitched pixels.
+    secondary_references: usize,
+    /// Fractals whose shader failed to build on the last reload, and still
+    /// draw with their previous pipeline.
+    broken_shaders: Vec<&'static str>,
 }
 /// Holds all wgpu state.
 impl<'a> State<'a> {
@@ -24,11 +51,17 @@ impl<'a> State<'a> {
         let app_state = AppState::new();
 
         let uniforms = Uniforms::new(&render_state.device);
+        let preview_uniforms = Uniforms::new(&render_state.device);
 
         Self {
             render_state,
             app_state,
             uniforms,
+            preview_uniforms,
+            reference: None,
+            skipped_iterations: 0,
+            secondary_references: 0,
+            broken_shaders: Vec::new(),
         }
     }
 
@@ -39,20 +72,215 @@ impl<'a> State<'a> {
     pub fn draw(&mut self) {
         self.app_state.update();
 
-        if self.app_state.zooming {
-            self.app_state.zoom += 0.005;
-        } else {
-            self.app_state.zoom = f32::max(self.app_state.zoom - 0.05, 1.0);
+        self.app_state.update_zoom(self.render_state.size());
+        self.update_reference(self.render_state.size());
+
+        let uniform_data = UniformData {
+            time: self.app_state.elapsed_time(),
+            resolution: resolution(self.render_state.size()),
+            params: self.app_state.params,
+            precision_mode: self.app_state.resolved_precision(self.render_state.size()) as u32,
+            palette_offset: self.app_state.colours.palette_offset,
+            palette_density: self.app_state.colours.palette_density,
+            colouring: self.app_state.colours.colouring as u32,
+            shading: self.app_state.colours.shading as u32,
+            light_angle: self.app_state.colours.light_angle,
+            ..UniformData::from_viewport(&self.app_state.viewport)
+        };
+        let palette = self.app_state.palette_table();
+        self.uniforms
+            .set_palette(&self.render_state.queue, &palette);
+        self.render_state
+            .equalise(&mut self.uniforms, uniform_data, self.app_state.bundle_idx);
+        self.uniforms.update(&self.render_state.queue, uniform_data);
+
+        let preview = self.update_preview();
+        self.render_state.draw(
+            &self.uniforms.uniform_bind_group,
+            self.app_state.bundle_idx,
+            self.app_state.selection_rect(),
+            preview.map(|(bundle_idx, rect)| Preview {
+                uniform_bind_group: &self.preview_uniforms.uniform_bind_group,
+                bundle_idx,
+                rect,
+            }),
+        );
+    }
+
+    /// Set up the Julia preview for the point under the cursor, if it is on
+    /// and the fractal has one, returning what to draw where.
+    fn update_preview(&mut self) -> Option<(usize, [f32; 4])> {
+        let size = self.render_state.size();
+        if !self.app_state.julia_preview {
+            return None;
         }
+        let (bundle_idx, params) = self.app_state.julia_at_cursor(size)?;
 
-        self.uniforms.update(
-            &mut self.render_state.queue,
-            self.app_state.elapsed_time(),
-            self.app_state.zoom,
-            self.app_state.offset,
+        let side = PREVIEW_FRACTION * size.width.min(size.height) as f32;
+        let rect = [
+            size.width as f32 - side - PREVIEW_MARGIN,
+            size.height as f32 - side - PREVIEW_MARGIN,
+            side,
+            side,
+        ];
+        let uniform_data = UniformData {
+            time: self.app_state.elapsed_time(),
+            resolution: [side, side],
+            params,
+            palette_offset: self.app_state.colours.palette_offset,
+            palette_density: self.app_state.colours.palette_density,
+            colouring: self.app_state.colours.colouring as u32,
+            shading: self.app_state.colours.shading as u32,
+            light_angle: self.app_state.colours.light_angle,
+            ..UniformData::from_viewport(&FRACTALS[bundle_idx].default_view())
+        };
+        let palette = self.app_state.palette_table();
+        self.preview_uniforms
+            .set_palette(&self.render_state.queue, &palette);
+        self.render_state
+            .equalise(&mut self.preview_uniforms, uniform_data, bundle_idx);
+        self.preview_uniforms
+            .update(&self.render_state.queue, uniform_data);
+        Some((bundle_idx, rect))
+    }
+
+    /// Recompute the reference orbit whenever the view, rendered at `size`,
+    /// calls for a new one, with secondaries for the pixels that glitch
+    /// against it.
+    fn update_reference(&mut self, size: PhysicalSize<u32>) {
+        let app_state = &self.app_state;
+        let reference = FRACTALS[app_state.bundle_idx].reference(
+            &app_state.viewport,
+            &app_state.params,
+            aspect_ratio(size),
         );
+        if reference == self.reference {
+            return;
+        }
+
+        let orbit = match &reference {
+            Some(reference) => self.find_glitches(reference, size),
+            None => Orbit::default(),
+        };
+        self.uniforms
+            .set_orbit(&self.render_state.device, &self.render_state.queue, &orbit);
+        self.reference = reference;
+        self.skipped_iterations = orbit.series.skip;
+        self.secondary_references = orbit.secondaries.len();
+    }
+
+    /// The orbit of `reference` for the view rendered at `size`, see
+    /// [`Reference::orbit_without_glitches`]. Leaves the uniforms as they
+    /// were, other than the orbit.
+    fn find_glitches(&mut self, reference: &Reference, size: PhysicalSize<u32>) -> Orbit {
+        let bundle_idx = self.app_state.bundle_idx;
+        let search = GlitchSearch::new(UniformData {
+            resolution: resolution(size),
+            params: self.app_state.params,
+            ..UniformData::from_viewport(&self.app_state.viewport)
+        });
+        let target = self
+            .render_state
+            .create_offscreen_target(search.width, search.height);
+        let on_screen = self.uniforms.data();
+        self.uniforms
+            .update(&self.render_state.queue, search.uniform_data);
+
+        let orbit = reference.orbit_without_glitches(|orbit| {
+            self.uniforms
+                .set_orbit(&self.render_state.device, &self.render_state.queue, orbit);
+            let pixels = self.render_state.draw_offscreen(
+                &target,
+                &self.uniforms.uniform_bind_group,
+                bundle_idx,
+            );
+            search.glitch(&pixels)
+        });
 
+        self.uniforms.update(&self.render_state.queue, on_screen);
+        orbit
+    }
+
+    /// Rebuild the fractals whose shader files changed, printing why any
+    /// failed. Returns whether all of them built.
+    pub fn reload_shaders(&mut self) -> bool {
+        let errors = self.render_state.reload_shaders();
+        for e in &errors {
+            eprintln!("{e}");
+        }
+        self.broken_shaders = errors.iter().map(|e| e.fractal).collect();
+        errors.is_empty()
+    }
+
+    /// Draw `formula` for the formula fractal, printing why if its shader
+    /// failed to build.
+    pub fn set_formula(&mut self, formula: Custom) {
+        if let Err(e) = self.render_state.set_formula(formula) {
+            eprintln!("{e}");
+        }
+    }
+
+    /// A summary of how the current view is rendered, for the window title.
+    pub fn status(&self) -> String {
+        let fractal = FRACTALS[self.app_state.bundle_idx].name();
+        let status = match &self.reference {
+            Some(reference) => format!(
+                "{fractal}: series skips {} of {} iterations",
+                self.skipped_iterations, reference.max_iterations
+            ),
+            None => fractal.to_string(),
+        };
+        let status = match self.secondary_references {
+            0 => status,
+            count => format!("{status}, {count} more references for glitches"),
+        };
+        if self.broken_shaders.contains(&fractal) {
+            format!("{status} (shader error, showing the last good shader)")
+        } else {
+            status
+        }
+    }
+
+    /// Save the view currently on screen to a png of the given size. Sizes
+    /// beyond the GPU texture limit are rendered in tiles.
+    pub fn export_png(&mut self, path: &Path, size: PhysicalSize<u32>) -> Result<(), ExportError> {
+        check_size(size.width, size.height)?;
+
+        let grid = TileGrid::new(
+            size.width,
+            size.height,
+            self.render_state.max_texture_dimension(),
+        );
+        let target = self
+            .render_state
+            .create_offscreen_target(grid.tile_width, grid.tile_height);
+        // The series must hold out to the corners of the exported image. The
+        // next frame puts back the window's orbit.
+        self.update_reference(size);
+        let on_screen = self.uniforms.data();
+        let export = UniformData {
+            resolution: resolution(size),
+            precision_mode: self.app_state.resolved_precision(size) as u32,
+            ..on_screen
+        };
         self.render_state
-            .draw(&self.uniforms.uniform_bind_group, self.app_state.bundle_idx);
+            .equalise(&mut self.uniforms, export, self.app_state.bundle_idx);
+
+        let result = write_png(path, &grid, |tile| {
+            self.uniforms
+                .update(&self.render_state.queue, export.with_tile(tile));
+            self.render_state.draw_offscreen(
+                &target,
+                &self.uniforms.uniform_bind_group,
+                self.app_state.bundle_idx,
+            )
+        });
+
+        self.uniforms.update(&self.render_state.queue, on_screen);
+        result
     }
 }
+
+fn resolution(size: PhysicalSize<u32>) -> [f32; 2] {
+    [size.width as f32, size.height as f32]
+}
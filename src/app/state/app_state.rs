@@ -1,19 +1,71 @@
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-use winit::dpi::PhysicalPosition;
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+
+use fractal::fractals::{self, JuliaPath};
+use fractal::histogram::Colouring;
+use fractal::named::Named;
+use fractal::palette::{self, Interpolation, Palette, Table};
+use fractal::shading::{DEFAULT_LIGHT_ANGLE, Shading};
+use fractal::uniforms::MAX_PARAMS;
+use fractal::{FRACTALS, Precision, Viewport};
+
+/// Zoom gained per second while the right mouse button is held.
+const HOLD_ZOOM_RATE: f32 = 0.3;
+/// Zoom lost per second when zoom decay is enabled.
+const ZOOM_DECAY_RATE: f32 = 3.0;
+/// Zoom gained per notch of the mouse wheel.
+const WHEEL_ZOOM_STEP: f32 = 1.0;
+/// Box zoom selections smaller than this many pixels across are ignored.
+const MIN_SELECTION_SIZE: f64 = 4.0;
+/// Palette lengths scrolled per second while the palette cycles.
+const PALETTE_CYCLE_RATE: f32 = 0.1;
+
+/// The palette, how intensities pick colours from it, and how they are
+/// shaded.
+#[derive(Clone)]
+pub struct Colours {
+    pub palette: Palette,
+    pub interpolation: Interpolation,
+    pub palette_offset: f32,
+    pub palette_density: f32,
+    pub colouring: Colouring,
+    pub shading: Shading,
+    /// In radians anticlockwise from the right.
+    pub light_angle: f32,
+}
 
 pub struct AppState {
     pub paused: bool,
     pub start_time: Instant,
     last_frame_time: Instant,
     paused_time: Duration,
-    pub zoom: f32,
+    frame_delta: Duration,
+    pub viewport: Viewport,
     pub zooming: bool,
-    pub offset: [f32; 2],
+    /// Drift back out to zoom 1 whenever the view is not being zoomed.
+    pub zoom_decay: bool,
     pub follow_mouse: bool,
+    /// Dragging out a box zoom rectangle from `mouse_click_point`.
+    pub selecting: bool,
     pub mouse_click_point: PhysicalPosition<f64>,
     pub mouse_pos: PhysicalPosition<f64>,
     pub bundle_idx: usize,
+    pub params: [f32; MAX_PARAMS],
+    /// Precision forced with the D key, or `None` to switch automatically.
+    pub precision: Option<Precision>,
+    /// Show the Julia set for the point under the cursor in a corner.
+    pub julia_preview: bool,
+    /// Palettes the C key steps through: the built-in ones and any loaded
+    /// from the command line.
+    palettes: Vec<Palette>,
+    pub colours: Colours,
+    /// `colours.palette` baked in `colours.interpolation`, see
+    /// [`AppState::palette_table`].
+    table: (Palette, Interpolation, Arc<Table>),
+    /// Scroll the palette along over time.
+    pub palette_cycling: bool,
 }
 
 impl AppState {
@@ -22,18 +74,35 @@ impl AppState {
         let start_time = now;
         let paused_time = Duration::ZERO;
         let last_frame_time = now;
+
+        let colours = Colours::default();
+        let table = colours.palette.bake(colours.interpolation);
+
         Self {
             paused: false,
             start_time,
             last_frame_time,
             paused_time,
-            zoom: 1.0,
+            frame_delta: Duration::ZERO,
+            viewport: FRACTALS[0].default_view(),
             zooming: false,
-            offset: [0.0, 0.0],
+            zoom_decay: false,
             follow_mouse: false,
+            selecting: false,
             mouse_click_point: PhysicalPosition { x: 0.0, y: 0.0 },
             mouse_pos: PhysicalPosition { x: 0.0, y: 0.0 },
             bundle_idx: 0,
+            params: FRACTALS[0].default_params(),
+            precision: None,
+            julia_preview: false,
+            palettes: palette::built_in().to_vec(),
+            table: (
+                colours.palette.clone(),
+                colours.interpolation,
+                Arc::new(table),
+            ),
+            colours,
+            palette_cycling: false,
         }
     }
 
@@ -44,7 +113,196 @@ impl AppState {
             self.paused_time += now - self.last_frame_time;
         }
 
+        self.frame_delta = now - self.last_frame_time;
         self.last_frame_time = now;
+
+        if self.palette_cycling && !self.paused {
+            self.colours.palette_offset = (self.colours.palette_offset
+                + PALETTE_CYCLE_RATE * self.frame_delta.as_secs_f32())
+            .rem_euclid(1.0);
+        }
+    }
+
+    /// Apply this frame's continuous zoom: towards the cursor while the right
+    /// button is held, otherwise back out if zoom decay is on.
+    pub fn update_zoom(&mut self, size: PhysicalSize<u32>) {
+        let dt = self.frame_delta.as_secs_f32();
+
+        if self.zooming {
+            let (x, y) = self.cursor_fraction(size);
+            self.viewport
+                .zoom_at(x, y, HOLD_ZOOM_RATE * dt, aspect_ratio(size));
+        } else if self.zoom_decay {
+            let zoom = f32::max(self.viewport.zoom() - ZOOM_DECAY_RATE * dt, 1.0);
+            // Zooming out only ever gives a view that can be shown.
+            let _ = self.viewport.set_zoom(zoom);
+        }
+    }
+
+    /// Zoom by whole wheel notches towards the cursor.
+    pub fn zoom_wheel(&mut self, notches: f32, size: PhysicalSize<u32>) {
+        let (x, y) = self.cursor_fraction(size);
+        self.viewport
+            .zoom_at(x, y, notches * WHEEL_ZOOM_STEP, aspect_ratio(size));
+    }
+
+    /// The box zoom rectangle being dragged as `[min_x, min_y, max_x, max_y]`
+    /// in pixels.
+    pub fn selection_rect(&self) -> Option<[f32; 4]> {
+        if !self.selecting {
+            return None;
+        }
+
+        let (start, end) = (self.mouse_click_point, self.mouse_pos);
+        Some([
+            start.x.min(end.x) as f32,
+            start.y.min(end.y) as f32,
+            start.x.max(end.x) as f32,
+            start.y.max(end.y) as f32,
+        ])
+    }
+
+    /// Finish a box zoom, fitting the selected rectangle to the window.
+    /// Selections too small to be deliberate are dropped.
+    pub fn zoom_to_selection(&mut self, size: PhysicalSize<u32>) {
+        self.selecting = false;
+
+        let (start, end) = (self.mouse_click_point, self.mouse_pos);
+        if (start.x - end.x).abs().max((start.y - end.y).abs()) < MIN_SELECTION_SIZE {
+            return;
+        }
+
+        // Selections big enough to keep are never empty.
+        let (width, height) = (size.width.max(1) as f64, size.height.max(1) as f64);
+        let _ = self.viewport.fit_rect(
+            (start.x / width) as f32,
+            (start.y / height) as f32,
+            (end.x / width) as f32,
+            (end.y / height) as f32,
+            aspect_ratio(size),
+        );
+    }
+
+    /// Switch the fractal's demo animation on or off, aimed at the centre of
+    /// the view. Returns whether it is now on, or `None` if the fractal has
+    /// no demo.
+    pub fn toggle_demo(&mut self) -> Option<bool> {
+        let fractal = FRACTALS[self.bundle_idx];
+        let demo = fractal.param_index("demo")?;
+
+        let on = self.params[demo] < 0.5;
+        if on {
+            let target = self.viewport.point_at(0.5, 0.5, 1.0);
+            for (name, value) in ["target_x", "target_y"].iter().zip(target) {
+                if let Some(idx) = fractal.param_index(name) {
+                    self.params[idx] = value as f32;
+                }
+            }
+        }
+        self.params[demo] = if on { 1.0 } else { 0.0 };
+        Some(on)
+    }
+
+    /// The Julia set for the point under the cursor, as its fractal index and
+    /// parameters, if the current fractal picks one.
+    pub fn julia_at_cursor(&self, size: PhysicalSize<u32>) -> Option<(usize, [f32; MAX_PARAMS])> {
+        let julia = fractals::find(FRACTALS[self.bundle_idx].julia()?)?;
+        let (x, y) = self.cursor_fraction(size);
+        let [c_x, c_y] = self.viewport.point_at(x, y, aspect_ratio(size));
+
+        let fractal = FRACTALS[julia];
+        let mut params = fractal.default_params();
+        let shared = FRACTALS[self.bundle_idx]
+            .params()
+            .iter()
+            .zip(self.params)
+            .map(|(param, value)| (param.name, value));
+        for (name, value) in shared.chain([
+            ("path", JuliaPath::Fixed as u32 as f32),
+            ("c_x", c_x as f32),
+            ("c_y", c_y as f32),
+        ]) {
+            if let Some(idx) = fractal.param_index(name) {
+                params[idx] = value;
+            }
+        }
+        Some((julia, params))
+    }
+
+    /// Switch to the Julia set for the point under the cursor. Returns its
+    /// `c`, or `None` if the current fractal does not pick one.
+    pub fn pick_julia(&mut self, size: PhysicalSize<u32>) -> Option<[f32; 2]> {
+        let (julia, params) = self.julia_at_cursor(size)?;
+        self.select_fractal(julia);
+        self.params = params;
+
+        let fractal = FRACTALS[julia];
+        let param = |name| fractal.param_index(name).map_or(0.0, |idx| params[idx]);
+        Some([param("c_x"), param("c_y")])
+    }
+
+    /// Step through automatic, single and double-single precision.
+    pub fn cycle_precision(&mut self) {
+        self.precision = match self.precision {
+            None => Some(Precision::Single),
+            Some(Precision::Single) => Some(Precision::DoubleSingle),
+            Some(Precision::DoubleSingle) => None,
+        };
+    }
+
+    /// Switch every fractal to the next palette, returning its name.
+    pub fn next_palette(&mut self) -> &str {
+        let current = &self.colours.palette;
+        let idx = match self.palettes.iter().position(|palette| palette == current) {
+            Some(idx) => idx,
+            None => {
+                self.palettes.push(current.clone());
+                self.palettes.len() - 1
+            }
+        };
+        self.colours.palette = self.palettes[(idx + 1) % self.palettes.len()].clone();
+        &self.colours.palette.name
+    }
+
+    /// Blend the palette in the next interpolation mode, returning it.
+    pub fn next_interpolation(&mut self) -> Interpolation {
+        self.colours.interpolation = self.colours.interpolation.next();
+        self.colours.interpolation
+    }
+
+    /// The palette to colour with, baked again only when it or its
+    /// interpolation has changed.
+    pub fn palette_table(&mut self) -> Arc<Table> {
+        let (palette, interpolation, table) = &mut self.table;
+        if *palette != self.colours.palette || *interpolation != self.colours.interpolation {
+            *palette = self.colours.palette.clone();
+            *interpolation = self.colours.interpolation;
+            *table = Arc::new(palette.bake(*interpolation));
+        }
+        table.clone()
+    }
+
+    pub fn next_shading(&mut self) -> Shading {
+        self.colours.shading = self.colours.shading.next();
+        self.colours.shading
+    }
+
+    /// The precision to draw the current view with at `size`.
+    pub fn resolved_precision(&self, size: PhysicalSize<u32>) -> Precision {
+        Precision::resolve(
+            self.precision,
+            FRACTALS[self.bundle_idx],
+            &self.viewport,
+            [size.width.max(1) as f32, size.height.max(1) as f32],
+        )
+    }
+
+    /// Cursor position as fractions of the window size from the top left.
+    fn cursor_fraction(&self, size: PhysicalSize<u32>) -> (f32, f32) {
+        (
+            (self.mouse_pos.x / size.width.max(1) as f64) as f32,
+            (self.mouse_pos.y / size.height.max(1) as f64) as f32,
+        )
     }
 
     pub fn elapsed_time(&self) -> f32 {
@@ -60,6 +318,48 @@ impl AppState {
         self.last_frame_time = now;
         self.paused_time = Duration::ZERO;
         self.paused = false;
-        self.offset = [0.0, 0.0];
+        let mut viewport = Viewport::default();
+        // The current zoom was shown, so it can be again.
+        let _ = viewport.set_zoom(self.viewport.zoom());
+        self.viewport = viewport;
+    }
+
+    /// Switch to another fractal, restoring its default view and parameters.
+    pub fn select_fractal(&mut self, bundle_idx: usize) {
+        let fractal = FRACTALS[bundle_idx];
+
+        self.reset_time();
+        self.bundle_idx = bundle_idx;
+        self.viewport = fractal.default_view();
+        self.params = fractal.default_params();
+    }
+
+    /// Switch to the next fractal, or the previous one if `backwards`, so
+    /// that every one is reachable past those on the number keys. Returns
+    /// its name.
+    pub fn step_fractal(&mut self, backwards: bool) -> &'static str {
+        let count = FRACTALS.len();
+        let step = if backwards { count - 1 } else { 1 };
+        self.select_fractal((self.bundle_idx + step) % count);
+        FRACTALS[self.bundle_idx].name()
     }
 }
+
+impl Default for Colours {
+    fn default() -> Self {
+        Self {
+            palette: palette::built_in()[0].clone(),
+            interpolation: Interpolation::LinearRgb,
+            palette_offset: 0.0,
+            palette_density: 1.0,
+            colouring: Colouring::Direct,
+            shading: Shading::Flat,
+            light_angle: DEFAULT_LIGHT_ANGLE,
+        }
+    }
+}
+
+/// Width over height of the window, as the shaders see it.
+pub fn aspect_ratio(size: PhysicalSize<u32>) -> f32 {
+    size.width.max(1) as f32 / size.height.max(1) as f32
+}
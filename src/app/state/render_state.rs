@@ -1,9 +1,25 @@
 use std::sync::Arc;
 
-use wgpu::{BindGroup, SurfaceError, SurfaceTexture};
+use wgpu::{BindGroup, SurfaceError, SurfaceTexture, TextureFormat};
 use winit::{dpi::PhysicalSize, window::Window};
 
-use crate::app::renderer::Renderer;
+use fractal::fractals::Custom;
+use fractal::headless::{OFFSCREEN_FORMAT, request_device};
+use fractal::histogram::{self, ValuePass};
+use fractal::offscreen::OffscreenTarget;
+use fractal::renderer::Renderer;
+use fractal::shader::ShaderError;
+use fractal::uniforms::{UniformData, Uniforms};
+
+use super::selection_overlay::SelectionOverlay;
+
+/// A second fractal drawn inset over the first.
+pub struct Preview<'a> {
+    pub uniform_bind_group: &'a BindGroup,
+    pub bundle_idx: usize,
+    /// `[x, y, width, height]` in pixels from the top left.
+    pub rect: [f32; 4],
+}
 
 pub struct RenderState<'a> {
     _instance: wgpu::Instance,
@@ -13,6 +29,11 @@ pub struct RenderState<'a> {
     pub queue: wgpu::Queue,
     config: wgpu::SurfaceConfiguration,
     renderer: Renderer,
+    /// Pipelines for exports, only needed when the surface format cannot be
+    /// read back directly.
+    export_renderer: Option<Renderer>,
+    value_pass: ValuePass,
+    selection_overlay: SelectionOverlay,
 }
 
 impl RenderState<'_> {
@@ -29,16 +50,7 @@ impl RenderState<'_> {
             .await
             .unwrap();
 
-        let (device, queue) = adapter
-            .request_device(&wgpu::DeviceDescriptor {
-                required_features: wgpu::Features::empty(),
-                label: None,
-                required_limits: wgpu::Limits::default(),
-                memory_hints: wgpu::MemoryHints::default(),
-                trace: wgpu::Trace::Off,
-            })
-            .await
-            .unwrap();
+        let (device, queue) = request_device(&adapter).await.unwrap();
 
         let size = window.inner_size();
         let surface_caps = surface.get_capabilities(&adapter);
@@ -57,6 +69,20 @@ impl RenderState<'_> {
 
         let renderer = Renderer::new(&device, &surface_format);
 
+        // Reuse the on-screen pipelines for exports when the readback can
+        // handle the surface format, so the export matches the window exactly.
+        let readable = matches!(
+            surface_format,
+            TextureFormat::Rgba8Unorm
+                | TextureFormat::Rgba8UnormSrgb
+                | TextureFormat::Bgra8Unorm
+                | TextureFormat::Bgra8UnormSrgb
+        );
+        let export_renderer = (!readable).then(|| Renderer::new(&device, &OFFSCREEN_FORMAT));
+
+        let value_pass = ValuePass::new(&device, histogram::value_format(&adapter));
+        let selection_overlay = SelectionOverlay::new(&device, &surface_format);
+
         RenderState {
             _instance: instance,
             surface,
@@ -65,6 +91,9 @@ impl RenderState<'_> {
             queue,
             config,
             renderer,
+            export_renderer,
+            value_pass,
+            selection_overlay,
         }
     }
 
@@ -76,11 +105,43 @@ impl RenderState<'_> {
         }
     }
 
+    /// Rebuild the pipelines whose shader source has changed, keeping the
+    /// old pipeline of any that fail.
+    pub fn reload_shaders(&mut self) -> Vec<ShaderError> {
+        if let Some(export_renderer) = self.export_renderer.as_mut() {
+            // Builds from the same sources, so fails the same way.
+            export_renderer.reload(&self.device);
+        }
+        self.value_pass.reload(&self.device);
+        self.renderer.reload(&self.device)
+    }
+
+    /// Draw `formula` for the formula fractal, see [`Renderer::set_formula`].
+    pub fn set_formula(&mut self, formula: Custom) -> Result<(), ShaderError> {
+        if let Some(export_renderer) = self.export_renderer.as_mut() {
+            export_renderer.set_formula(&self.device, formula.clone())?;
+        }
+        self.value_pass.set_formula(&self.device, formula.clone())?;
+        self.renderer.set_formula(&self.device, formula)
+    }
+
+    pub fn size(&self) -> PhysicalSize<u32> {
+        PhysicalSize::new(self.config.width, self.config.height)
+    }
+
     pub fn get_frame(&self) -> Result<SurfaceTexture, SurfaceError> {
         self.surface.get_current_texture()
     }
 
-    pub fn draw(&mut self, uniform_bind_group: &BindGroup, bundle_idx: usize) {
+    /// Draw the fractal, with the box zoom selection on top when given as
+    /// `[min_x, min_y, max_x, max_y]` in pixels, and the preview over both.
+    pub fn draw(
+        &mut self,
+        uniform_bind_group: &BindGroup,
+        bundle_idx: usize,
+        selection: Option<[f32; 4]>,
+        preview: Option<Preview>,
+    ) {
         let frame = if let Ok(frame) = self.get_frame() {
             frame
         } else {
@@ -91,6 +152,10 @@ impl RenderState<'_> {
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
 
+        if let Some(rect) = selection {
+            self.selection_overlay.update(&self.queue, rect);
+        }
+
         let mut encoder = self
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor {
@@ -115,9 +180,66 @@ impl RenderState<'_> {
 
             self.renderer
                 .draw(&mut rpass, uniform_bind_group, bundle_idx);
+
+            if selection.is_some() {
+                self.selection_overlay.draw(&mut rpass);
+            }
+
+            if let Some(preview) = preview {
+                let [x, y, width, height] = preview.rect;
+                rpass.set_viewport(x, y, width, height, 0.0, 1.0);
+                self.renderer
+                    .draw(&mut rpass, preview.uniform_bind_group, preview.bundle_idx);
+            }
         }
 
         self.queue.submit(Some(encoder.finish()));
         frame.present();
     }
+
+    /// [`ValuePass::equalise`].
+    pub fn equalise(
+        &mut self,
+        uniforms: &mut Uniforms,
+        uniform_data: UniformData,
+        bundle_idx: usize,
+    ) {
+        self.value_pass.equalise(
+            &self.device,
+            &self.queue,
+            uniforms,
+            uniform_data,
+            bundle_idx,
+        );
+    }
+
+    pub fn max_texture_dimension(&self) -> u32 {
+        self.device.limits().max_texture_dimension_2d
+    }
+
+    /// Create a texture that exports can be rendered into.
+    pub fn create_offscreen_target(&self, width: u32, height: u32) -> OffscreenTarget {
+        let format = match self.export_renderer {
+            Some(_) => OFFSCREEN_FORMAT,
+            None => self.config.format,
+        };
+        OffscreenTarget::new(&self.device, width, height, format)
+    }
+
+    /// Render a frame into `target` and return its RGBA8 pixels, top row first.
+    pub fn draw_offscreen(
+        &self,
+        target: &OffscreenTarget,
+        uniform_bind_group: &BindGroup,
+        bundle_idx: usize,
+    ) -> Vec<u8> {
+        let renderer = self.export_renderer.as_ref().unwrap_or(&self.renderer);
+        target.draw(
+            &self.device,
+            &self.queue,
+            renderer,
+            uniform_bind_group,
+            bundle_idx,
+        )
+    }
 }
@@ -0,0 +1,97 @@
+use wgpu::util::DeviceExt;
+use wgpu::{Device, Queue, RenderPass, TextureFormat};
+
+/// Draws the box zoom selection rectangle on top of the fractal.
+pub struct SelectionOverlay {
+    pipeline: wgpu::RenderPipeline,
+    rect_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl SelectionOverlay {
+    pub fn new(device: &Device, surface_format: &TextureFormat) -> Self {
+        let shader =
+            device.create_shader_module(wgpu::include_wgsl!("../../shaders/selection.wgsl"));
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("selection_bind_group_layout"),
+        });
+
+        let rect_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Selection Buffer"),
+            contents: bytemuck::bytes_of(&[0.0f32; 4]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: rect_buffer.as_entire_binding(),
+            }],
+            label: Some("selection_bind_group"),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Selection layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Selection render pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: *surface_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            pipeline,
+            rect_buffer,
+            bind_group,
+        }
+    }
+
+    /// Set the rectangle to draw as `[min_x, min_y, max_x, max_y]` in pixels.
+    pub fn update(&self, queue: &Queue, rect: [f32; 4]) {
+        queue.write_buffer(&self.rect_buffer, 0, bytemuck::bytes_of(&rect));
+    }
+
+    pub fn draw(&self, render_pass: &mut RenderPass) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,352 @@
+use std::path::PathBuf;
+
+use winit::dpi::PhysicalSize;
+
+use fractal::histogram::Colouring;
+use fractal::named::Named;
+use fractal::palette::Interpolation;
+use fractal::shading::{DEFAULT_LIGHT_ANGLE, Shading};
+use fractal::{FRACTALS, Precision, fractals, polynomial};
+
+pub const USAGE: &str = "\
+Usage: fractal [OPTIONS]
+
+Without a mode flag the interactive viewer is opened.
+
+Modes:
+  --headless             Render one frame of every fractal without a window
+  --export <PATH>        Render a single view to a png file and exit
+  --save-palette <PATH>  Write the --palette to a .map, .ggr, .ugr or .palette
+                         file and exit
+
+Options:
+  --size <W>x<H>         Image size for --export and the viewer's P key
+  --fractal <N|NAME>     Fractal to render, by name or numbered as on the number
+                         keys, where 0 is 10 [default: 1]
+  --param <NAME>=<VALUE> Override one of the fractal's parameters
+  --roots <LIST>         Roots of nova's polynomial as complex numbers, like
+                         1,-0.5+0.866i,-0.5-0.866i. Selects nova
+  --coefficients <LIST>  Coefficients of nova's polynomial, highest power
+                         first, like 1,0,0,-1 for z^3 - 1. Selects nova
+  --formula <TEXT>       Formula for the formula fractal, which it selects
+                         unless --fractal is given (see Formulas)
+  --zoom <ZOOM>          Zoom level [default: 1]
+  --offset <X>,<Y>       View offset [default: 0,0]
+  --view <PATH>          Load a view saved with the viewer's V key instead of
+                         --zoom and --offset
+  --time <SECONDS>       Animation time [default: 0]
+  --precision <MODE>     auto, single or double (double-single) [default: auto]
+  --palette <NAME|PATH>  Built-in palette (classic, fire, ocean, grey, rainbow)
+                         or a .palette, Fractint .map, GIMP .ggr or Ultra
+                         Fractal .ugr file, where PATH#NAME picks a gradient
+                         from a .ugr [default: classic]
+  --interpolation <MODE> Blend the palette's colours in rgb (linear), oklab or
+                         hsv [default: rgb]
+  --palette-offset <X>   Shift the palette along by X [default: 0]
+  --palette-density <X>  Repeat the palette X times [default: 1]
+  --colouring <MODE>     direct, or histogram to spread the view's intensities
+                         evenly over the palette [default: direct]
+  --shading <MODE>       flat, boundary to outline escape-time sets by their
+                         distance estimate, or lighting to light them as a
+                         surface [default: flat]
+  --light-angle <DEG>    Direction of the light for lighting, anticlockwise
+                         from the right [default: 45]
+  --fallback             Use the software (fallback) adapter
+  --cpu                  Render on the CPU instead of the GPU
+  --watch-shaders        Load the viewer's shaders from src/shaders and reload
+                         them when they are saved
+  -h, --help             Print this help
+
+Formulas:
+  Statements separated by ; or new lines. z = <EXPR> is iterated and the
+  optional z0 = <EXPR> is where z starts, 0 by default. Expressions are complex
+  and can use z, c, pixel, i, pi, e, numbers like 2.5 or 3i, + - * / ^ and
+  sin cos tan sinh cosh tanh exp log sqrt abs conj re im. For example:
+    --formula \"z0 = c; z = sin(z) * c\"";
+
+pub enum Mode {
+    Viewer,
+    Headless,
+    Export(PathBuf),
+    SavePalette(PathBuf),
+    Help,
+}
+
+pub struct Options {
+    pub mode: Mode,
+    pub size: Option<PhysicalSize<u32>>,
+    pub bundle_idx: usize,
+    pub zoom: f32,
+    pub offset: [f64; 2],
+    /// Saved view to load in place of `zoom` and `offset`.
+    pub view: Option<PathBuf>,
+    pub time: f32,
+    /// `None` picks the precision from the view.
+    pub precision: Option<Precision>,
+    pub force_fallback_adapter: bool,
+    pub cpu: bool,
+    /// Load the viewer's shaders from the source tree and reload them on
+    /// change.
+    pub watch_shaders: bool,
+    pub params: Vec<(String, f32)>,
+    /// Source of the formula fractal, see `fractal::formula`.
+    pub formula: Option<String>,
+    /// Built-in palette name or palette file, see `fractal::palette`.
+    pub palette: Option<String>,
+    pub interpolation: Interpolation,
+    pub palette_offset: f32,
+    pub palette_density: f32,
+    pub colouring: Colouring,
+    pub shading: Shading,
+    /// In radians.
+    pub light_angle: f32,
+}
+
+impl Options {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut options = Options {
+            mode: Mode::Viewer,
+            size: None,
+            bundle_idx: 0,
+            zoom: 1.0,
+            offset: [0.0, 0.0],
+            view: None,
+            time: 0.0,
+            precision: None,
+            force_fallback_adapter: false,
+            cpu: false,
+            watch_shaders: false,
+            params: Vec::new(),
+            formula: None,
+            palette: None,
+            interpolation: Interpolation::LinearRgb,
+            palette_offset: 0.0,
+            palette_density: 1.0,
+            colouring: Colouring::Direct,
+            shading: Shading::Flat,
+            light_angle: DEFAULT_LIGHT_ANGLE,
+        };
+        let mut fractal_given = false;
+        // The flag that set nova's polynomial, if any.
+        let mut polynomial_flag = None;
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("missing value for {arg}"));
+            match arg.as_str() {
+                "--headless" => options.mode = Mode::Headless,
+                "--export" => options.mode = Mode::Export(PathBuf::from(value()?)),
+                "--save-palette" => options.mode = Mode::SavePalette(PathBuf::from(value()?)),
+                "--size" => options.size = Some(parse_size(&value()?)?),
+                "--fractal" => {
+                    options.bundle_idx = parse_fractal(&value()?)?;
+                    fractal_given = true;
+                }
+                "--param" => options.params.push(parse_param(&value()?)?),
+                "--roots" => {
+                    options
+                        .params
+                        .extend(root_params(&parse_complex_list(&value()?)?)?);
+                    polynomial_flag = Some("--roots");
+                }
+                "--coefficients" => {
+                    let coefficients = parse_complex_list(&value()?)?;
+                    options
+                        .params
+                        .extend(root_params(&polynomial::roots(&coefficients))?);
+                    polynomial_flag = Some("--coefficients");
+                }
+                "--zoom" => options.zoom = parse_finite(&value()?)?,
+                "--offset" => options.offset = parse_pair(&value()?)?,
+                "--view" => options.view = Some(PathBuf::from(value()?)),
+                "--time" => options.time = parse_finite(&value()?)?,
+                "--precision" => options.precision = parse_precision(&value()?)?,
+                "--palette" => options.palette = Some(value()?),
+                "--interpolation" => options.interpolation = parse_interpolation(&value()?)?,
+                "--palette-offset" => options.palette_offset = parse_finite(&value()?)?,
+                "--palette-density" => options.palette_density = parse_density(&value()?)?,
+                "--colouring" => options.colouring = parse_colouring(&value()?)?,
+                "--shading" => options.shading = parse_shading(&value()?)?,
+                "--light-angle" => {
+                    options.light_angle = parse_finite::<f32>(&value()?)?.to_radians()
+                }
+                "--fallback" => options.force_fallback_adapter = true,
+                "--cpu" => options.cpu = true,
+                "--watch-shaders" => options.watch_shaders = true,
+                "--formula" => options.formula = Some(value()?),
+                "-h" | "--help" => options.mode = Mode::Help,
+                _ => return Err(format!("unknown argument: {arg}")),
+            }
+        }
+
+        if let Some(flag) = polynomial_flag {
+            let nova = fractals::find("nova").expect("nova is registered");
+            if fractal_given && options.bundle_idx != nova {
+                return Err(format!(
+                    "{flag} sets nova's polynomial, but --fractal selects {}",
+                    FRACTALS[options.bundle_idx].name()
+                ));
+            }
+            if options.formula.is_some() && !fractal_given {
+                return Err(format!(
+                    "{flag} selects nova and --formula the formula fractal, \
+                     pick one with --fractal"
+                ));
+            }
+            options.bundle_idx = nova;
+        } else if options.formula.is_some() && !fractal_given {
+            options.bundle_idx = fractals::find("formula").expect("formula is registered");
+        }
+
+        Ok(options)
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid number: {value}"))
+}
+
+/// A number that is neither infinite nor NaN, which `parse` lets through.
+fn parse_finite<T: std::str::FromStr + Into<f64> + Copy>(value: &str) -> Result<T, String> {
+    let number: T = parse_number(value)?;
+    if number.into().is_finite() {
+        Ok(number)
+    } else {
+        Err(format!("number must be finite: {value}"))
+    }
+}
+
+fn parse_size(value: &str) -> Result<PhysicalSize<u32>, String> {
+    let (width, height) = value
+        .split_once('x')
+        .ok_or(format!("invalid size, expected <W>x<H>: {value}"))?;
+    Ok(PhysicalSize::new(
+        parse_number(width)?,
+        parse_number(height)?,
+    ))
+}
+
+fn parse_pair(value: &str) -> Result<[f64; 2], String> {
+    let (x, y) = value
+        .split_once(',')
+        .ok_or(format!("invalid pair, expected <X>,<Y>: {value}"))?;
+    Ok([parse_finite(x)?, parse_finite(y)?])
+}
+
+fn parse_fractal(value: &str) -> Result<usize, String> {
+    let bundle_idx = match value.parse::<usize>() {
+        // As on the number keys, 0 follows 9.
+        Ok(0) => Some(9),
+        Ok(n) => Some(n - 1),
+        Err(_) => fractals::find(value),
+    };
+
+    bundle_idx
+        .filter(|idx| *idx < FRACTALS.len())
+        .ok_or_else(|| {
+            let names: Vec<_> = FRACTALS.iter().map(|fractal| fractal.name()).collect();
+            format!(
+                "unknown fractal {value}, expected 0-{} or one of: {}",
+                FRACTALS.len(),
+                names.join(", ")
+            )
+        })
+}
+
+fn parse_param(value: &str) -> Result<(String, f32), String> {
+    let (name, number) = value.split_once('=').ok_or(format!(
+        "invalid parameter, expected <NAME>=<VALUE>: {value}"
+    ))?;
+    Ok((name.trim().to_string(), parse_number(number)?))
+}
+
+/// Parse a comma separated list of complex numbers.
+fn parse_complex_list(value: &str) -> Result<Vec<[f64; 2]>, String> {
+    value.split(',').map(parse_complex).collect()
+}
+
+/// Parse a complex number written as `a`, `bi` or `a+bi`.
+fn parse_complex(value: &str) -> Result<[f64; 2], String> {
+    let value = value.trim();
+    let invalid = || format!("invalid complex number, expected <A>+<B>i: {value}");
+    let Some(imaginary) = value.strip_suffix('i') else {
+        return Ok([parse_number(value)?, 0.0]);
+    };
+
+    // The sign that starts the imaginary part, skipping a leading sign and
+    // those of exponents.
+    let split = imaginary
+        .char_indices()
+        .skip(1)
+        .filter(|(i, c)| matches!(c, '+' | '-') && !imaginary[..*i].ends_with(['e', 'E']))
+        .map(|(i, _)| i)
+        .last()
+        .unwrap_or(0);
+    let (real, imaginary) = imaginary.split_at(split);
+    let imaginary = match imaginary {
+        "" | "+" => 1.0,
+        "-" => -1.0,
+        _ => imaginary.parse().map_err(|_| invalid())?,
+    };
+    let real = if real.is_empty() {
+        0.0
+    } else {
+        real.parse().map_err(|_| invalid())?
+    };
+    Ok([real, imaginary])
+}
+
+/// The parameters that set nova's polynomial to the one with `roots`.
+fn root_params(roots: &[[f64; 2]]) -> Result<Vec<(String, f32)>, String> {
+    if roots.is_empty() {
+        return Err("the polynomial needs at least one root".to_string());
+    }
+
+    let mut params = vec![("degree".to_string(), roots.len() as f32)];
+    for (k, [x, y]) in roots.iter().enumerate() {
+        params.push((format!("root{}_x", k + 1), *x as f32));
+        params.push((format!("root{}_y", k + 1), *y as f32));
+    }
+    Ok(params)
+}
+
+fn parse_precision(value: &str) -> Result<Option<Precision>, String> {
+    match value {
+        "auto" => Ok(None),
+        "single" => Ok(Some(Precision::Single)),
+        "double" => Ok(Some(Precision::DoubleSingle)),
+        _ => Err(format!(
+            "unknown precision {value}, expected auto, single or double"
+        )),
+    }
+}
+
+fn parse_interpolation(value: &str) -> Result<Interpolation, String> {
+    Interpolation::from_name(value).ok_or(format!(
+        "unknown interpolation {value}, expected rgb, oklab or hsv"
+    ))
+}
+
+fn parse_density(value: &str) -> Result<f32, String> {
+    let density: f32 = parse_number(value)?;
+    if density > 0.0 {
+        Ok(density)
+    } else {
+        Err(format!("palette density must be positive, got {value}"))
+    }
+}
+
+fn parse_colouring(value: &str) -> Result<Colouring, String> {
+    Colouring::from_name(value).ok_or(format!(
+        "unknown colouring {value}, expected direct or histogram"
+    ))
+}
+
+fn parse_shading(value: &str) -> Result<Shading, String> {
+    Shading::from_name(value).ok_or(format!(
+        "unknown shading {value}, expected flat, boundary or lighting"
+    ))
+}
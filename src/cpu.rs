@@ -0,0 +1,244 @@
+//! Pure-Rust versions of the fractal shaders.
+//!
+//! Each fractal's [`Fractal::sample`] mirrors its WGSL `fs_main` line for line,
+//! in `f32`, so the output can stand in for the GPU when no adapter is
+//! available and serve as a reference when checking GPU output. The helpers
+//! here mirror the shared parts of the shaders.
+
+pub mod double_single;
+
+use std::path::Path;
+
+use rayon::prelude::*;
+
+use crate::export::{ExportError, check_size, write_png};
+use crate::fractals::Fractal;
+use crate::histogram::{self, Colouring, Equaliser};
+use crate::palette::Table;
+use crate::perturbation::{GlitchSearch, Orbit, Reference};
+use crate::tiled::TileGrid;
+use crate::uniforms::UniformData;
+
+/// Largest tile rendered in one go when exporting, to bound memory use.
+const TILE_DIMENSION: u32 = 4096;
+
+/// What [`get_colour`] picks colours from. CPU counterpart of the shaders'
+/// palette and equaliser buffers.
+#[derive(Clone, Copy, Debug)]
+pub struct ColourTables<'a> {
+    pub palette: &'a Table,
+    /// Used for histogram colouring only.
+    pub equaliser: &'a Equaliser,
+}
+
+/// Render a frame and return its RGBA8 pixels, top row first, encoded to
+/// sRGB exactly like the offscreen GPU target. The view's aspect ratio
+/// comes from `uniform_data.resolution`; `orbit` is `fractal`'s reference
+/// orbit, if it has one, and `palette` the one to colour it with.
+pub fn render(
+    width: u32,
+    height: u32,
+    uniform_data: &UniformData,
+    orbit: &Orbit,
+    fractal: &dyn Fractal,
+    palette: &Table,
+) -> Vec<u8> {
+    let equaliser = equaliser(uniform_data, orbit, fractal, palette);
+    let tables = ColourTables {
+        palette,
+        equaliser: &equaliser,
+    };
+    render_tile(width, height, uniform_data, orbit, fractal, tables)
+}
+
+/// CPU counterpart of `HeadlessState::orbit`.
+pub fn orbit(reference: &Reference, uniform_data: &UniformData, fractal: &dyn Fractal) -> Orbit {
+    let search = GlitchSearch::new(*uniform_data);
+    // Glitches are drawn without the palette.
+    let palette = Table::default();
+    reference.orbit_without_glitches(|orbit| {
+        let pixels = render(
+            search.width,
+            search.height,
+            &search.uniform_data,
+            orbit,
+            fractal,
+            &palette,
+        );
+        search.glitch(&pixels)
+    })
+}
+
+/// [`render`] with the equaliser already worked out, so that every tile of
+/// an image shares it.
+fn render_tile(
+    width: u32,
+    height: u32,
+    uniform_data: &UniformData,
+    orbit: &Orbit,
+    fractal: &dyn Fractal,
+    tables: ColourTables,
+) -> Vec<u8> {
+    let mut pixels = vec![0u8; width as usize * height as usize * 4];
+    pixels
+        .par_chunks_exact_mut(width as usize * 4)
+        .enumerate()
+        .for_each(|(y, row)| {
+            for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+                let uv = pixel_uv(x, y, width, height, uniform_data.tile);
+                let colour = fractal.sample(uv, uniform_data, orbit, tables);
+                pixel[0] = encode_srgb(colour[0]);
+                pixel[1] = encode_srgb(colour[1]);
+                pixel[2] = encode_srgb(colour[2]);
+                pixel[3] = 255;
+            }
+        });
+
+    pixels
+}
+
+/// The uv of the centre of a pixel in a `width` by `height` image of
+/// `tile`. uv has its origin in the bottom left.
+pub(crate) fn pixel_uv(x: usize, y: usize, width: u32, height: u32, tile: [f32; 4]) -> [f32; 2] {
+    let [tile_x, tile_y, tile_w, tile_h] = tile;
+    let u = (x as f32 + 0.5) / width as f32;
+    let v = 1.0 - (y as f32 + 0.5) / height as f32;
+    [tile_x + u * tile_w, tile_y + v * tile_h]
+}
+
+/// CPU counterpart of `ValuePass::equalise`: the equaliser for the whole
+/// view if `uniform_data` asks for histogram colouring, or else one that
+/// changes nothing.
+fn equaliser(
+    uniform_data: &UniformData,
+    orbit: &Orbit,
+    fractal: &dyn Fractal,
+    palette: &Table,
+) -> Equaliser {
+    if uniform_data.colouring != Colouring::Histogram as u32 {
+        return Equaliser::default();
+    }
+
+    // The first pass writes out intensities without equalising them.
+    let unused = Equaliser::default();
+    let tables = ColourTables {
+        palette,
+        equaliser: &unused,
+    };
+    let (width, height) = histogram::sample_size(uniform_data.resolution);
+    let uniform_data = UniformData {
+        tile: [0.0, 0.0, 1.0, 1.0],
+        colouring: Colouring::Values as u32,
+        ..*uniform_data
+    };
+    let values: Vec<f32> = (0..height as usize)
+        .into_par_iter()
+        .flat_map_iter(|y| {
+            (0..width as usize).filter_map(move |x| {
+                let uv = pixel_uv(x, y, width, height, uniform_data.tile);
+                let value = fractal.sample(uv, &uniform_data, orbit, tables);
+                histogram::value_intensity(value[0], f32::EPSILON)
+            })
+        })
+        .collect();
+    Equaliser::from_values(values)
+}
+
+/// CPU counterpart of `HeadlessState::export_png`.
+pub fn export_png(
+    path: &Path,
+    width: u32,
+    height: u32,
+    uniform_data: UniformData,
+    orbit: &Orbit,
+    fractal: &dyn Fractal,
+    palette: &Table,
+) -> Result<(), ExportError> {
+    check_size(width, height)?;
+
+    let grid = TileGrid::new(width, height, TILE_DIMENSION);
+    let uniform_data = UniformData {
+        resolution: [width as f32, height as f32],
+        ..uniform_data
+    };
+    let equaliser = equaliser(&uniform_data, orbit, fractal, palette);
+    let tables = ColourTables {
+        palette,
+        equaliser: &equaliser,
+    };
+
+    write_png(path, &grid, |tile| {
+        render_tile(
+            grid.tile_width,
+            grid.tile_height,
+            &uniform_data.with_tile(tile),
+            orbit,
+            fractal,
+            tables,
+        )
+    })
+}
+
+/// WGSL `clamp`, which is `min(max(e, low), high)` and so maps NaN to `low`
+/// on GPUs, unlike `f32::clamp`.
+pub fn clamp(e: f32, low: f32, high: f32) -> f32 {
+    e.max(low).min(high)
+}
+
+fn encode_srgb(linear: f32) -> u8 {
+    let c = clamp(linear, 0.0, 1.0);
+    let encoded = if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    };
+    (encoded * 255.0).round() as u8
+}
+
+/// CPU version of `get_colour` in `prelude.wgsl`.
+pub fn get_colour(intensity: f32, uniforms: &UniformData, tables: ColourTables) -> [f32; 3] {
+    let t = match uniforms.colouring {
+        colouring if colouring == Colouring::Values as u32 => {
+            return [-1.0 - intensity.clamp(0.0, 1.0), 0.0, 0.0];
+        }
+        colouring if colouring == Colouring::Glitches as u32 => return [0.0; 3],
+        colouring if colouring == Colouring::Histogram as u32 => {
+            tables.equaliser.equalise(intensity)
+        }
+        _ => intensity,
+    };
+    tables
+        .palette
+        .lookup(t, uniforms.palette_offset, uniforms.palette_density)
+}
+
+/// CPU version of `view_delta` in `prelude.wgsl`: how far the point at `uv`
+/// is from the centre of the view, where the shorter side of the view spans
+/// 3 at zoom 0.
+pub fn view_delta(uv: [f32; 2], uniforms: &UniformData) -> [f32; 2] {
+    let zoom = 1.2f32.powf(uniforms.zoom);
+    let aspect = uniforms.resolution[0] / uniforms.resolution[1];
+    let scale = [aspect.max(1.0), (1.0 / aspect).max(1.0)];
+    [
+        (uv[0] * 2.0 - 1.0) * scale[0] * (1.5 / zoom),
+        (uv[1] * 2.0 - 1.0) * scale[1] * (1.5 / zoom),
+    ]
+}
+
+/// CPU version of `view_point` in `prelude.wgsl`: the point of the plane at
+/// `uv`.
+pub fn view_point(uv: [f32; 2], uniforms: &UniformData) -> [f32; 2] {
+    let [dx, dy] = view_delta(uv, uniforms);
+    [dx - uniforms.offset[0], dy + uniforms.offset[1]]
+}
+
+/// CPU version of `escape_intensity` in `prelude.wgsl`: the intensity of a
+/// point that escaped after `iterations`, out of `max_iter`, spread over the
+/// palette on a log scale as most points escape early.
+pub fn escape_intensity(iterations: f32, max_iter: u32) -> f32 {
+    clamp(
+        (iterations.max(0.0) + 1.0).log2() / (max_iter as f32 + 1.0).log2(),
+        0.0,
+        1.0,
+    )
+}
@@ -0,0 +1,55 @@
+//! CPU mirror of `double_single.wgsl`: each value is `[hi, lo]` with `lo`
+//! at most half an ulp of `hi`.
+
+pub type DoubleSingle = [f32; 2];
+
+pub fn ds(a: f32) -> DoubleSingle {
+    [a, 0.0]
+}
+
+fn two_sum(a: f32, b: f32) -> DoubleSingle {
+    let s = a + b;
+    let v = s - a;
+    let e = (a - (s - v)) + (b - v);
+    [s, e]
+}
+
+fn quick_two_sum(a: f32, b: f32) -> DoubleSingle {
+    let s = a + b;
+    let e = b - (s - a);
+    [s, e]
+}
+
+fn split(a: f32) -> DoubleSingle {
+    let t = 4097.0 * a;
+    let hi = t - (t - a);
+    [hi, a - hi]
+}
+
+fn two_prod(a: f32, b: f32) -> DoubleSingle {
+    let p = a * b;
+    let sa = split(a);
+    let sb = split(b);
+    let e = ((sa[0] * sb[0] - p) + sa[0] * sb[1] + sa[1] * sb[0]) + sa[1] * sb[1];
+    [p, e]
+}
+
+pub fn add(a: DoubleSingle, b: DoubleSingle) -> DoubleSingle {
+    let s = two_sum(a[0], b[0]);
+    quick_two_sum(s[0], s[1] + a[1] + b[1])
+}
+
+pub fn sub(a: DoubleSingle, b: DoubleSingle) -> DoubleSingle {
+    add(a, [-b[0], -b[1]])
+}
+
+pub fn mul(a: DoubleSingle, b: DoubleSingle) -> DoubleSingle {
+    let p = two_prod(a[0], b[0]);
+    quick_two_sum(p[0], p[1] + (a[0] * b[1] + a[1] * b[0]))
+}
+
+pub fn div(a: DoubleSingle, b: DoubleSingle) -> DoubleSingle {
+    let q = a[0] / b[0];
+    let r = sub(a, mul(b, ds(q)));
+    quick_two_sum(q, r[0] / b[0])
+}
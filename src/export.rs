@@ -0,0 +1,71 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::tiled::TileGrid;
+
+#[derive(Debug)]
+pub enum ExportError {
+    Io(std::io::Error),
+    Png(png::EncodingError),
+    Size { width: u32, height: u32 },
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportError::Io(e) => write!(f, "failed to write image: {e}"),
+            ExportError::Png(e) => write!(f, "failed to encode png: {e}"),
+            ExportError::Size { width, height } => {
+                write!(f, "cannot render an empty {width}x{height} image")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ExportError {}
+
+impl From<std::io::Error> for ExportError {
+    fn from(e: std::io::Error) -> Self {
+        ExportError::Io(e)
+    }
+}
+
+impl From<png::EncodingError> for ExportError {
+    fn from(e: png::EncodingError) -> Self {
+        ExportError::Png(e)
+    }
+}
+
+/// Check that an image of the given size can be written at all.
+pub fn check_size(width: u32, height: u32) -> Result<(), ExportError> {
+    if width == 0 || height == 0 {
+        return Err(ExportError::Size { width, height });
+    }
+    Ok(())
+}
+
+/// Render `grid` tile by tile and stream it to a png file, so only one row of
+/// tiles is ever held in memory.
+pub fn write_png(
+    path: &Path,
+    grid: &TileGrid,
+    draw_tile: impl FnMut([f32; 4]) -> Vec<u8>,
+) -> Result<(), ExportError> {
+    let file = File::create(path)?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), grid.width, grid.height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+
+    let mut writer = encoder.write_header()?;
+    {
+        let mut stream = writer.stream_writer()?;
+        grid.render(draw_tile, |rows| stream.write_all(rows))?;
+        stream.finish()?;
+    }
+    writer.finish()?;
+
+    Ok(())
+}
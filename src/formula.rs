@@ -0,0 +1,716 @@
+//! A small language for escape-time formulas, compiled to WGSL.
+//!
+//! A formula is one or more statements separated by `;` or new lines:
+//!
+//! ```text
+//! z0 = c
+//! z = sin(z) * c
+//! ```
+//!
+//! `z = ...` is applied every iteration and `z0 = ...` gives the starting
+//! value, 0 when left out. Every value is complex. Expressions can use `z`,
+//! `c`, `pixel`, the constants `i`, `pi` and `e`, numbers such as `2.5` or
+//! `3i`, the operators `+ - * / ^` and the functions in [`Function`].
+//!
+//! The same tree is compiled into `formula.wgsl` for the GPU and evaluated
+//! directly for the CPU renderer.
+
+use std::fmt;
+
+use wgpu::naga;
+
+use crate::named::{Named, impl_named};
+use crate::shader;
+
+/// Shader file the compiled formula is spliced into, at `{{z0}}` and
+/// `{{step}}`.
+const TEMPLATE: &str = "formula.wgsl";
+/// Largest integer power computed by repeated multiplication rather than
+/// through `exp` and `log`.
+const MAX_INTEGER_POWER: f32 = 64.0;
+/// Deepest brackets, calls and operators may nest, well within the stack
+/// and naga's own recursion limit.
+const MAX_DEPTH: usize = 64;
+
+/// A parsed formula together with its shader.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Formula {
+    text: String,
+    z0: Option<Expr>,
+    step: Expr,
+}
+
+/// Why a formula was rejected.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FormulaError {
+    /// The text is not a formula. Lines and columns count from 1.
+    Syntax {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    /// The generated shader failed validation, as naga reports it.
+    Shader(String),
+}
+
+impl fmt::Display for FormulaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormulaError::Syntax {
+                line,
+                column,
+                message,
+            } => write!(f, "formula line {line}, column {column}: {message}"),
+            FormulaError::Shader(message) => {
+                write!(f, "formula compiled to an invalid shader:\n{message}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for FormulaError {}
+
+/// Functions a formula can call, each of one complex argument.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Function {
+    Sin,
+    Cos,
+    Tan,
+    Sinh,
+    Cosh,
+    Tanh,
+    Exp,
+    /// The principal natural logarithm.
+    Log,
+    /// The principal square root.
+    Sqrt,
+    /// The modulus, as a real number.
+    Abs,
+    Conj,
+    /// The real part.
+    Re,
+    /// The imaginary part, as a real number.
+    Im,
+}
+
+// Named by what formulas call them.
+impl_named!(Function {
+    Sin => "sin",
+    Cos => "cos",
+    Tan => "tan",
+    Sinh => "sinh",
+    Cosh => "cosh",
+    Tanh => "tanh",
+    Exp => "exp",
+    Log => "log",
+    Sqrt => "sqrt",
+    Abs => "abs",
+    Conj => "conj",
+    Re => "re",
+    Im => "im",
+});
+
+impl Function {
+    /// The `formula.wgsl` function computing it.
+    fn wgsl_name(self) -> &'static str {
+        match self {
+            Function::Sin => "c_sin",
+            Function::Cos => "c_cos",
+            Function::Tan => "c_tan",
+            Function::Sinh => "c_sinh",
+            Function::Cosh => "c_cosh",
+            Function::Tanh => "c_tanh",
+            Function::Exp => "c_exp",
+            Function::Log => "c_log",
+            Function::Sqrt => "c_sqrt",
+            Function::Abs => "c_abs",
+            Function::Conj => "c_conj",
+            Function::Re => "c_re",
+            Function::Im => "c_im",
+        }
+    }
+
+    fn apply(self, a: [f32; 2]) -> [f32; 2] {
+        match self {
+            Function::Sin => sin(a),
+            Function::Cos => cos(a),
+            Function::Tan => div(sin(a), cos(a)),
+            Function::Sinh => sinh(a),
+            Function::Cosh => cosh(a),
+            Function::Tanh => div(sinh(a), cosh(a)),
+            Function::Exp => exp(a),
+            Function::Log => log(a),
+            Function::Sqrt => sqrt(a),
+            Function::Abs => [length(a), 0.0],
+            Function::Conj => [a[0], -a[1]],
+            Function::Re => [a[0], 0.0],
+            Function::Im => [a[1], 0.0],
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Variable {
+    Z,
+    C,
+    Pixel,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Power,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Expr {
+    Number([f32; 2]),
+    Variable(Variable),
+    Negate(Box<Expr>),
+    Binary(Operator, Box<Expr>, Box<Expr>),
+    Call(Function, Box<Expr>),
+}
+
+/// Values of the variables while evaluating on the CPU.
+struct Values {
+    z: [f32; 2],
+    c: [f32; 2],
+    pixel: [f32; 2],
+}
+
+impl Formula {
+    /// Parse `text` and compile it, checking the shader with naga.
+    pub fn parse(text: &str) -> Result<Self, FormulaError> {
+        let tokens = tokenize(text)?;
+        let (z0, step) = Parser {
+            text,
+            tokens: &tokens,
+            next: 0,
+            z_defined: true,
+            depth: 0,
+        }
+        .program()?;
+
+        let formula = Self {
+            text: text.to_string(),
+            z0,
+            step,
+        };
+        let wgsl = shader::preprocess("formula", &formula.wgsl())
+            .map_err(|e| FormulaError::Shader(e.to_string()))?;
+        validate(&wgsl)?;
+        Ok(formula)
+    }
+
+    /// The formula as written.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The complete shader, with `vs_main` and `fs_main` entry points, before
+    /// [`shader::preprocess`].
+    pub fn wgsl(&self) -> String {
+        let z0 = self
+            .z0
+            .as_ref()
+            .map_or("vec2<f32>(0.0, 0.0)".to_string(), Expr::wgsl);
+        shader::source(TEMPLATE)
+            .replace("{{z0}}", &z0)
+            .replace("{{step}}", &self.step.wgsl())
+    }
+
+    /// CPU version of `formula_z0` in the shader.
+    pub fn start(&self, c: [f32; 2], pixel: [f32; 2]) -> [f32; 2] {
+        let values = Values {
+            z: [0.0, 0.0],
+            c,
+            pixel,
+        };
+        self.z0.as_ref().map_or([0.0, 0.0], |z0| z0.eval(&values))
+    }
+
+    /// CPU version of `formula_step` in the shader.
+    pub fn step(&self, z: [f32; 2], c: [f32; 2], pixel: [f32; 2]) -> [f32; 2] {
+        self.step.eval(&Values { z, c, pixel })
+    }
+}
+
+/// Check `wgsl` the way wgpu will when it is turned into a pipeline.
+fn validate(wgsl: &str) -> Result<(), FormulaError> {
+    let module = naga::front::wgsl::parse_str(wgsl)
+        .map_err(|e| FormulaError::Shader(e.emit_to_string(wgsl)))?;
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::default(),
+    )
+    .validate(&module)
+    .map_err(|e| FormulaError::Shader(e.emit_to_string(wgsl)))?;
+    Ok(())
+}
+
+impl Expr {
+    /// Nodes on the longest path from this one down to a value.
+    fn depth(&self) -> usize {
+        match self {
+            Expr::Number(_) | Expr::Variable(_) => 1,
+            Expr::Negate(a) | Expr::Call(_, a) => 1 + a.depth(),
+            Expr::Binary(_, a, b) => 1 + a.depth().max(b.depth()),
+        }
+    }
+
+    /// The exponent if it is a small whole number, which is raised to by
+    /// repeated multiplication.
+    fn integer_power(&self) -> Option<i32> {
+        match self {
+            Expr::Number([x, y])
+                if *y == 0.0 && x.fract() == 0.0 && x.abs() <= MAX_INTEGER_POWER =>
+            {
+                Some(*x as i32)
+            }
+            _ => None,
+        }
+    }
+
+    /// A WGSL expression of type `vec2<f32>`.
+    fn wgsl(&self) -> String {
+        match self {
+            Expr::Number([x, y]) => format!("vec2<f32>({x:?}, {y:?})"),
+            Expr::Variable(Variable::Z) => "z".to_string(),
+            Expr::Variable(Variable::C) => "c".to_string(),
+            Expr::Variable(Variable::Pixel) => "pixel".to_string(),
+            Expr::Negate(a) => format!("(-{})", a.wgsl()),
+            Expr::Binary(operator, a, b) => {
+                let (a, b_wgsl) = (a.wgsl(), b.wgsl());
+                match operator {
+                    Operator::Add => format!("({a} + {b_wgsl})"),
+                    Operator::Subtract => format!("({a} - {b_wgsl})"),
+                    Operator::Multiply => format!("c_mul({a}, {b_wgsl})"),
+                    Operator::Divide => format!("c_div({a}, {b_wgsl})"),
+                    Operator::Power => match b.integer_power() {
+                        Some(n) => format!("c_powi({a}, {n})"),
+                        None => format!("c_pow({a}, {b_wgsl})"),
+                    },
+                }
+            }
+            Expr::Call(function, a) => format!("{}({})", function.wgsl_name(), a.wgsl()),
+        }
+    }
+
+    fn eval(&self, values: &Values) -> [f32; 2] {
+        match self {
+            Expr::Number(value) => *value,
+            Expr::Variable(Variable::Z) => values.z,
+            Expr::Variable(Variable::C) => values.c,
+            Expr::Variable(Variable::Pixel) => values.pixel,
+            Expr::Negate(a) => {
+                let a = a.eval(values);
+                [-a[0], -a[1]]
+            }
+            Expr::Binary(operator, a, b) => {
+                let (a, b_value) = (a.eval(values), b.eval(values));
+                match operator {
+                    Operator::Add => [a[0] + b_value[0], a[1] + b_value[1]],
+                    Operator::Subtract => [a[0] - b_value[0], a[1] - b_value[1]],
+                    Operator::Multiply => mul(a, b_value),
+                    Operator::Divide => div(a, b_value),
+                    Operator::Power => match b.integer_power() {
+                        Some(n) => powi(a, n),
+                        None => pow(a, b_value),
+                    },
+                }
+            }
+            Expr::Call(function, a) => function.apply(a.eval(values)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number([f32; 2]),
+    Name(String),
+    Symbol(char),
+    /// `;` or a new line.
+    Separator,
+}
+
+/// Split `text` into tokens with the byte offsets they start at.
+fn tokenize(text: &str) -> Result<Vec<(Token, usize)>, FormulaError> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+
+    while let Some(&(start, ch)) = chars.peek() {
+        if ch == '\n' || ch == ';' {
+            chars.next();
+            tokens.push((Token::Separator, start));
+        } else if ch.is_whitespace() {
+            chars.next();
+        } else if ch.is_ascii_digit() || ch == '.' {
+            let mut end = start;
+            while let Some(&(i, c)) = chars.peek() {
+                let exponent = matches!(c, 'e' | 'E') && {
+                    let rest = &text[i + 1..];
+                    let rest = rest.strip_prefix(['+', '-']).unwrap_or(rest);
+                    rest.starts_with(|c: char| c.is_ascii_digit())
+                };
+                let sign = matches!(c, '+' | '-') && text[..i].ends_with(['e', 'E']);
+                if !(c.is_ascii_digit() || c == '.' || exponent || sign) {
+                    break;
+                }
+                end = i + c.len_utf8();
+                chars.next();
+            }
+
+            let value: f32 = text[start..end]
+                .parse()
+                .ok()
+                .filter(|value: &f32| value.is_finite())
+                .ok_or_else(|| {
+                    syntax_error(text, start, format!("invalid number {}", &text[start..end]))
+                })?;
+
+            // A trailing i makes it imaginary, unless it starts a name.
+            let imaginary = text[end..].starts_with('i')
+                && !text[end + 1..].starts_with(|c: char| c.is_alphanumeric() || c == '_');
+            if imaginary {
+                chars.next();
+                tokens.push((Token::Number([0.0, value]), start));
+            } else {
+                tokens.push((Token::Number([value, 0.0]), start));
+            }
+        } else if ch.is_alphabetic() || ch == '_' {
+            let mut end = start;
+            while let Some(&(i, c)) = chars.peek() {
+                if !(c.is_alphanumeric() || c == '_') {
+                    break;
+                }
+                end = i + c.len_utf8();
+                chars.next();
+            }
+            tokens.push((Token::Name(text[start..end].to_string()), start));
+        } else if "+-*/^()=".contains(ch) {
+            chars.next();
+            tokens.push((Token::Symbol(ch), start));
+        } else {
+            return Err(syntax_error(text, start, format!("unexpected {ch:?}")));
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn syntax_error(text: &str, at: usize, message: String) -> FormulaError {
+    let before = &text[..at];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    FormulaError::Syntax {
+        line,
+        column: before[line_start..].chars().count() + 1,
+        message,
+    }
+}
+
+/// Recursive descent over the tokens, one method per precedence level.
+struct Parser<'a> {
+    text: &'a str,
+    tokens: &'a [(Token, usize)],
+    next: usize,
+    /// Whether `z` has a value in the expression being parsed.
+    z_defined: bool,
+    /// Brackets, calls and operators the parser is inside of.
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next).map(|(token, _)| token)
+    }
+
+    /// Byte offset of the next token, or the end of the text.
+    fn offset(&self) -> usize {
+        self.tokens
+            .get(self.next)
+            .map_or(self.text.len(), |(_, at)| *at)
+    }
+
+    fn error(&self, message: impl Into<String>) -> FormulaError {
+        syntax_error(self.text, self.offset(), message.into())
+    }
+
+    fn too_deep(&self, at: usize) -> FormulaError {
+        syntax_error(self.text, at, "formula nested too deeply".to_string())
+    }
+
+    /// Parse with `parse` one level further in, so that deeply nested text
+    /// is an error rather than overflowing the stack.
+    fn nested(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<Expr, FormulaError>,
+    ) -> Result<Expr, FormulaError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(self.too_deep(self.offset()));
+        }
+        self.depth += 1;
+        let expr = parse(self);
+        self.depth -= 1;
+        expr
+    }
+
+    /// `expr`, built from the operator at byte offset `at`, unless it is too
+    /// deep to compile and evaluate.
+    fn node(&self, at: usize, expr: Expr) -> Result<Expr, FormulaError> {
+        if expr.depth() > MAX_DEPTH {
+            return Err(self.too_deep(at));
+        }
+        Ok(expr)
+    }
+
+    fn eat(&mut self, symbol: char) -> bool {
+        let found = self.peek() == Some(&Token::Symbol(symbol));
+        if found {
+            self.next += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), FormulaError> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected '{symbol}'")))
+        }
+    }
+
+    /// The statements, as the `z0` and `z` expressions.
+    fn program(mut self) -> Result<(Option<Expr>, Expr), FormulaError> {
+        let (mut z0, mut step) = (None, None);
+
+        loop {
+            while self.peek() == Some(&Token::Separator) {
+                self.next += 1;
+            }
+            let Some(token) = self.peek() else {
+                break;
+            };
+
+            let is_z0 = match token {
+                Token::Name(name) if name == "z" => false,
+                Token::Name(name) if name == "z0" => true,
+                _ => return Err(self.error("expected a statement, z = ... or z0 = ...")),
+            };
+            if (is_z0 && z0.is_some()) || (!is_z0 && step.is_some()) {
+                return Err(self.error("assigned twice"));
+            }
+            self.next += 1;
+            self.expect('=')?;
+
+            self.z_defined = !is_z0;
+            let expr = self.expr()?;
+            if is_z0 {
+                z0 = Some(expr);
+            } else {
+                step = Some(expr);
+            }
+
+            match self.peek() {
+                None | Some(Token::Separator) => (),
+                Some(_) => return Err(self.error("expected an operator or the end of the line")),
+            }
+        }
+
+        let step = step.ok_or_else(|| self.error("missing the iteration, as in z = z^2 + c"))?;
+        Ok((z0, step))
+    }
+
+    fn expr(&mut self) -> Result<Expr, FormulaError> {
+        let mut expr = self.term()?;
+        loop {
+            let at = self.offset();
+            let operator = if self.eat('+') {
+                Operator::Add
+            } else if self.eat('-') {
+                Operator::Subtract
+            } else {
+                return Ok(expr);
+            };
+            let term = self.term()?;
+            expr = self.node(at, Expr::Binary(operator, Box::new(expr), Box::new(term)))?;
+        }
+    }
+
+    fn term(&mut self) -> Result<Expr, FormulaError> {
+        let mut expr = self.unary()?;
+        loop {
+            let at = self.offset();
+            let operator = if self.eat('*') {
+                Operator::Multiply
+            } else if self.eat('/') {
+                Operator::Divide
+            } else {
+                return Ok(expr);
+            };
+            let factor = self.unary()?;
+            expr = self.node(at, Expr::Binary(operator, Box::new(expr), Box::new(factor)))?;
+        }
+    }
+
+    /// Negation binds looser than `^`, so `-z^2` is `-(z^2)`.
+    fn unary(&mut self) -> Result<Expr, FormulaError> {
+        let at = self.offset();
+        if !self.eat('-') {
+            return self.power();
+        }
+        match self.nested(Self::unary)? {
+            Expr::Number([x, y]) => Ok(Expr::Number([-x, -y])),
+            expr => self.node(at, Expr::Negate(Box::new(expr))),
+        }
+    }
+
+    /// `^` is right associative: `z^2^3` is `z^(2^3)`.
+    fn power(&mut self) -> Result<Expr, FormulaError> {
+        let base = self.atom()?;
+        let at = self.offset();
+        if !self.eat('^') {
+            return Ok(base);
+        }
+        let exponent = self.nested(Self::unary)?;
+        self.node(
+            at,
+            Expr::Binary(Operator::Power, Box::new(base), Box::new(exponent)),
+        )
+    }
+
+    fn atom(&mut self) -> Result<Expr, FormulaError> {
+        let Some(token) = self.peek().cloned() else {
+            return Err(self.error("expected a value"));
+        };
+
+        match token {
+            Token::Number(value) => {
+                self.next += 1;
+                Ok(Expr::Number(value))
+            }
+            Token::Symbol('(') => self.nested(|parser| {
+                parser.next += 1;
+                let expr = parser.expr()?;
+                parser.expect(')')?;
+                Ok(expr)
+            }),
+            Token::Name(name) => {
+                if let Some(function) = Function::from_name(&name) {
+                    let at = self.offset();
+                    self.next += 1;
+                    if !self.eat('(') {
+                        return Err(
+                            self.error(format!("{name} takes an argument, as in {name}(z)"))
+                        );
+                    }
+                    let argument = self.nested(|parser| {
+                        let argument = parser.expr()?;
+                        parser.expect(')')?;
+                        Ok(argument)
+                    })?;
+                    return self.node(at, Expr::Call(function, Box::new(argument)));
+                }
+
+                let expr = match name.as_str() {
+                    "z" if !self.z_defined => {
+                        return Err(self.error("z has no value before the first iteration"));
+                    }
+                    "z" => Expr::Variable(Variable::Z),
+                    "c" => Expr::Variable(Variable::C),
+                    "pixel" => Expr::Variable(Variable::Pixel),
+                    "i" => Expr::Number([0.0, 1.0]),
+                    "pi" => Expr::Number([std::f32::consts::PI, 0.0]),
+                    "e" => Expr::Number([std::f32::consts::E, 0.0]),
+                    _ => {
+                        let functions: Vec<_> = Function::ALL.iter().map(|f| f.name()).collect();
+                        return Err(self.error(format!(
+                            "unknown name {name}, expected z, c, pixel, i, pi, e or one of: {}",
+                            functions.join(", ")
+                        )));
+                    }
+                };
+                self.next += 1;
+                Ok(expr)
+            }
+            _ => Err(self.error("expected a value")),
+        }
+    }
+}
+
+// CPU versions of the complex functions in `formula.wgsl`.
+
+fn mul(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+    [a[0] * b[0] - a[1] * b[1], a[0] * b[1] + a[1] * b[0]]
+}
+
+fn div(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+    let d = b[0] * b[0] + b[1] * b[1];
+    [
+        (a[0] * b[0] + a[1] * b[1]) / d,
+        (a[1] * b[0] - a[0] * b[1]) / d,
+    ]
+}
+
+fn length(a: [f32; 2]) -> f32 {
+    (a[0] * a[0] + a[1] * a[1]).sqrt()
+}
+
+fn exp(a: [f32; 2]) -> [f32; 2] {
+    let r = a[0].exp();
+    [r * a[1].cos(), r * a[1].sin()]
+}
+
+fn log(a: [f32; 2]) -> [f32; 2] {
+    [length(a).ln(), a[1].atan2(a[0])]
+}
+
+fn pow(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+    if a[0] * a[0] + a[1] * a[1] == 0.0 {
+        return [0.0, 0.0];
+    }
+    exp(mul(b, log(a)))
+}
+
+fn powi(a: [f32; 2], n: i32) -> [f32; 2] {
+    let mut result = [1.0, 0.0];
+    let mut base = a;
+    let mut k = n.unsigned_abs();
+    while k > 0 {
+        if k & 1 == 1 {
+            result = mul(result, base);
+        }
+        base = mul(base, base);
+        k >>= 1;
+    }
+    if n < 0 {
+        div([1.0, 0.0], result)
+    } else {
+        result
+    }
+}
+
+fn sqrt(a: [f32; 2]) -> [f32; 2] {
+    let r = length(a);
+    let y = (0.5 * (r - a[0])).sqrt();
+    [(0.5 * (r + a[0])).sqrt(), if a[1] < 0.0 { -y } else { y }]
+}
+
+fn sin(a: [f32; 2]) -> [f32; 2] {
+    [a[0].sin() * a[1].cosh(), a[0].cos() * a[1].sinh()]
+}
+
+fn cos(a: [f32; 2]) -> [f32; 2] {
+    [a[0].cos() * a[1].cosh(), -a[0].sin() * a[1].sinh()]
+}
+
+fn sinh(a: [f32; 2]) -> [f32; 2] {
+    [a[0].sinh() * a[1].cos(), a[0].cosh() * a[1].sin()]
+}
+
+fn cosh(a: [f32; 2]) -> [f32; 2] {
+    [a[0].cosh() * a[1].cos(), a[0].sinh() * a[1].sin()]
+}
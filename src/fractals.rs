@@ -0,0 +1,148 @@
+//! Every fractal the renderers know about.
+//!
+//! Adding a fractal means writing one type that implements [`Fractal`] and
+//! listing it in [`FRACTALS`]; the renderer, the CPU reference renderer and
+//! the number keys all enumerate the registry.
+
+mod custom;
+mod escape_time;
+mod julia;
+mod mandelbrot;
+mod mandelbrot_deep;
+mod nova;
+
+pub use custom::{Custom, DEFAULT_FORMULA};
+pub use julia::JuliaPath;
+pub use nova::NovaColouring;
+
+use std::borrow::Cow;
+
+use escape_time::{EscapeTime, Family};
+use julia::Julia;
+use mandelbrot::Mandelbrot;
+use mandelbrot_deep::MandelbrotDeep;
+use nova::Nova;
+
+use crate::cpu::ColourTables;
+use crate::perturbation::{Orbit, Reference};
+use crate::uniforms::{MAX_PARAMS, UniformData};
+use crate::viewport::Viewport;
+
+/// A tweakable value passed to the shader in `uniforms.params`.
+pub struct ParamSpec {
+    pub name: &'static str,
+    pub default: f32,
+    pub min: f32,
+    pub max: f32,
+}
+
+pub trait Fractal: Sync {
+    fn name(&self) -> &'static str;
+
+    /// WGSL source with `vs_main` and `fs_main` entry points, before it goes
+    /// through [`crate::shader::preprocess`].
+    fn wgsl_source(&self) -> Cow<'static, str>;
+
+    /// Parameters in the order they appear in `uniforms.params`.
+    fn params(&self) -> &'static [ParamSpec] {
+        &[]
+    }
+
+    /// Whether the shader has a double-single path, taken when
+    /// `uniforms.precision` asks for it.
+    fn supports_double_single(&self) -> bool {
+        false
+    }
+
+    /// Whether the image moves with `uniforms.time` under `params`.
+    fn animated(&self, _params: &[f32; MAX_PARAMS]) -> bool {
+        false
+    }
+
+    /// Where the camera starts when the fractal is selected.
+    fn default_view(&self) -> Viewport {
+        Viewport::default()
+    }
+
+    /// The reference orbit the shader needs for a view with the given aspect
+    /// ratio, if it renders by perturbation.
+    fn reference(
+        &self,
+        _viewport: &Viewport,
+        _params: &[f32; MAX_PARAMS],
+        _aspect: f32,
+    ) -> Option<Reference> {
+        None
+    }
+
+    /// The Julia set fractal whose `c` a point of this one picks, for
+    /// escape-time sets iterated over `c`. It takes `c` as the `c_x` and
+    /// `c_y` parameters, with `path` set to [`JuliaPath::Fixed`] if it has
+    /// one, and any other parameter the two share by name.
+    fn julia(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// CPU version of `fs_main`, returning the linear colour at `uv`. `orbit`
+    /// is the computed [`Fractal::reference`] orbit, or empty, and `tables`
+    /// are what the shaders colour from.
+    fn sample(
+        &self,
+        uv: [f32; 2],
+        uniforms: &UniformData,
+        orbit: &Orbit,
+        tables: ColourTables,
+    ) -> [f32; 3];
+
+    fn default_params(&self) -> [f32; MAX_PARAMS] {
+        let mut values = [0.0; MAX_PARAMS];
+        for (value, param) in values.iter_mut().zip(self.params()) {
+            *value = param.default;
+        }
+        values
+    }
+
+    /// Index of the parameter called `name` in `uniforms.params`.
+    fn param_index(&self, name: &str) -> Option<usize> {
+        self.params().iter().position(|param| param.name == name)
+    }
+}
+
+/// Every fractal, in the order of the number keys.
+pub static FRACTALS: &[&dyn Fractal] = &[
+    &Mandelbrot,
+    &Julia,
+    &Nova,
+    &MandelbrotDeep,
+    &EscapeTime {
+        family: Family::BurningShip,
+        julia: false,
+    },
+    &EscapeTime {
+        family: Family::BurningShip,
+        julia: true,
+    },
+    &EscapeTime {
+        family: Family::Tricorn,
+        julia: false,
+    },
+    &EscapeTime {
+        family: Family::Tricorn,
+        julia: true,
+    },
+    &EscapeTime {
+        family: Family::Multibrot,
+        julia: false,
+    },
+    &EscapeTime {
+        family: Family::Multibrot,
+        julia: true,
+    },
+    &Custom::REGISTERED,
+];
+
+pub fn find(name: &str) -> Option<usize> {
+    FRACTALS
+        .iter()
+        .position(|fractal| fractal.name().eq_ignore_ascii_case(name))
+}
@@ -0,0 +1,137 @@
+use std::borrow::Cow;
+use std::sync::{Arc, LazyLock};
+
+use crate::cpu::{ColourTables, escape_intensity, get_colour, view_point};
+use crate::formula::Formula;
+use crate::perturbation::Orbit;
+use crate::uniforms::UniformData;
+
+use super::{Fractal, ParamSpec};
+
+/// Iterates a formula typed in at runtime, see [`crate::formula`]. The one
+/// in [`super::FRACTALS`] iterates [`DEFAULT_FORMULA`]. Renderers are given
+/// another with [`Renderer::set_formula`](crate::renderer::Renderer::set_formula)
+/// and the CPU draws it through [`Custom::stand_in`].
+#[derive(Clone, Default)]
+pub struct Custom {
+    /// `None` for [`DEFAULT_FORMULA`].
+    formula: Option<Arc<Formula>>,
+}
+
+/// What the formula fractal iterates unless given another.
+pub const DEFAULT_FORMULA: &str = "z = z^2 + c";
+
+static DEFAULT: LazyLock<Formula> =
+    LazyLock::new(|| Formula::parse(DEFAULT_FORMULA).expect("the default formula compiles"));
+
+impl Custom {
+    /// The formula fractal as registered, iterating [`DEFAULT_FORMULA`].
+    pub const REGISTERED: Custom = Custom { formula: None };
+
+    pub fn new(formula: Formula) -> Self {
+        Self {
+            formula: Some(Arc::new(formula)),
+        }
+    }
+
+    /// The formula this iterates.
+    pub fn formula(&self) -> &Formula {
+        self.formula.as_deref().unwrap_or(&DEFAULT)
+    }
+
+    /// `fractal`, or this in its place if it is the registered formula
+    /// fractal.
+    pub fn stand_in<'a>(&'a self, fractal: &'a dyn Fractal) -> &'a dyn Fractal {
+        if fractal.name() == self.name() {
+            self
+        } else {
+            fractal
+        }
+    }
+}
+
+const PARAMS: &[ParamSpec] = &[
+    ParamSpec {
+        name: "max_iterations",
+        default: 500.0,
+        min: 1.0,
+        max: 5000.0,
+    },
+    // Distance from 0 at which a point has escaped.
+    ParamSpec {
+        name: "bailout",
+        default: 4.0,
+        min: 1.0,
+        max: 1000.0,
+    },
+    // 1 iterates over z from the pixel with c fixed at (c_x, c_y).
+    ParamSpec {
+        name: "julia",
+        default: 0.0,
+        min: 0.0,
+        max: 1.0,
+    },
+    ParamSpec {
+        name: "c_x",
+        default: -0.8,
+        min: -2.0,
+        max: 2.0,
+    },
+    ParamSpec {
+        name: "c_y",
+        default: 0.156,
+        min: -2.0,
+        max: 2.0,
+    },
+];
+
+impl Fractal for Custom {
+    fn name(&self) -> &'static str {
+        "formula"
+    }
+
+    fn wgsl_source(&self) -> Cow<'static, str> {
+        self.formula().wgsl().into()
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        PARAMS
+    }
+
+    fn sample(
+        &self,
+        uv: [f32; 2],
+        uniforms: &UniformData,
+        _orbit: &Orbit,
+        tables: ColourTables,
+    ) -> [f32; 3] {
+        let pixel = view_point(uv, uniforms);
+
+        let max_iter = uniforms.params[0] as u32;
+        let bailout = uniforms.params[1];
+
+        // Mandelbrot-style formulas iterate over c from z0, Julia-style ones
+        // over z with c fixed.
+        let formula = self.formula();
+        let (mut z, c) = if uniforms.params[2] > 0.5 {
+            (pixel, [uniforms.params[3], uniforms.params[4]])
+        } else {
+            (formula.start(pixel, pixel), pixel)
+        };
+
+        let mut i = 0u32;
+        loop {
+            if i >= max_iter || z[0] * z[0] + z[1] * z[1] > bailout * bailout {
+                break;
+            }
+            z = formula.step(z, c, pixel);
+            i += 1;
+        }
+
+        if i >= max_iter {
+            return [0.0, 0.0, 0.0];
+        }
+
+        get_colour(escape_intensity(i as f32, max_iter), uniforms, tables)
+    }
+}
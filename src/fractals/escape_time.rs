@@ -0,0 +1,240 @@
+//! Escape-time variants of the Mandelbrot set, each in a Mandelbrot-style
+//! form iterated over `c` and a Julia-style form iterated over `z`.
+
+use std::borrow::Cow;
+
+use crate::cpu::{ColourTables, escape_intensity, view_point};
+use crate::perturbation::Orbit;
+use crate::shader;
+use crate::shading::{self, shade};
+use crate::uniforms::UniformData;
+use crate::viewport::Viewport;
+
+use super::{Fractal, ParamSpec};
+
+/// Square of the radius past which a point has escaped.
+const ESCAPE: f32 = 256.0;
+
+/// A 2x2 matrix as its columns, like WGSL's `mat2x2`.
+type Matrix = [[f32; 2]; 2];
+
+const IDENTITY: Matrix = [[1.0, 0.0], [0.0, 1.0]];
+
+/// What a family does to `z` before squaring, or raising to the exponent.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Family {
+    /// `(|x| + |y| i)^d + c`
+    BurningShip = 0,
+    /// `conj(z)^d + c`, the Mandelbar.
+    Tricorn = 1,
+    /// `z^d + c`
+    Multibrot = 2,
+}
+
+/// A [`Family`] in either of its forms. Both share `escape_time.wgsl`,
+/// which the family and form are written ahead of as constants.
+pub struct EscapeTime {
+    pub family: Family,
+    /// Iterate over `z` with `c` fixed, rather than over `c` from 0.
+    pub julia: bool,
+}
+
+/// Parameters of the Julia-style form. The Mandelbrot-style form takes the
+/// first two.
+const fn params(exponent: f32, c: [f32; 2]) -> [ParamSpec; 4] {
+    [
+        ParamSpec {
+            name: "max_iterations",
+            default: 500.0,
+            min: 1.0,
+            max: 5000.0,
+        },
+        // Any real power; 2 is computed exactly.
+        ParamSpec {
+            name: "exponent",
+            default: exponent,
+            min: 1.5,
+            max: 8.0,
+        },
+        ParamSpec {
+            name: "c_x",
+            default: c[0],
+            min: -2.0,
+            max: 2.0,
+        },
+        ParamSpec {
+            name: "c_y",
+            default: c[1],
+            min: -2.0,
+            max: 2.0,
+        },
+    ]
+}
+
+const BURNING_SHIP_PARAMS: [ParamSpec; 4] = params(2.0, [-1.2, -0.2]);
+const TRICORN_PARAMS: [ParamSpec; 4] = params(2.0, [-1.0, 0.0]);
+const MULTIBROT_PARAMS: [ParamSpec; 4] = params(3.0, [-0.3, 0.6]);
+
+/// CPU version of `power` in `escape_time.wgsl`.
+fn power(z: [f32; 2], d: f32) -> [f32; 2] {
+    if d == 2.0 {
+        return [z[0] * z[0] - z[1] * z[1], 2.0 * z[0] * z[1]];
+    }
+    let r2 = z[0] * z[0] + z[1] * z[1];
+    if r2 == 0.0 {
+        return [0.0, 0.0];
+    }
+    let r = r2.powf(0.5 * d);
+    let angle = d * z[1].atan2(z[0]);
+    [r * angle.cos(), r * angle.sin()]
+}
+
+/// `matrix` times the column `v`.
+fn transform(matrix: Matrix, v: [f32; 2]) -> [f32; 2] {
+    [
+        matrix[0][0] * v[0] + matrix[1][0] * v[1],
+        matrix[0][1] * v[0] + matrix[1][1] * v[1],
+    ]
+}
+
+fn multiply(a: Matrix, b: Matrix) -> Matrix {
+    [transform(a, b[0]), transform(a, b[1])]
+}
+
+/// WGSL's `sign`, which unlike `f32::signum` is 0 at 0.
+fn sign(x: f32) -> f32 {
+    if x == 0.0 { 0.0 } else { x.signum() }
+}
+
+impl EscapeTime {
+    /// CPU version of `step` in `escape_time.wgsl`.
+    fn step(&self, z: [f32; 2], d: f32) -> [f32; 2] {
+        match self.family {
+            Family::BurningShip => power([z[0].abs(), z[1].abs()], d),
+            Family::Tricorn => power([z[0], -z[1]], d),
+            Family::Multibrot => power(z, d),
+        }
+    }
+
+    /// CPU version of `step_derivative` in `escape_time.wgsl`.
+    fn step_derivative(&self, z: [f32; 2], d: f32) -> Matrix {
+        let (w, fold) = match self.family {
+            Family::BurningShip => (
+                [z[0].abs(), z[1].abs()],
+                [[sign(z[0]), 0.0], [0.0, sign(z[1])]],
+            ),
+            Family::Tricorn => ([z[0], -z[1]], [[1.0, 0.0], [0.0, -1.0]]),
+            Family::Multibrot => (z, IDENTITY),
+        };
+        let p = if d == 2.0 {
+            [2.0 * w[0], 2.0 * w[1]]
+        } else {
+            power(w, d - 1.0).map(|x| d * x)
+        };
+        multiply([[p[0], p[1]], [-p[1], p[0]]], fold)
+    }
+}
+
+impl Fractal for EscapeTime {
+    fn name(&self) -> &'static str {
+        match (self.family, self.julia) {
+            (Family::BurningShip, false) => "burning_ship",
+            (Family::BurningShip, true) => "burning_ship_julia",
+            (Family::Tricorn, false) => "tricorn",
+            (Family::Tricorn, true) => "tricorn_julia",
+            (Family::Multibrot, false) => "multibrot",
+            (Family::Multibrot, true) => "multibrot_julia",
+        }
+    }
+
+    fn wgsl_source(&self) -> Cow<'static, str> {
+        format!(
+            "#define FAMILY {}u\n#define JULIA {}\n{}",
+            self.family as u32,
+            self.julia,
+            shader::source("escape_time.wgsl")
+        )
+        .into()
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        let params: &'static [ParamSpec; 4] = match self.family {
+            Family::BurningShip => &BURNING_SHIP_PARAMS,
+            Family::Tricorn => &TRICORN_PARAMS,
+            Family::Multibrot => &MULTIBROT_PARAMS,
+        };
+        if self.julia { params } else { &params[..2] }
+    }
+
+    fn default_view(&self) -> Viewport {
+        let view = match (self.family, self.julia) {
+            (Family::BurningShip, false) => Viewport::new(-0.5, [0.37, -0.53]),
+            (Family::Tricorn, false) => Viewport::new(0.0, [0.35, 0.0]),
+            _ => return Viewport::default(),
+        };
+        view.expect("the default view is valid")
+    }
+
+    fn julia(&self) -> Option<&'static str> {
+        let julia = EscapeTime {
+            family: self.family,
+            julia: true,
+        };
+        (!self.julia).then(|| julia.name())
+    }
+
+    fn sample(
+        &self,
+        uv: [f32; 2],
+        uniforms: &UniformData,
+        _orbit: &Orbit,
+        tables: ColourTables,
+    ) -> [f32; 3] {
+        let point = view_point(uv, uniforms);
+
+        let max_iter = uniforms.params[0] as u32;
+        let exponent = uniforms.params[1];
+
+        // Mandelbrot-style sets iterate from 0 over c, Julia-style ones over z.
+        let (mut z, c, mut derivative) = if self.julia {
+            (point, [uniforms.params[2], uniforms.params[3]], IDENTITY)
+        } else {
+            ([0.0, 0.0], point, [[0.0; 2]; 2])
+        };
+
+        let escape = shading::shaded_escape(ESCAPE, uniforms);
+        let mut i = 0u32;
+        loop {
+            if i >= max_iter || z[0] * z[0] + z[1] * z[1] > escape {
+                break;
+            }
+            if shading::shaded(uniforms) {
+                derivative = multiply(self.step_derivative(z, exponent), derivative);
+                if !self.julia {
+                    derivative[0][0] += 1.0;
+                    derivative[1][1] += 1.0;
+                }
+            }
+            let stepped = self.step(z, exponent);
+            z = [stepped[0] + c[0], stepped[1] + c[1]];
+            i += 1;
+        }
+
+        if i >= max_iter {
+            return [0.0, 0.0, 0.0];
+        }
+
+        // |z| grows like |z|^d an iteration, so the fraction of an iteration
+        // left over is how far the escaping exponent overshot.
+        let smooth_i =
+            i as f32 + 1.0 - (0.5 * (z[0] * z[0] + z[1] * z[1]).log2()).log2() / exponent.log2();
+        let t = escape_intensity(smooth_i, max_iter);
+
+        let transposed = [
+            [derivative[0][0], derivative[1][0]],
+            [derivative[0][1], derivative[1][1]],
+        ];
+        shade(t, z, transform(transposed, z), uniforms, tables)
+    }
+}
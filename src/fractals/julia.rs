@@ -0,0 +1,187 @@
+use std::borrow::Cow;
+
+use crate::cpu::double_single::{self as dsf, ds};
+use crate::cpu::{ColourTables, clamp, view_delta};
+use crate::perturbation::Orbit;
+use crate::precision::Precision;
+use crate::shader;
+use crate::shading::{self, holomorphic_gradient, shade};
+use crate::uniforms::{MAX_PARAMS, UniformData};
+
+use super::{Fractal, ParamSpec};
+
+pub struct Julia;
+
+/// How the constant `c` moves over time, as the `path` parameter.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JuliaPath {
+    /// Round a circle of `radius` about `(c_x, c_y)`.
+    Circle = 0,
+    /// Held at `(c_x, c_y)`.
+    Fixed = 1,
+    /// Back and forth between `(c_x, c_y)` and `(end_x, end_y)`.
+    Line = 2,
+}
+
+const PARAMS: &[ParamSpec] = &[
+    ParamSpec {
+        name: "radius",
+        default: 0.7885,
+        min: 0.0,
+        max: 2.0,
+    },
+    ParamSpec {
+        name: "speed",
+        default: 0.05,
+        min: 0.0,
+        max: 1.0,
+    },
+    ParamSpec {
+        name: "path",
+        default: JuliaPath::Circle as u32 as f32,
+        min: 0.0,
+        max: 2.0,
+    },
+    ParamSpec {
+        name: "c_x",
+        default: 0.0,
+        min: -2.0,
+        max: 2.0,
+    },
+    ParamSpec {
+        name: "c_y",
+        default: 0.0,
+        min: -2.0,
+        max: 2.0,
+    },
+    ParamSpec {
+        name: "end_x",
+        default: 0.0,
+        min: -2.0,
+        max: 2.0,
+    },
+    ParamSpec {
+        name: "end_y",
+        default: 0.0,
+        min: -2.0,
+        max: 2.0,
+    },
+];
+
+/// CPU version of `julia_c` in `julia.wgsl`.
+fn julia_c(uniforms: &UniformData) -> [f32; 2] {
+    let radius = uniforms.params[0];
+    let speed = uniforms.params[1];
+    let path = uniforms.params[2] as u32;
+    let start = [uniforms.params[3], uniforms.params[4]];
+    let end = [uniforms.params[5], uniforms.params[6]];
+    let angle = uniforms.time * speed;
+
+    if path == JuliaPath::Fixed as u32 {
+        start
+    } else if path == JuliaPath::Line as u32 {
+        let t = 0.5 - 0.5 * angle.cos();
+        [
+            start[0] * (1.0 - t) + end[0] * t,
+            start[1] * (1.0 - t) + end[1] * t,
+        ]
+    } else {
+        [
+            start[0] + radius * angle.cos(),
+            start[1] + radius * angle.sin(),
+        ]
+    }
+}
+
+impl Fractal for Julia {
+    fn name(&self) -> &'static str {
+        "julia"
+    }
+
+    fn wgsl_source(&self) -> Cow<'static, str> {
+        shader::source("julia.wgsl")
+    }
+
+    fn supports_double_single(&self) -> bool {
+        true
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        PARAMS
+    }
+
+    fn animated(&self, params: &[f32; MAX_PARAMS]) -> bool {
+        params[1] != 0.0 && params[2] as u32 != JuliaPath::Fixed as u32
+    }
+
+    fn sample(
+        &self,
+        uv: [f32; 2],
+        uniforms: &UniformData,
+        _orbit: &Orbit,
+        tables: ColourTables,
+    ) -> [f32; 3] {
+        let zoom = 1.2f32.powf(uniforms.zoom);
+        let delta = view_delta(uv, uniforms);
+        let z = [delta[0] - uniforms.offset[0], delta[1] + uniforms.offset[1]];
+
+        let c = julia_c(uniforms);
+
+        let mut value = z;
+        let mut dz = [1.0f32, 0.0];
+        let mut i = 0u32;
+        let iterations = ((100.0 + 100.0 * zoom) as u32).clamp(0, 1000);
+        let escape = shading::shaded_escape(4.0, uniforms);
+        // dz' = 2 z dz
+        let derive = |z: [f32; 2], dz: [f32; 2]| {
+            [
+                2.0 * (z[0] * dz[0] - z[1] * dz[1]),
+                2.0 * (z[0] * dz[1] + z[1] * dz[0]),
+            ]
+        };
+        if uniforms.precision_mode == Precision::DoubleSingle as u32 {
+            let mut x = dsf::add([-uniforms.offset[0], -uniforms.offset_lo[0]], ds(delta[0]));
+            let mut y = dsf::add([uniforms.offset[1], uniforms.offset_lo[1]], ds(delta[1]));
+            loop {
+                if i >= iterations || x[0] * x[0] + y[0] * y[0] > escape {
+                    break;
+                }
+                if shading::shaded(uniforms) {
+                    dz = derive([x[0], y[0]], dz);
+                }
+                let xy = dsf::mul(x, y);
+                x = dsf::add(dsf::sub(dsf::mul(x, x), dsf::mul(y, y)), ds(c[0]));
+                y = dsf::add(dsf::add(xy, xy), ds(c[1]));
+                i += 1;
+            }
+            value = [x[0], y[0]];
+        } else {
+            loop {
+                if i >= iterations || value[0] * value[0] + value[1] * value[1] > escape {
+                    break;
+                }
+                if shading::shaded(uniforms) {
+                    dz = derive(value, dz);
+                }
+                value = [
+                    value[0] * value[0] - value[1] * value[1] + c[0],
+                    2.0 * value[0] * value[1] + c[1],
+                ];
+                i += 1;
+            }
+        }
+
+        if i >= iterations && shading::shaded(uniforms) {
+            return [0.0, 0.0, 0.0];
+        }
+
+        let dot = value[0] * value[0] + value[1] * value[1];
+        let mut t = i as f32 - dot.log2().log2() + 4.0;
+        let max_iter = 1000.0;
+        t = clamp(t / max_iter, 0.0, 1.0);
+        t = t.powf(0.6);
+
+        shade(t, value, holomorphic_gradient(value, dz), uniforms, tables)
+    }
+}
@@ -0,0 +1,150 @@
+use std::borrow::Cow;
+
+use crate::cpu::double_single::{self as dsf, ds};
+use crate::cpu::{ColourTables, escape_intensity, view_delta, view_point};
+use crate::perturbation::Orbit;
+use crate::precision::Precision;
+use crate::shader;
+use crate::shading::{self, holomorphic_gradient, shade};
+use crate::uniforms::{MAX_PARAMS, UniformData};
+use crate::viewport::Viewport;
+
+use super::{Fractal, ParamSpec};
+
+pub struct Mandelbrot;
+
+const PARAMS: &[ParamSpec] = &[
+    ParamSpec {
+        name: "max_iterations",
+        default: 1000.0,
+        min: 1.0,
+        max: 5000.0,
+    },
+    // 1 ignores the view and zooms in and out of the target over time.
+    ParamSpec {
+        name: "demo",
+        default: 0.0,
+        min: 0.0,
+        max: 1.0,
+    },
+    ParamSpec {
+        name: "target_x",
+        default: -1.006,
+        min: -2.0,
+        max: 2.0,
+    },
+    ParamSpec {
+        name: "target_y",
+        default: -0.2506,
+        min: -2.0,
+        max: 2.0,
+    },
+];
+
+impl Fractal for Mandelbrot {
+    fn name(&self) -> &'static str {
+        "mandelbrot"
+    }
+
+    fn wgsl_source(&self) -> Cow<'static, str> {
+        shader::source("mandelbrot.wgsl")
+    }
+
+    fn supports_double_single(&self) -> bool {
+        true
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        PARAMS
+    }
+
+    fn animated(&self, params: &[f32; MAX_PARAMS]) -> bool {
+        params[1] > 0.5
+    }
+
+    fn julia(&self) -> Option<&'static str> {
+        Some("julia")
+    }
+
+    fn default_view(&self) -> Viewport {
+        Viewport::new(0.0, [0.75, 0.0]).expect("the default view is valid")
+    }
+
+    fn sample(
+        &self,
+        uv: [f32; 2],
+        uniforms: &UniformData,
+        _orbit: &Orbit,
+        tables: ColourTables,
+    ) -> [f32; 3] {
+        let demo = uniforms.params[1] > 0.5;
+        let c = if demo {
+            let aspect = uniforms.resolution[0] / uniforms.resolution[1];
+            let scale = [aspect.max(1.0), (1.0 / aspect).max(1.0)];
+            let zoom = 0.8f32.powf(-25.0 * (uniforms.time * 0.05).cos() + 20.0);
+            let center = [uniforms.params[2], uniforms.params[3]];
+            let uv = [
+                (uv[0] - 0.5) * scale[0] * zoom,
+                (uv[1] - 0.5) * scale[1] * zoom,
+            ];
+
+            [uv[0] + center[0], uv[1] + center[1]]
+        } else {
+            view_point(uv, uniforms)
+        };
+
+        let mut z = [0.0f32, 0.0];
+        let mut dz = [0.0f32, 0.0];
+        let mut i = 0u32;
+
+        let max_iter = uniforms.params[0] as u32;
+        let escape = shading::shaded_escape(4.0, uniforms);
+
+        // dz' = 2 z dz + 1
+        let derive = |z: [f32; 2], dz: [f32; 2]| {
+            [
+                2.0 * (z[0] * dz[0] - z[1] * dz[1]) + 1.0,
+                2.0 * (z[0] * dz[1] + z[1] * dz[0]),
+            ]
+        };
+
+        if uniforms.precision_mode == Precision::DoubleSingle as u32 && !demo {
+            let delta = view_delta(uv, uniforms);
+            let cx = dsf::add([-uniforms.offset[0], -uniforms.offset_lo[0]], ds(delta[0]));
+            let cy = dsf::add([uniforms.offset[1], uniforms.offset_lo[1]], ds(delta[1]));
+            let (mut x, mut y) = (ds(0.0), ds(0.0));
+            loop {
+                if i >= max_iter || x[0] * x[0] + y[0] * y[0] > escape {
+                    break;
+                }
+                if shading::shaded(uniforms) {
+                    dz = derive([x[0], y[0]], dz);
+                }
+                let xy = dsf::mul(x, y);
+                x = dsf::add(dsf::sub(dsf::mul(x, x), dsf::mul(y, y)), cx);
+                y = dsf::add(dsf::add(xy, xy), cy);
+                i += 1;
+            }
+            z = [x[0], y[0]];
+        } else {
+            loop {
+                if i >= max_iter || z[0] * z[0] + z[1] * z[1] > escape {
+                    break;
+                }
+                if shading::shaded(uniforms) {
+                    dz = derive(z, dz);
+                }
+                z = [z[0] * z[0] - z[1] * z[1] + c[0], 2.0 * z[0] * z[1] + c[1]];
+                i += 1;
+            }
+        }
+
+        if i >= max_iter {
+            return [0.0, 0.0, 0.0];
+        }
+
+        let t = escape_intensity(i as f32, max_iter);
+
+        shade(t, z, holomorphic_gradient(z, dz), uniforms, tables)
+    }
+}
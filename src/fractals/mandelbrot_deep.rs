@@ -0,0 +1,208 @@
+use std::borrow::Cow;
+
+use crate::cpu::{ColourTables, view_delta};
+use crate::histogram::Colouring;
+use crate::perturbation::{MAX_REFERENCES, Orbit, Reference};
+use crate::shader;
+use crate::shading::{self, holomorphic_gradient, shade};
+use crate::uniforms::{MAX_PARAMS, UniformData};
+use crate::viewport::Viewport;
+
+use super::{Fractal, ParamSpec};
+
+/// The Mandelbrot set by perturbation around a reference orbit, for zooms
+/// far beyond `f32`. Deltas are still `f32`, so the view bottoms out once
+/// pixel spacing nears its smallest normal number, around 1e-37.
+///
+/// Rebasing carries a pixel back to the start of its reference's orbit,
+/// which fixes the glitches from a pixel drifting away from the reference.
+/// Those from the reference itself, such as when the pixel passes far closer
+/// to 0 than it does, need another reference: a glitched pixel is drawn
+/// again against each of the orbit's secondaries in turn, see
+/// [`Reference::orbit_without_glitches`]. Pixels still glitched after the
+/// last are marked by `show_glitches`.
+pub struct MandelbrotDeep;
+
+const PARAMS: &[ParamSpec] = &[
+    ParamSpec {
+        name: "max_iterations",
+        default: 2000.0,
+        min: 1.0,
+        max: 100_000.0,
+    },
+    ParamSpec {
+        name: "show_glitches",
+        default: 0.0,
+        min: 0.0,
+        max: 1.0,
+    },
+];
+
+const GLITCH_TOLERANCE: f32 = 1e-6;
+const COLOUR_PERIOD: f32 = 64.0;
+
+/// Where a pixel's iteration ended.
+struct Iterated {
+    z: [f32; 2],
+    derivative: [f32; 2],
+    i: u32,
+    glitched: bool,
+}
+
+fn complex_mul(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+    [a[0] * b[0] - a[1] * b[1], a[0] * b[1] + a[1] * b[0]]
+}
+
+/// CPU version of `iterate` in `mandelbrot_deep.wgsl`: iterate the pixel at
+/// `dc_main` from the centre against the reference at `index`, 0 for the
+/// main one and the secondaries after it.
+fn iterate(dc_main: [f32; 2], orbit: &Orbit, index: usize, uniforms: &UniformData) -> Iterated {
+    let (offset, points) = match index {
+        0 => ([0.0; 2], orbit.padded_points()),
+        _ => {
+            let secondary = &orbit.secondaries[index - 1];
+            (secondary.offset, &secondary.points[..])
+        }
+    };
+    let dc = [dc_main[0] - offset[0], dc_main[1] - offset[1]];
+    let max_iter = uniforms.params[0] as u32;
+    let series = &orbit.series;
+
+    let mut dz = [0.0f32, 0.0];
+    let mut z = [0.0f32, 0.0];
+    let mut derivative = [0.0f32, 0.0];
+    let mut n = 0usize;
+    let mut i = 0u32;
+    let mut glitched = false;
+    if index == 0 && series.skip > 0 {
+        let [a, b, c] = series.coefficients;
+        let u = [dc[0] / series.scale, dc[1] / series.scale];
+        let cu = complex_mul(c, u);
+        let bu = complex_mul([cu[0] + b[0], cu[1] + b[1]], u);
+        dz = complex_mul([bu[0] + a[0], bu[1] + a[1]], u);
+        let cu3 = complex_mul([3.0 * c[0], 3.0 * c[1]], u);
+        let du = complex_mul([cu3[0] + 2.0 * b[0], cu3[1] + 2.0 * b[1]], u);
+        derivative = [(du[0] + a[0]) / series.scale, (du[1] + a[1]) / series.scale];
+        n = series.skip as usize;
+        i = series.skip;
+        z = [points[n][0] + dz[0], points[n][1] + dz[1]];
+    }
+    let escape = shading::shaded_escape(4.0, uniforms);
+    loop {
+        if i >= max_iter {
+            break;
+        }
+
+        if shading::shaded(uniforms) {
+            let step = complex_mul(z, derivative);
+            derivative = [2.0 * step[0] + 1.0, 2.0 * step[1]];
+        }
+
+        let reference = points[n];
+        let step = complex_mul([2.0 * reference[0] + dz[0], 2.0 * reference[1] + dz[1]], dz);
+        dz = [step[0] + dc[0], step[1] + dc[1]];
+        n += 1;
+        i += 1;
+
+        let point = points[n];
+        z = [point[0] + dz[0], point[1] + dz[1]];
+        let r2 = z[0] * z[0] + z[1] * z[1];
+        if r2 > escape {
+            break;
+        }
+
+        if r2 < GLITCH_TOLERANCE * (point[0] * point[0] + point[1] * point[1]) {
+            glitched = true;
+        }
+
+        if r2 < dz[0] * dz[0] + dz[1] * dz[1] || n + 1 >= points.len() {
+            dz = z;
+            n = 0;
+        }
+    }
+
+    Iterated {
+        z,
+        derivative,
+        i,
+        glitched,
+    }
+}
+
+impl Fractal for MandelbrotDeep {
+    fn name(&self) -> &'static str {
+        "mandelbrot_deep"
+    }
+
+    fn wgsl_source(&self) -> Cow<'static, str> {
+        shader::source("mandelbrot_deep.wgsl")
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        PARAMS
+    }
+
+    fn julia(&self) -> Option<&'static str> {
+        Some("julia")
+    }
+
+    fn default_view(&self) -> Viewport {
+        Viewport::new(0.0, [0.5, 0.0]).expect("the default view is valid")
+    }
+
+    fn reference(
+        &self,
+        viewport: &Viewport,
+        params: &[f32; MAX_PARAMS],
+        aspect: f32,
+    ) -> Option<Reference> {
+        Some(Reference::new(
+            viewport.center().clone(),
+            viewport.half_extents(aspect),
+            params[0] as u32,
+        ))
+    }
+
+    fn sample(
+        &self,
+        uv: [f32; 2],
+        uniforms: &UniformData,
+        orbit: &Orbit,
+        tables: ColourTables,
+    ) -> [f32; 3] {
+        let dc = view_delta(uv, uniforms);
+        let max_iter = uniforms.params[0] as u32;
+        let show_glitches = uniforms.params[1] > 0.5;
+
+        let references = (1 + orbit.secondaries.len()).min(MAX_REFERENCES);
+        let mut iterated = iterate(dc, orbit, 0, uniforms);
+        for index in 1..references {
+            if !iterated.glitched {
+                break;
+            }
+            iterated = iterate(dc, orbit, index, uniforms);
+        }
+        let Iterated {
+            z, derivative, i, ..
+        } = iterated;
+
+        if iterated.glitched && (show_glitches || uniforms.colouring == Colouring::Glitches as u32)
+        {
+            return [1.0, 0.0, 1.0];
+        }
+        if i >= max_iter {
+            return [0.0, 0.0, 0.0];
+        }
+
+        let smooth_i = i as f32 - (z[0] * z[0] + z[1] * z[1]).log2().log2() + 4.0;
+        let wave = smooth_i / COLOUR_PERIOD;
+        let t = 1.0 - ((wave - wave.floor()) * 2.0 - 1.0).abs();
+        shade(
+            0.2 + (1.0 - 0.2) * t,
+            z,
+            holomorphic_gradient(z, derivative),
+            uniforms,
+            tables,
+        )
+    }
+}
@@ -0,0 +1,257 @@
+use std::borrow::Cow;
+
+use crate::cpu::double_single::{self as dsf, DoubleSingle, ds};
+use crate::cpu::{ColourTables, clamp, get_colour, view_delta};
+use crate::perturbation::Orbit;
+use crate::precision::Precision;
+use crate::shader;
+use crate::uniforms::{MAX_PARAMS, UniformData};
+
+use super::{Fractal, ParamSpec};
+
+/// Newton's method on a polynomial given by its roots, plus a constant `c`
+/// that circles with time. With an amplitude of 0 it is the plain Newton
+/// fractal.
+pub struct Nova;
+
+/// Most roots the polynomial can have, as many as fit in the parameters
+/// after the first four.
+const MAX_ROOTS: usize = 6;
+
+/// How points are coloured, as the `colouring` parameter.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NovaColouring {
+    /// A hue per root, by the root the point converged to, darker the
+    /// longer it took.
+    Root = 0,
+    /// The palette, by how many iterations it took to converge.
+    Speed = 1,
+}
+
+/// Square of the step below which a point has converged.
+const CONVERGED: f32 = 1e-6;
+/// Iterations to converge that span the whole palette.
+const SPEED_ITERATIONS: f32 = 32.0;
+/// How far from the origin roots can be placed.
+const ROOT_RANGE: f32 = 8.0;
+
+const fn coordinate(name: &'static str, default: f32) -> ParamSpec {
+    ParamSpec {
+        name,
+        default,
+        min: -ROOT_RANGE,
+        max: ROOT_RANGE,
+    }
+}
+
+/// The first four parameters, then the roots. Defaults to `z^3 - 1`.
+const PARAMS: &[ParamSpec] = &[
+    ParamSpec {
+        name: "amplitude",
+        default: 0.3,
+        min: 0.0,
+        max: 1.0,
+    },
+    ParamSpec {
+        name: "relaxation",
+        default: 1.0,
+        min: 0.1,
+        max: 2.0,
+    },
+    ParamSpec {
+        name: "colouring",
+        default: NovaColouring::Root as u32 as f32,
+        min: 0.0,
+        max: 1.0,
+    },
+    ParamSpec {
+        name: "degree",
+        default: 3.0,
+        min: 1.0,
+        max: MAX_ROOTS as f32,
+    },
+    coordinate("root1_x", 1.0),
+    coordinate("root1_y", 0.0),
+    coordinate("root2_x", -0.5),
+    coordinate("root2_y", 0.866_025_4),
+    coordinate("root3_x", -0.5),
+    coordinate("root3_y", -0.866_025_4),
+    coordinate("root4_x", 0.0),
+    coordinate("root4_y", 0.0),
+    coordinate("root5_x", 0.0),
+    coordinate("root5_y", 0.0),
+    coordinate("root6_x", 0.0),
+    coordinate("root6_y", 0.0),
+];
+
+/// CPU version of `root` in `nova.wgsl`.
+fn root(uniforms: &UniformData, k: usize) -> [f32; 2] {
+    [uniforms.params[4 + 2 * k], uniforms.params[5 + 2 * k]]
+}
+
+/// CPU version of `reciprocal` in `nova.wgsl`.
+fn reciprocal(z: [f32; 2]) -> [f32; 2] {
+    let n = z[0] * z[0] + z[1] * z[1];
+    if n > 0.0 {
+        [z[0] / n, -z[1] / n]
+    } else {
+        [0.0, 0.0]
+    }
+}
+
+/// CPU version of `root_colour` in `nova.wgsl`.
+fn root_colour(k: usize, degree: usize) -> [f32; 3] {
+    let hue = k as f32 / degree as f32;
+    [0.0, 1.0 / 3.0, 2.0 / 3.0]
+        .map(|phase| 0.5 + 0.5 * (std::f32::consts::TAU * (hue + phase)).cos())
+}
+
+impl Fractal for Nova {
+    fn name(&self) -> &'static str {
+        "nova"
+    }
+
+    fn wgsl_source(&self) -> Cow<'static, str> {
+        shader::source("nova.wgsl")
+    }
+
+    fn supports_double_single(&self) -> bool {
+        true
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        PARAMS
+    }
+
+    fn animated(&self, params: &[f32; MAX_PARAMS]) -> bool {
+        params[0] != 0.0
+    }
+
+    fn sample(
+        &self,
+        uv: [f32; 2],
+        uniforms: &UniformData,
+        _orbit: &Orbit,
+        tables: ColourTables,
+    ) -> [f32; 3] {
+        let zoom = 1.2f32.powf(uniforms.zoom);
+        let delta = view_delta(uv, uniforms);
+        let mut z = [delta[0] - uniforms.offset[0], delta[1] + uniforms.offset[1]];
+
+        // Small perturbation constant to animate over time
+        let amplitude = uniforms.params[0];
+        let c = [
+            amplitude * (uniforms.time * 0.2).cos(),
+            amplitude * (uniforms.time * 0.35).sin(),
+        ];
+        let relaxation = uniforms.params[1];
+        let colouring = uniforms.params[2] as u32;
+        let degree = (uniforms.params[3] as usize).clamp(1, MAX_ROOTS);
+
+        // Square of how far the last step moved.
+        let mut moved = 0.0;
+        let mut converged = false;
+        let mut i = 0u32;
+        let max_iter = ((100.0 + 100.0 * zoom) as u32).clamp(0, 1000);
+        if uniforms.precision_mode == Precision::DoubleSingle as u32 {
+            let mut x = dsf::add([-uniforms.offset[0], -uniforms.offset_lo[0]], ds(delta[0]));
+            let mut y = dsf::add([uniforms.offset[1], uniforms.offset_lo[1]], ds(delta[1]));
+            loop {
+                if i >= max_iter {
+                    break;
+                }
+
+                // f'(z) / f(z) = sum of 1 / (z - root)
+                let (mut s_x, mut s_y) = (ds(0.0), ds(0.0));
+                for k in 0..degree {
+                    let r = root(uniforms, k);
+                    let d_x = dsf::sub(x, ds(r[0]));
+                    let d_y = dsf::sub(y, ds(r[1]));
+                    let n = dsf::add(dsf::mul(d_x, d_x), dsf::mul(d_y, d_y));
+                    if n[0] > 0.0 {
+                        s_x = dsf::add(s_x, dsf::div(d_x, n));
+                        s_y = dsf::sub(s_y, dsf::div(d_y, n));
+                    }
+                }
+
+                // z = z - relaxation * f(z)/f'(z) + c
+                let (mut step_x, mut step_y): (DoubleSingle, DoubleSingle) = (ds(0.0), ds(0.0));
+                let n = dsf::add(dsf::mul(s_x, s_x), dsf::mul(s_y, s_y));
+                if n[0] > 0.0 {
+                    step_x = dsf::div(dsf::mul(ds(relaxation), s_x), n);
+                    step_y = dsf::div(dsf::mul(ds(-relaxation), s_y), n);
+                }
+                let move_x = dsf::sub(ds(c[0]), step_x);
+                let move_y = dsf::sub(ds(c[1]), step_y);
+                x = dsf::add(x, move_x);
+                y = dsf::add(y, move_y);
+
+                moved = move_x[0] * move_x[0] + move_y[0] * move_y[0];
+                if moved < CONVERGED {
+                    converged = true;
+                    break;
+                }
+
+                i += 1;
+            }
+            z = [x[0], y[0]];
+        } else {
+            loop {
+                if i >= max_iter {
+                    break;
+                }
+
+                // f'(z) / f(z) = sum of 1 / (z - root)
+                let mut s = [0.0, 0.0];
+                for k in 0..degree {
+                    let r = root(uniforms, k);
+                    let term = reciprocal([z[0] - r[0], z[1] - r[1]]);
+                    s = [s[0] + term[0], s[1] + term[1]];
+                }
+
+                // z = z - relaxation * f(z)/f'(z) + c
+                let newton = reciprocal(s);
+                let step = [c[0] - relaxation * newton[0], c[1] - relaxation * newton[1]];
+                z = [z[0] + step[0], z[1] + step[1]];
+
+                moved = step[0] * step[0] + step[1] * step[1];
+                if moved < CONVERGED {
+                    converged = true;
+                    break;
+                }
+
+                i += 1;
+            }
+        }
+
+        // Newton's method converges quadratically, so the fraction of a step
+        // left over is how far the final step's exponent overshot.
+        let mut t = 1.0;
+        if converged {
+            let overshoot = moved.max(1e-30).ln() / CONVERGED.ln();
+            t = clamp(
+                (i as f32 - clamp(overshoot.log2(), 0.0, 1.0)) / SPEED_ITERATIONS,
+                0.0,
+                1.0,
+            );
+        }
+
+        if colouring == NovaColouring::Speed as u32 {
+            return get_colour(t, uniforms, tables);
+        }
+
+        let distance = |k: usize| {
+            let r = root(uniforms, k);
+            (z[0] - r[0]).hypot(z[1] - r[1])
+        };
+        let nearest = (1..degree).fold(0, |nearest, k| {
+            if distance(k) < distance(nearest) {
+                k
+            } else {
+                nearest
+            }
+        });
+        root_colour(nearest, degree).map(|channel| channel * (1.0 - t))
+    }
+}
@@ -0,0 +1,155 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::export::{ExportError, check_size, write_png};
+use crate::fractals::Custom;
+use crate::palette::Table;
+use crate::perturbation::{GlitchSearch, Orbit, Reference};
+use crate::shader::ShaderError;
+use crate::tiled::TileGrid;
+use crate::uniforms::{UniformData, Uniforms};
+
+use offscreen_state::OffscreenState;
+
+pub use offscreen_state::{OFFSCREEN_FORMAT, OffscreenError};
+
+mod offscreen_state;
+
+/// Renders frames to memory instead of a window.
+pub struct HeadlessState {
+    offscreen_state: OffscreenState,
+    uniforms: Uniforms,
+}
+
+impl HeadlessState {
+    pub async fn new(force_fallback_adapter: bool) -> Result<Self, OffscreenError> {
+        let offscreen_state = OffscreenState::new(force_fallback_adapter).await?;
+
+        let uniforms = Uniforms::new(&offscreen_state.device);
+
+        Ok(Self {
+            offscreen_state,
+            uniforms,
+        })
+    }
+
+    /// Render a single frame and return its RGBA8 pixels, top row first.
+    /// The view's aspect ratio comes from `uniform_data.resolution`.
+    pub fn draw(
+        &mut self,
+        width: u32,
+        height: u32,
+        uniform_data: UniformData,
+        orbit: &Orbit,
+        bundle_idx: usize,
+    ) -> Vec<u8> {
+        self.uniforms.set_orbit(
+            &self.offscreen_state.device,
+            &self.offscreen_state.queue,
+            orbit,
+        );
+        self.offscreen_state
+            .equalise(&mut self.uniforms, uniform_data, bundle_idx);
+        self.uniforms
+            .update(&self.offscreen_state.queue, uniform_data);
+
+        let target = self.offscreen_state.create_offscreen_target(width, height);
+        self.offscreen_state
+            .draw(&target, &self.uniforms.uniform_bind_group, bundle_idx)
+    }
+
+    /// The orbit of `reference` for the view drawn with `uniform_data` by
+    /// the fractal at `bundle_idx`, with secondaries for the pixels that
+    /// glitch against it, see [`Reference::orbit_without_glitches`].
+    pub fn orbit(
+        &mut self,
+        reference: &Reference,
+        uniform_data: UniformData,
+        bundle_idx: usize,
+    ) -> Orbit {
+        let search = GlitchSearch::new(uniform_data);
+        reference.orbit_without_glitches(|orbit| {
+            let pixels = self.draw(
+                search.width,
+                search.height,
+                search.uniform_data,
+                orbit,
+                bundle_idx,
+            );
+            search.glitch(&pixels)
+        })
+    }
+
+    /// Rebuild the pipelines whose shader source has changed, such as after
+    /// [`crate::shader::load`]. A fractal whose shader fails to build keeps
+    /// drawing with its previous one.
+    pub fn reload_shaders(&mut self) -> Vec<ShaderError> {
+        self.offscreen_state.reload_shaders()
+    }
+
+    /// Draw `formula` for the formula fractal from now on. If its shader
+    /// fails to build, the previous formula is kept.
+    pub fn set_formula(&mut self, formula: Custom) -> Result<(), ShaderError> {
+        self.offscreen_state.set_formula(formula)
+    }
+
+    /// Colour with `palette` from now on.
+    pub fn set_palette(&mut self, palette: Arc<Table>) {
+        self.uniforms
+            .set_palette(&self.offscreen_state.queue, &palette);
+    }
+
+    /// Render a view straight to a png file. Sizes beyond the GPU texture
+    /// limit are rendered in tiles.
+    pub fn export_png(
+        &mut self,
+        path: &Path,
+        width: u32,
+        height: u32,
+        uniform_data: UniformData,
+        orbit: &Orbit,
+        bundle_idx: usize,
+    ) -> Result<(), ExportError> {
+        check_size(width, height)?;
+
+        self.uniforms.set_orbit(
+            &self.offscreen_state.device,
+            &self.offscreen_state.queue,
+            orbit,
+        );
+
+        let grid = TileGrid::new(width, height, self.offscreen_state.max_texture_dimension());
+        let target = self
+            .offscreen_state
+            .create_offscreen_target(grid.tile_width, grid.tile_height);
+
+        let uniform_data = UniformData {
+            resolution: [width as f32, height as f32],
+            ..uniform_data
+        };
+        self.offscreen_state
+            .equalise(&mut self.uniforms, uniform_data, bundle_idx);
+
+        write_png(path, &grid, |tile| {
+            self.uniforms
+                .update(&self.offscreen_state.queue, uniform_data.with_tile(tile));
+            self.offscreen_state
+                .draw(&target, &self.uniforms.uniform_bind_group, bundle_idx)
+        })
+    }
+}
+
+/// Request a device with the features and limits every renderer here needs.
+pub async fn request_device(
+    adapter: &wgpu::Adapter,
+) -> Result<(wgpu::Device, wgpu::Queue), wgpu::RequestDeviceError> {
+    adapter
+        .request_device(&wgpu::DeviceDescriptor {
+            required_features: wgpu::Features::empty(),
+            label: None,
+            required_limits: wgpu::Limits::default(),
+            memory_hints: wgpu::MemoryHints::default(),
+            trace: wgpu::Trace::Off,
+        })
+        .await
+}
@@ -0,0 +1,130 @@
+use std::fmt;
+
+use wgpu::{BindGroup, TextureFormat};
+
+use crate::fractals::Custom;
+use crate::histogram::{self, ValuePass};
+use crate::offscreen::OffscreenTarget;
+use crate::renderer::Renderer;
+use crate::shader::ShaderError;
+use crate::uniforms::{UniformData, Uniforms};
+
+use super::request_device;
+
+/// Format used for offscreen frames. sRGB so the stored bytes match what a
+/// typical sRGB swapchain would present.
+pub const OFFSCREEN_FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+
+#[derive(Debug)]
+pub enum OffscreenError {
+    Adapter(wgpu::RequestAdapterError),
+    Device(wgpu::RequestDeviceError),
+}
+
+impl fmt::Display for OffscreenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OffscreenError::Adapter(e) => write!(f, "no suitable adapter: {e}"),
+            OffscreenError::Device(e) => write!(f, "failed to create device: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for OffscreenError {}
+
+/// wgpu state for rendering without a window or surface.
+pub struct OffscreenState {
+    _instance: wgpu::Instance,
+    _adapter: wgpu::Adapter,
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+    renderer: Renderer,
+    value_pass: ValuePass,
+}
+
+impl OffscreenState {
+    /// `force_fallback_adapter` selects the software adapter (e.g. lavapipe or
+    /// llvmpipe), which works on machines without a GPU.
+    pub async fn new(force_fallback_adapter: bool) -> Result<Self, OffscreenError> {
+        let instance = wgpu::Instance::default();
+
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptionsBase {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                force_fallback_adapter,
+                compatible_surface: None,
+            })
+            .await
+            .map_err(OffscreenError::Adapter)?;
+
+        let (device, queue) = request_device(&adapter)
+            .await
+            .map_err(OffscreenError::Device)?;
+
+        let renderer = Renderer::new(&device, &OFFSCREEN_FORMAT);
+        let value_pass = ValuePass::new(&device, histogram::value_format(&adapter));
+
+        Ok(OffscreenState {
+            _instance: instance,
+            _adapter: adapter,
+            device,
+            queue,
+            renderer,
+            value_pass,
+        })
+    }
+
+    /// Rebuild the pipelines whose shader source has changed.
+    pub fn reload_shaders(&mut self) -> Vec<ShaderError> {
+        // Builds from the same sources, so fails the same way.
+        self.value_pass.reload(&self.device);
+        self.renderer.reload(&self.device)
+    }
+
+    /// Draw `formula` for the formula fractal, see [`Renderer::set_formula`].
+    pub fn set_formula(&mut self, formula: Custom) -> Result<(), ShaderError> {
+        self.value_pass.set_formula(&self.device, formula.clone())?;
+        self.renderer.set_formula(&self.device, formula)
+    }
+
+    /// Equalise `uniforms` for the view in `uniform_data`, see
+    /// [`ValuePass::equalise`].
+    pub fn equalise(
+        &mut self,
+        uniforms: &mut Uniforms,
+        uniform_data: UniformData,
+        bundle_idx: usize,
+    ) {
+        self.value_pass.equalise(
+            &self.device,
+            &self.queue,
+            uniforms,
+            uniform_data,
+            bundle_idx,
+        );
+    }
+
+    pub fn max_texture_dimension(&self) -> u32 {
+        self.device.limits().max_texture_dimension_2d
+    }
+
+    pub fn create_offscreen_target(&self, width: u32, height: u32) -> OffscreenTarget {
+        OffscreenTarget::new(&self.device, width, height, OFFSCREEN_FORMAT)
+    }
+
+    /// Render a frame into `target` and return its RGBA8 pixels, top row first.
+    pub fn draw(
+        &self,
+        target: &OffscreenTarget,
+        uniform_bind_group: &BindGroup,
+        bundle_idx: usize,
+    ) -> Vec<u8> {
+        target.draw(
+            &self.device,
+            &self.queue,
+            &self.renderer,
+            uniform_bind_group,
+            bundle_idx,
+        )
+    }
+}
@@ -0,0 +1,306 @@
+//! Histogram-equalised colouring.
+//!
+//! Colouring by intensity as it is leaves most of the palette unused at most
+//! zoom levels, where the pixels' smooth iteration counts bunch together.
+//! Equalising spreads them evenly over the palette instead, in two passes.
+//! The first draws the whole view small with [`Colouring::Values`], which
+//! has `get_colour` in `prelude.wgsl` write out each pixel's intensity in
+//! place of its colour, see [`value_intensity`]. Those are read back and
+//! sorted into an [`Equaliser`], and the second pass, with
+//! [`Colouring::Histogram`], maps every intensity through it before picking
+//! a colour.
+//!
+//! Every fractal's intensity rises with its smooth iteration count, and
+//! equalising depends only on their order, so intensities stand in for the
+//! counts themselves. Read back intensities are rounded down, so that the
+//! many pixels sharing an intensity, such as a band of unsmoothed
+//! iterations, equalise to the top of their share however the GPU rounds.
+
+use half::f16;
+use wgpu::{Adapter, Device, Queue, TextureFormat, TextureUsages};
+
+use crate::fractals::{Custom, FRACTALS};
+use crate::named::impl_named;
+use crate::offscreen::OffscreenTarget;
+use crate::renderer::Renderer;
+use crate::shader::ShaderError;
+use crate::uniforms::{UniformData, Uniforms};
+
+/// Intensities kept in an [`Equaliser`].
+pub const QUANTILES: usize = 256;
+/// Longest side in pixels of the first pass.
+pub const SAMPLE_DIMENSION: u32 = 256;
+/// Seconds of animation an animated view keeps its equaliser for, so that
+/// it follows the picture without a first pass every frame.
+pub const EQUALISE_INTERVAL: f32 = 0.1;
+/// Format of the first pass's texture, which holds intensities in red.
+pub const VALUE_FORMAT: TextureFormat = TextureFormat::R32Float;
+/// Format of the first pass's texture where the GPU cannot draw to
+/// [`VALUE_FORMAT`], as with OpenGL. Keeps intensities to about three
+/// significant figures.
+pub const FALLBACK_VALUE_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+
+/// How intensities pick colours from the palette, as passed in
+/// `UniformData::colouring`.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Colouring {
+    /// As they are.
+    Direct = 0,
+    /// Equalised over the view first.
+    Histogram = 1,
+    /// The first pass of `Histogram`: colours hold intensities, see
+    /// [`value_intensity`].
+    Values = 2,
+    /// The search for glitches: glitched pixels are magenta and all others
+    /// black, see [`crate::perturbation::GlitchSearch`].
+    Glitches = 3,
+}
+
+/// Evenly spaced ranks of the intensities in a view, for equalising them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Equaliser {
+    /// [`QUANTILES`] intensities in rising order, from the lowest in the
+    /// view to the highest.
+    pub quantiles: Vec<f32>,
+}
+
+/// What the equaliser in a [`Uniforms`] was worked out for, so that it is
+/// only worked out again when the view, parameters or fractal change, or an
+/// animation moves on.
+#[derive(Clone, Copy)]
+pub struct Equalised {
+    /// The uniforms, less those that leave intensities alone. Time is kept
+    /// to the [`EQUALISE_INTERVAL`] it falls in, and only if the fractal is
+    /// animated.
+    uniform_data: UniformData,
+    bundle_idx: usize,
+    /// [`ValuePass::shaders`] at the time.
+    shaders: u32,
+}
+
+/// Draws the first pass of histogram colouring on the GPU.
+pub struct ValuePass {
+    renderer: Renderer,
+    format: TextureFormat,
+    /// Kept from one first pass to the next, while the size stays the same.
+    target: Option<OffscreenTarget>,
+    /// Counts the times the pipelines may have changed, which changes
+    /// intensities too.
+    shaders: u32,
+}
+
+// `Values` and `Glitches` are only used internally, so they are not ones to
+// choose from.
+impl_named!(Colouring {
+    Direct => "direct",
+    Histogram => "histogram",
+} hidden {
+    Values => "values",
+    Glitches => "glitches",
+});
+
+impl Default for Equaliser {
+    /// Leaves intensities as they are.
+    fn default() -> Self {
+        let last = (QUANTILES - 1) as f32;
+        Self {
+            quantiles: (0..QUANTILES).map(|k| k as f32 / last).collect(),
+        }
+    }
+}
+
+impl Equaliser {
+    /// Rank the intensities in a view.
+    pub fn from_values(values: impl IntoIterator<Item = f32>) -> Self {
+        let mut values: Vec<f32> = values.into_iter().collect();
+        if values.is_empty() {
+            return Self::default();
+        }
+        values.sort_unstable_by(f32::total_cmp);
+
+        let last = values.len() - 1;
+        let quantiles = (0..QUANTILES)
+            .map(|k| values[(k * last + (QUANTILES - 1) / 2) / (QUANTILES - 1)])
+            .collect();
+        Self { quantiles }
+    }
+
+    /// CPU version of `equalise` in `prelude.wgsl`: the fraction of the
+    /// view at or below `intensity`, from 0 for the lowest to 1.
+    pub fn equalise(&self, intensity: f32) -> f32 {
+        let quantiles = &self.quantiles;
+        let last = quantiles.len() - 1;
+        if intensity <= quantiles[0] {
+            return 0.0;
+        }
+        if intensity >= quantiles[last] {
+            return 1.0;
+        }
+
+        // Narrow down to the quantiles either side of the intensity.
+        let (mut low, mut high) = (0, last);
+        while high - low > 1 {
+            let middle = (low + high) / 2;
+            if quantiles[middle] <= intensity {
+                low = middle;
+            } else {
+                high = middle;
+            }
+        }
+        let fraction = (intensity - quantiles[low]) / (quantiles[high] - quantiles[low]);
+        (low as f32 + fraction) / last as f32
+    }
+}
+
+/// The intensity held in red by a pixel of the first pass, or `None` if it
+/// never picked a colour. `get_colour` writes `-1 - intensity`, with the
+/// intensity clamped to [0, 1], so that it stands apart from the colours
+/// drawn without one, such as black inside the set. `epsilon` is that of
+/// the format `red` was stored in, the most storing it can have moved it,
+/// and the intensity is lowered by as much.
+pub fn value_intensity(red: f32, epsilon: f32) -> Option<f32> {
+    (red <= -1.0).then_some(-1.0 - red - epsilon)
+}
+
+impl Equalised {
+    /// What equalising `uniform_data` for the fractal at `bundle_idx`
+    /// depends on, where `shaders` counts the times the first pass's
+    /// pipelines have changed.
+    pub fn new(uniform_data: UniformData, bundle_idx: usize, shaders: u32) -> Self {
+        let time = if FRACTALS[bundle_idx].animated(&uniform_data.params) {
+            (uniform_data.time / EQUALISE_INTERVAL).floor()
+        } else {
+            0.0
+        };
+        Self {
+            uniform_data: UniformData {
+                time,
+                tile: [0.0; 4],
+                palette_offset: 0.0,
+                palette_density: 0.0,
+                light_angle: 0.0,
+                ..uniform_data
+            },
+            bundle_idx,
+            shaders,
+        }
+    }
+}
+
+impl PartialEq for Equalised {
+    fn eq(&self, other: &Self) -> bool {
+        bytemuck::bytes_of(&self.uniform_data) == bytemuck::bytes_of(&other.uniform_data)
+            && self.bundle_idx == other.bundle_idx
+            && self.shaders == other.shaders
+    }
+}
+
+/// The format the first pass draws to on `adapter`.
+pub fn value_format(adapter: &Adapter) -> TextureFormat {
+    let features = adapter.get_texture_format_features(VALUE_FORMAT);
+    if features
+        .allowed_usages
+        .contains(TextureUsages::RENDER_ATTACHMENT)
+    {
+        VALUE_FORMAT
+    } else {
+        FALLBACK_VALUE_FORMAT
+    }
+}
+
+/// Size of the first pass for an image of `resolution`: the same shape,
+/// shrunk to fit [`SAMPLE_DIMENSION`].
+pub fn sample_size(resolution: [f32; 2]) -> (u32, u32) {
+    let [width, height] = resolution.map(|side| side.max(1.0));
+    let scale = (SAMPLE_DIMENSION as f32 / width.max(height)).min(1.0);
+    (
+        ((width * scale).round() as u32).max(1),
+        ((height * scale).round() as u32).max(1),
+    )
+}
+
+impl ValuePass {
+    /// Draw to `format`, see [`value_format`].
+    pub fn new(device: &Device, format: TextureFormat) -> Self {
+        Self {
+            renderer: Renderer::new(device, &format),
+            format,
+            target: None,
+            shaders: 0,
+        }
+    }
+
+    /// Rebuild the pipelines whose shader source has changed, see
+    /// [`Renderer::reload`].
+    pub fn reload(&mut self, device: &Device) -> Vec<ShaderError> {
+        self.shaders += 1;
+        self.renderer.reload(device)
+    }
+
+    /// See [`Renderer::set_formula`].
+    pub fn set_formula(&mut self, device: &Device, formula: Custom) -> Result<(), ShaderError> {
+        self.shaders += 1;
+        self.renderer.set_formula(device, formula)
+    }
+
+    /// If `uniform_data` asks for histogram colouring, draw its whole view
+    /// and give `uniforms` the equaliser for it, unless they already have
+    /// it. Drawing leaves the first pass's data in `uniforms`, so update
+    /// them before drawing.
+    pub fn equalise(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        uniforms: &mut Uniforms,
+        uniform_data: UniformData,
+        bundle_idx: usize,
+    ) {
+        if uniform_data.colouring != Colouring::Histogram as u32 {
+            return;
+        }
+
+        let equalised = Equalised::new(uniform_data, bundle_idx, self.shaders);
+        if uniforms.equalised() == Some(equalised) {
+            return;
+        }
+
+        let (width, height) = sample_size(uniform_data.resolution);
+        let target = match self.target.take() {
+            Some(target) if target.size() == (width, height) => target,
+            _ => OffscreenTarget::new(device, width, height, self.format),
+        };
+        uniforms.update(
+            queue,
+            UniformData {
+                tile: [0.0, 0.0, 1.0, 1.0],
+                colouring: Colouring::Values as u32,
+                ..uniform_data
+            },
+        );
+        let pixels = target.draw(
+            device,
+            queue,
+            &self.renderer,
+            &uniforms.uniform_bind_group,
+            bundle_idx,
+        );
+
+        let (red, epsilon): (fn(&[u8]) -> f32, f32) = match self.format {
+            FALLBACK_VALUE_FORMAT => (
+                |pixel| f16::from_ne_bytes([pixel[0], pixel[1]]).to_f32(),
+                f16::EPSILON.to_f32(),
+            ),
+            _ => (
+                |pixel| f32::from_ne_bytes([pixel[0], pixel[1], pixel[2], pixel[3]]),
+                f32::EPSILON,
+            ),
+        };
+        let pixel_size = self.format.block_copy_size(None).unwrap() as usize;
+        let values = pixels
+            .chunks_exact(pixel_size)
+            .filter_map(|pixel| value_intensity(red(pixel), epsilon));
+        uniforms.set_equaliser(queue, &Equaliser::from_values(values), equalised);
+        self.target = Some(target);
+    }
+}
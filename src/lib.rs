@@ -0,0 +1,50 @@
+//! Escape-time fractal rendering on the GPU with wgpu, plus a CPU reference
+//! renderer that mirrors the shaders.
+//!
+//! Render a view to RGBA8 pixels without a window:
+//!
+//! ```no_run
+//! use fractal::perturbation::Orbit;
+//! use fractal::{FRACTALS, HeadlessState, UniformData, Viewport};
+//!
+//! let julia = fractal::fractals::find("julia").unwrap();
+//! let view = Viewport::new(2.0, [0.1, 0.0]).unwrap();
+//! let uniform_data = UniformData {
+//!     resolution: [640.0, 480.0],
+//!     params: FRACTALS[julia].default_params(),
+//!     ..UniformData::from_viewport(&view)
+//! };
+//!
+//! let mut headless = pollster::block_on(HeadlessState::new(false)).unwrap();
+//! // Julia needs no reference orbit, so pass an empty one.
+//! let pixels = headless.draw(640, 480, uniform_data, &Orbit::default(), julia);
+//! assert_eq!(pixels.len(), 640 * 480 * 4);
+//! ```
+
+pub mod cpu;
+pub mod export;
+pub mod formula;
+pub mod fractals;
+pub mod headless;
+pub mod histogram;
+pub mod named;
+pub mod offscreen;
+pub mod palette;
+pub mod perturbation;
+pub mod polynomial;
+pub mod precision;
+mod quad;
+pub mod renderer;
+pub mod shader;
+pub mod shader_watcher;
+pub mod shading;
+pub mod tiled;
+pub mod uniforms;
+mod vertex;
+pub mod viewport;
+
+pub use fractals::{FRACTALS, Fractal, ParamSpec};
+pub use headless::HeadlessState;
+pub use precision::Precision;
+pub use uniforms::UniformData;
+pub use viewport::Viewport;
@@ -1,15 +1,328 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use winit::dpi::PhysicalSize;
 use winit::event_loop::{ControlFlow, EventLoop};
 
 mod app;
+mod cli;
+
+use fractal::formula::Formula;
+use fractal::fractals::Custom;
+use fractal::palette::{Palette, Table};
+use fractal::perturbation::Orbit;
+use fractal::uniforms::MAX_PARAMS;
+use fractal::{FRACTALS, HeadlessState, Precision, UniformData, Viewport, cpu};
+
+use app::{App, Colours};
+use cli::{Mode, Options, USAGE};
+
+const HEADLESS_WIDTH: u32 = 800;
+const HEADLESS_HEIGHT: u32 = 600;
+const DEFAULT_EXPORT_SIZE: PhysicalSize<u32> = PhysicalSize::new(1920, 1080);
 
-use app::App;
 fn main() {
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{e}\n\n{USAGE}");
+            std::process::exit(2);
+        }
+    };
+
+    let formula = match options.formula.as_deref().map(Formula::parse) {
+        Some(Ok(formula)) => Custom::new(formula),
+        Some(Err(e)) => {
+            eprintln!("Error: {e}");
+            std::process::exit(2);
+        }
+        None => Custom::default(),
+    };
+
+    let palette = match Palette::find(options.palette.as_deref().unwrap_or("classic")) {
+        Ok(palette) => palette,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            std::process::exit(2);
+        }
+    };
+
+    let result = match &options.mode {
+        Mode::Viewer => run_viewer(&options, formula, palette),
+        Mode::Headless => run_headless(&options, &formula, &palette),
+        Mode::Export(path) => run_export(&options, &formula, &palette, path),
+        Mode::SavePalette(path) => save_palette(&options, &palette, path),
+        Mode::Help => {
+            println!("{USAGE}");
+            Ok(())
+        }
+    };
+
+    if let Err(e) = result {
+        eprintln!("Error: {e}");
+        std::process::exit(1);
+    }
+}
+
+fn run_viewer(
+    options: &Options,
+    formula: Custom,
+    palette: Palette,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let view = options.view.as_deref().map(load_view).transpose()?;
+    let params = fractal_params(options)?;
+
     let event_loop = EventLoop::new().unwrap();
 
     event_loop.set_control_flow(ControlFlow::Poll);
 
-    let mut app = App::default();
+    let mut app = App::new(
+        options.size,
+        options.bundle_idx,
+        view,
+        params,
+        options.watch_shaders,
+        Colours {
+            palette,
+            interpolation: options.interpolation,
+            palette_offset: options.palette_offset,
+            palette_density: options.palette_density,
+            colouring: options.colouring,
+            shading: options.shading,
+            light_angle: options.light_angle,
+        },
+        formula,
+    );
     if let Err(e) = event_loop.run_app(&mut app) {
         eprintln!("Application error: {e}")
     }
+
+    Ok(())
+}
+
+/// Read a view saved with the viewer's V key.
+fn load_view(path: &Path) -> Result<Viewport, Box<dyn std::error::Error>> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+    Ok(text.parse()?)
+}
+
+/// Create the GPU renderer, drawing `formula` for the formula fractal and
+/// colouring with `palette`, unless the CPU was asked for. Falls back to the
+/// CPU when no adapter is available.
+fn headless_state(
+    options: &Options,
+    formula: &Custom,
+    palette: &Arc<Table>,
+) -> Result<Option<HeadlessState>, Box<dyn std::error::Error>> {
+    if options.cpu {
+        return Ok(None);
+    }
+
+    match pollster::block_on(HeadlessState::new(options.force_fallback_adapter)) {
+        Ok(mut headless) => {
+            headless.set_formula(formula.clone())?;
+            headless.set_palette(palette.clone());
+            Ok(Some(headless))
+        }
+        Err(e) => {
+            eprintln!("GPU unavailable ({e}), rendering on the CPU");
+            Ok(None)
+        }
+    }
+}
+
+/// Render one frame of every fractal without opening a window.
+fn run_headless(
+    options: &Options,
+    formula: &Custom,
+    palette: &Palette,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let palette = Arc::new(palette.bake(options.interpolation));
+    let mut headless = headless_state(options, formula, &palette)?;
+
+    for (bundle_idx, fractal) in FRACTALS.iter().enumerate() {
+        let viewport = fractal.default_view();
+        let params = fractal.default_params();
+        let uniform_data = UniformData {
+            resolution: [HEADLESS_WIDTH as f32, HEADLESS_HEIGHT as f32],
+            params,
+            palette_offset: options.palette_offset,
+            palette_density: options.palette_density,
+            colouring: options.colouring as u32,
+            shading: options.shading as u32,
+            light_angle: options.light_angle,
+            ..UniformData::from_viewport(&viewport)
+        };
+        let orbit = reference_orbit(
+            headless.as_mut(),
+            formula,
+            bundle_idx,
+            &viewport,
+            uniform_data,
+        );
+        let pixels = match headless.as_mut() {
+            Some(headless) => headless.draw(
+                HEADLESS_WIDTH,
+                HEADLESS_HEIGHT,
+                uniform_data,
+                &orbit,
+                bundle_idx,
+            ),
+            None => cpu::render(
+                HEADLESS_WIDTH,
+                HEADLESS_HEIGHT,
+                &uniform_data,
+                &orbit,
+                formula.stand_in(*fractal),
+                &palette,
+            ),
+        };
+        println!(
+            "Fractal {} ({}): rendered {HEADLESS_WIDTH}x{HEADLESS_HEIGHT} ({} bytes)",
+            bundle_idx + 1,
+            fractal.name(),
+            pixels.len()
+        );
+    }
+
+    Ok(())
+}
+
+/// The chosen fractal's parameters with the command line's overrides.
+fn fractal_params(options: &Options) -> Result<[f32; MAX_PARAMS], String> {
+    let fractal = FRACTALS[options.bundle_idx];
+    let mut params = fractal.default_params();
+    for (name, value) in &options.params {
+        let idx = fractal
+            .param_index(name)
+            .ok_or(format!("{} has no parameter {name}", fractal.name()))?;
+        let spec = &fractal.params()[idx];
+        if !(spec.min..=spec.max).contains(value) {
+            return Err(format!(
+                "{name} must be between {} and {}, got {value}",
+                spec.min, spec.max
+            ));
+        }
+        params[idx] = *value;
+    }
+    Ok(params)
+}
+
+/// Render the view described on the command line to a png.
+fn run_export(
+    options: &Options,
+    formula: &Custom,
+    palette: &Palette,
+    path: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let fractal = FRACTALS[options.bundle_idx];
+    let params = fractal_params(options)?;
+
+    let size = options.size.unwrap_or(DEFAULT_EXPORT_SIZE);
+    let viewport = match &options.view {
+        Some(path) => load_view(path)?,
+        None => Viewport::new(options.zoom, options.offset)?,
+    };
+    let precision = Precision::resolve(
+        options.precision,
+        fractal,
+        &viewport,
+        [size.width as f32, size.height as f32],
+    );
+    let uniform_data = UniformData {
+        time: options.time,
+        params,
+        precision_mode: precision as u32,
+        palette_offset: options.palette_offset,
+        palette_density: options.palette_density,
+        colouring: options.colouring as u32,
+        shading: options.shading as u32,
+        light_angle: options.light_angle,
+        ..UniformData::from_viewport(&viewport)
+    };
+
+    let palette = Arc::new(palette.bake(options.interpolation));
+    let mut headless = headless_state(options, formula, &palette)?;
+    let orbit = reference_orbit(
+        headless.as_mut(),
+        formula,
+        options.bundle_idx,
+        &viewport,
+        UniformData {
+            resolution: [size.width as f32, size.height as f32],
+            ..uniform_data
+        },
+    );
+    match headless {
+        Some(mut headless) => headless.export_png(
+            path,
+            size.width,
+            size.height,
+            uniform_data,
+            &orbit,
+            options.bundle_idx,
+        )?,
+        None => cpu::export_png(
+            path,
+            size.width,
+            size.height,
+            uniform_data,
+            &orbit,
+            formula.stand_in(fractal),
+            &palette,
+        )?,
+    }
+    println!(
+        "Saved {}x{} image to {}",
+        size.width,
+        size.height,
+        path.display()
+    );
+
+    Ok(())
+}
+
+/// Write the palette chosen on the command line to a file.
+fn save_palette(
+    options: &Options,
+    palette: &Palette,
+    path: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    palette.save(path, options.interpolation)?;
+    println!("Saved palette {} to {}", palette.name, path.display());
+    Ok(())
+}
+
+/// The reference orbit the fractal at `bundle_idx` renders the view drawn
+/// with `uniform_data` with, or nothing if it does not use one. Reports the
+/// iterations its series skips and any references added for glitches.
+fn reference_orbit(
+    headless: Option<&mut HeadlessState>,
+    formula: &Custom,
+    bundle_idx: usize,
+    viewport: &Viewport,
+    uniform_data: UniformData,
+) -> Orbit {
+    let fractal = formula.stand_in(FRACTALS[bundle_idx]);
+    let [width, height] = uniform_data.resolution;
+    let Some(reference) = fractal.reference(viewport, &uniform_data.params, width / height) else {
+        return Orbit::default();
+    };
+
+    let orbit = match headless {
+        Some(headless) => headless.orbit(&reference, uniform_data, bundle_idx),
+        None => cpu::orbit(&reference, &uniform_data, fractal),
+    };
+    println!(
+        "Series approximation skipped {} of {} iterations",
+        orbit.series.skip, reference.max_iterations
+    );
+    if !orbit.secondaries.is_empty() {
+        println!(
+            "Added {} references for glitched pixels",
+            orbit.secondaries.len()
+        );
+    }
+    orbit
 }
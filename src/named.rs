@@ -0,0 +1,51 @@
+//! Options chosen by name, on the command line, from the keyboard or in a
+//! formula.
+
+/// A fixed set of options, each with a name.
+pub trait Named: Copy + PartialEq + 'static {
+    /// The options to choose from, in the order they are cycled through.
+    const ALL: &'static [Self];
+
+    fn name(self) -> &'static str;
+
+    /// The option in [`Named::ALL`] called `name`.
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|option| option.name() == name)
+    }
+
+    /// The option after this one in [`Named::ALL`], wrapping around, or the
+    /// first if this one is not among them.
+    fn next(self) -> Self {
+        let idx = Self::ALL.iter().position(|option| *option == self);
+        Self::ALL[idx.map_or(0, |idx| (idx + 1) % Self::ALL.len())]
+    }
+}
+
+/// Implements [`Named`] for an enum from its variants' names. Variants after
+/// `hidden` have a name but are left out of [`Named::ALL`].
+macro_rules! impl_named {
+    (
+        $ty:ident {
+            $($variant:ident => $name:literal),+ $(,)?
+        }
+        $(hidden {
+            $($hidden:ident => $hidden_name:literal),+ $(,)?
+        })?
+    ) => {
+        impl $crate::named::Named for $ty {
+            const ALL: &'static [Self] = &[$($ty::$variant),+];
+
+            fn name(self) -> &'static str {
+                match self {
+                    $($ty::$variant => $name,)+
+                    $($($ty::$hidden => $hidden_name,)+)?
+                }
+            }
+        }
+    };
+}
+
+pub(crate) use impl_named;
@@ -0,0 +1,410 @@
+//! Palettes mapping a fractal's intensity to a colour.
+//!
+//! A palette is a list of colour stops. Before use it is baked into a
+//! [`Table`] of evenly spaced colours, interpolated between the stops in the
+//! chosen [`Interpolation`], which the shaders read from a storage buffer
+//! and the CPU renderer from memory. Shaders pick from the table with
+//! `get_colour` in `prelude.wgsl`, which also applies the cycling offset and
+//! density from the uniforms.
+//!
+//! Palette files have one stop per line: a position from 0 to 1 followed by
+//! an sRGB colour as `#rrggbb` or three linear RGB values. Blank lines and
+//! lines starting with `//` are ignored.
+//!
+//! ```text
+//! // fire.palette
+//! 0.0  #000000
+//! 0.5  #f06000
+//! 1.0  1.0 1.0 1.0
+//! ```
+//!
+//! Fractint, GIMP and Ultra Fractal palettes are read and written too, see
+//! [`Format`].
+
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use crate::named::impl_named;
+
+mod formats;
+
+pub use formats::Format;
+
+/// Colours in a baked [`Table`].
+pub const TABLE_SIZE: usize = 256;
+
+/// The palettes that come with the renderer, the first being the default.
+const BUILT_IN: &[(&str, &str)] = &[
+    ("classic", include_str!("palettes/classic.palette")),
+    ("fire", include_str!("palettes/fire.palette")),
+    ("ocean", include_str!("palettes/ocean.palette")),
+    ("grey", include_str!("palettes/grey.palette")),
+    ("rainbow", include_str!("palettes/rainbow.palette")),
+];
+
+static BUILT_IN_PALETTES: LazyLock<Vec<Palette>> = LazyLock::new(|| {
+    BUILT_IN
+        .iter()
+        .map(|(name, text)| Palette::parse(name, text).expect("built-in palettes parse"))
+        .collect()
+});
+
+/// A colour at a position along the palette.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stop {
+    pub position: f32,
+    /// Linear RGB.
+    pub colour: [f32; 3],
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    pub name: String,
+    /// In order of position.
+    pub stops: Vec<Stop>,
+}
+
+/// The colour space the stops are blended in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interpolation {
+    LinearRgb,
+    /// Perceptually even steps in lightness and hue.
+    Oklab,
+    /// Round the hue circle the short way, on sRGB values.
+    Hsv,
+}
+
+/// A palette baked for lookup, see [`Palette::bake`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Table {
+    /// [`TABLE_SIZE`] linear RGB colours evenly spaced from 0 to 1, padded to
+    /// four floats as the shaders' storage buffer is laid out.
+    pub colours: Vec<[f32; 4]>,
+}
+
+#[derive(Debug)]
+pub enum PaletteError {
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+    Write {
+        path: PathBuf,
+        error: io::Error,
+    },
+    /// Lines count from 1.
+    Syntax {
+        line: usize,
+        message: String,
+    },
+    /// A file with several palettes has none of the name asked for.
+    Missing {
+        path: PathBuf,
+        name: String,
+    },
+}
+
+impl fmt::Display for PaletteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PaletteError::Io { path, error } => {
+                write!(f, "failed to read {}: {error}", path.display())
+            }
+            PaletteError::Write { path, error } => {
+                write!(f, "failed to write {}: {error}", path.display())
+            }
+            PaletteError::Syntax { line, message } => write!(f, "palette line {line}: {message}"),
+            PaletteError::Missing { path, name } => {
+                write!(f, "no palette {name} in {}", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for PaletteError {}
+
+/// The palettes that come with the renderer.
+pub fn built_in() -> &'static [Palette] {
+    &BUILT_IN_PALETTES
+}
+
+impl Palette {
+    /// Parse the palette file format, see the [module](self) docs.
+    pub fn parse(name: &str, text: &str) -> Result<Self, PaletteError> {
+        let mut stops = Vec::new();
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+            let error = |message: String| PaletteError::Syntax {
+                line: idx + 1,
+                message,
+            };
+
+            let mut words = line.split_whitespace();
+            let position = words.next().unwrap_or_default();
+            let position: f32 = position
+                .parse()
+                .map_err(|_| error(format!("expected a position, got {position}")))?;
+            if !(0.0..=1.0).contains(&position) {
+                return Err(error(format!("position {position} is not between 0 and 1")));
+            }
+            if stops
+                .last()
+                .is_some_and(|stop: &Stop| stop.position > position)
+            {
+                return Err(error(format!("position {position} comes before the last")));
+            }
+
+            let colour: Vec<&str> = words.collect();
+            let colour = parse_colour(&colour).ok_or_else(|| {
+                error(format!(
+                    "expected #rrggbb or three numbers, got {}",
+                    colour.join(" ")
+                ))
+            })?;
+            stops.push(Stop { position, colour });
+        }
+
+        if stops.is_empty() {
+            return Err(PaletteError::Syntax {
+                line: text.lines().count().max(1),
+                message: "a palette needs at least one stop".to_string(),
+            });
+        }
+        Ok(Self {
+            name: name.to_string(),
+            stops,
+        })
+    }
+
+    /// Read every palette in a file, in the [`Format`] its extension names.
+    /// Palettes without a name of their own are named after the file.
+    pub fn load_all(path: &Path) -> Result<Vec<Self>, PaletteError> {
+        let text = std::fs::read_to_string(path).map_err(|error| PaletteError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        let name = path
+            .file_stem()
+            .map_or("palette".into(), |stem| stem.to_string_lossy());
+        Format::from_path(path).parse(&name, &text)
+    }
+
+    /// Read the first palette in a file, see [`Palette::load_all`].
+    pub fn load(path: &Path) -> Result<Self, PaletteError> {
+        Ok(Self::load_all(path)?.swap_remove(0))
+    }
+
+    /// A built-in palette by name, or else the palette file at `name`. A
+    /// palette other than the first in a file is picked as `PATH#NAME`.
+    pub fn find(name: &str) -> Result<Self, PaletteError> {
+        if let Some(palette) = built_in().iter().find(|palette| palette.name == name) {
+            return Ok(palette.clone());
+        }
+
+        let path = Path::new(name);
+        let Some((file, wanted)) = name.rsplit_once('#').filter(|_| !path.exists()) else {
+            return Self::load(path);
+        };
+        Self::load_all(Path::new(file))?
+            .into_iter()
+            .find(|palette| palette.name == wanted)
+            .ok_or_else(|| PaletteError::Missing {
+                path: PathBuf::from(file),
+                name: wanted.to_string(),
+            })
+    }
+
+    /// Write the palette to a file in the [`Format`] its extension names,
+    /// blended in `interpolation` where the format cannot say how to blend.
+    pub fn save(&self, path: &Path, interpolation: Interpolation) -> Result<(), PaletteError> {
+        let text = Format::from_path(path).write(self, interpolation);
+        std::fs::write(path, text).map_err(|error| PaletteError::Write {
+            path: path.to_path_buf(),
+            error,
+        })
+    }
+
+    /// The colour at `position`, blending the stops either side of it.
+    /// Before the first stop and after the last the colour is constant.
+    pub fn colour_at(&self, position: f32, interpolation: Interpolation) -> [f32; 3] {
+        let next = self.stops.iter().position(|stop| stop.position > position);
+        match next {
+            Some(0) => self.stops[0].colour,
+            None => self.stops[self.stops.len() - 1].colour,
+            Some(next) => {
+                let (a, b) = (self.stops[next - 1], self.stops[next]);
+                let t = (position - a.position) / (b.position - a.position);
+                interpolation.blend(a.colour, b.colour, t)
+            }
+        }
+    }
+
+    /// Sample the palette at [`TABLE_SIZE`] evenly spaced positions.
+    pub fn bake(&self, interpolation: Interpolation) -> Table {
+        let colours = (0..TABLE_SIZE)
+            .map(|k| {
+                let [r, g, b] = self.colour_at(k as f32 / (TABLE_SIZE - 1) as f32, interpolation);
+                [r, g, b, 1.0]
+            })
+            .collect();
+        Table { colours }
+    }
+}
+
+impl_named!(Interpolation {
+    LinearRgb => "rgb",
+    Oklab => "oklab",
+    Hsv => "hsv",
+});
+
+impl Interpolation {
+    /// `t` of the way from `a` to `b`, both linear RGB.
+    pub fn blend(self, a: [f32; 3], b: [f32; 3], t: f32) -> [f32; 3] {
+        match self {
+            Interpolation::LinearRgb => mix(a, b, t),
+            Interpolation::Oklab => oklab_to_linear(mix(linear_to_oklab(a), linear_to_oklab(b), t)),
+            Interpolation::Hsv => {
+                let (a, b) = (
+                    rgb_to_hsv(a.map(encode_srgb)),
+                    rgb_to_hsv(b.map(encode_srgb)),
+                );
+                // A grey has no hue of its own, so takes the other colour's.
+                let hue_a = if a[1] == 0.0 { b[0] } else { a[0] };
+                let hue_b = if b[1] == 0.0 { a[0] } else { b[0] };
+                let turn = (hue_b - hue_a + 0.5).rem_euclid(1.0) - 0.5;
+                let hsv = [
+                    (hue_a + turn * t).rem_euclid(1.0),
+                    a[1] + (b[1] - a[1]) * t,
+                    a[2] + (b[2] - a[2]) * t,
+                ];
+                hsv_to_rgb(hsv).map(decode_srgb)
+            }
+        }
+    }
+}
+
+impl Default for Table {
+    /// The first built-in palette, blended in linear RGB.
+    fn default() -> Self {
+        built_in()[0].bake(Interpolation::LinearRgb)
+    }
+}
+
+impl Table {
+    /// CPU version of the lookup in `get_colour` in `prelude.wgsl`: the
+    /// colour for `intensity` with the palette repeated `density` times and
+    /// shifted by `offset`. A whole number of repetitions ends on the last
+    /// colour rather than wrapping to the first.
+    pub fn lookup(&self, intensity: f32, offset: f32, density: f32) -> [f32; 3] {
+        let u = intensity * density + offset;
+        let mut wrapped = u - u.floor();
+        if wrapped == 0.0 && u > 0.0 {
+            wrapped = 1.0;
+        }
+
+        let x = wrapped * (self.colours.len() - 1) as f32;
+        let i = (x as usize).min(self.colours.len() - 2);
+        let [r0, g0, b0, _] = self.colours[i];
+        let [r1, g1, b1, _] = self.colours[i + 1];
+        mix([r0, g0, b0], [r1, g1, b1], x - i as f32)
+    }
+}
+
+/// `#rrggbb` as sRGB, or three linear values.
+fn parse_colour(words: &[&str]) -> Option<[f32; 3]> {
+    match words {
+        [hex] => {
+            let hex = hex.strip_prefix('#')?;
+            if hex.len() != 6 {
+                return None;
+            }
+            let channel = |k: usize| {
+                u8::from_str_radix(hex.get(2 * k..2 * k + 2)?, 16)
+                    .ok()
+                    .map(|value| decode_srgb(value as f32 / 255.0))
+            };
+            Some([channel(0)?, channel(1)?, channel(2)?])
+        }
+        [r, g, b] => Some([r.parse().ok()?, g.parse().ok()?, b.parse().ok()?]),
+        _ => None,
+    }
+}
+
+fn mix(a: [f32; 3], b: [f32; 3], t: f32) -> [f32; 3] {
+    [
+        a[0] + (b[0] - a[0]) * t,
+        a[1] + (b[1] - a[1]) * t,
+        a[2] + (b[2] - a[2]) * t,
+    ]
+}
+
+/// An sRGB encoded channel from 0 to 1 as linear light.
+pub fn decode_srgb(encoded: f32) -> f32 {
+    if encoded <= 0.04045 {
+        encoded / 12.92
+    } else {
+        ((encoded + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// A linear channel from 0 to 1 as sRGB encoded.
+pub fn encode_srgb(linear: f32) -> f32 {
+    if linear <= 0.003_130_8 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Björn Ottosson's OKLab, from linear sRGB.
+fn linear_to_oklab([r, g, b]: [f32; 3]) -> [f32; 3] {
+    let l = (0.412_221_46 * r + 0.536_332_55 * g + 0.051_445_995 * b).cbrt();
+    let m = (0.211_903_5 * r + 0.680_699_5 * g + 0.107_396_96 * b).cbrt();
+    let s = (0.088_302_46 * r + 0.281_718_85 * g + 0.629_978_7 * b).cbrt();
+    [
+        0.210_454_26 * l + 0.793_617_8 * m - 0.004_072_047 * s,
+        1.977_998_5 * l - 2.428_592_2 * m + 0.450_593_7 * s,
+        0.025_904_037 * l + 0.782_771_77 * m - 0.808_675_77 * s,
+    ]
+}
+
+fn oklab_to_linear([lightness, a, b]: [f32; 3]) -> [f32; 3] {
+    let l = (lightness + 0.396_337_78 * a + 0.215_803_76 * b).powi(3);
+    let m = (lightness - 0.105_561_346 * a - 0.063_854_17 * b).powi(3);
+    let s = (lightness - 0.089_484_18 * a - 1.291_485_5 * b).powi(3);
+    [
+        4.076_741_7 * l - 3.307_711_6 * m + 0.230_969_94 * s,
+        -1.268_438 * l + 2.609_757_4 * m - 0.341_319_38 * s,
+        -0.004_196_086_3 * l - 0.703_418_6 * m + 1.707_614_7 * s,
+    ]
+}
+
+/// Hue, saturation and value, each from 0 to 1.
+fn rgb_to_hsv([r, g, b]: [f32; 3]) -> [f32; 3] {
+    let max = r.max(g).max(b);
+    let range = max - r.min(g).min(b);
+    let hue = if range == 0.0 {
+        0.0
+    } else if max == r {
+        ((g - b) / range).rem_euclid(6.0)
+    } else if max == g {
+        (b - r) / range + 2.0
+    } else {
+        (r - g) / range + 4.0
+    };
+    let saturation = if max == 0.0 { 0.0 } else { range / max };
+    [hue / 6.0, saturation, max]
+}
+
+fn hsv_to_rgb([hue, saturation, value]: [f32; 3]) -> [f32; 3] {
+    let channel = |n: f32| {
+        let k = (n + hue * 6.0).rem_euclid(6.0);
+        value - value * saturation * k.min(4.0 - k).clamp(0.0, 1.0)
+    };
+    [channel(5.0), channel(3.0), channel(1.0)]
+}
@@ -0,0 +1,625 @@
+//! Palette files from other fractal programs and graphics editors.
+//!
+//! - Fractint `.map`: one `r g b` line per colour, 0 to 255 in sRGB, spread
+//!   evenly from 0 to 1. Anything after the third number is a comment.
+//! - GIMP `.ggr`: segments, each blending between two colours with its own
+//!   midpoint, blend function and colouring. Segments that are not a plain
+//!   linear RGB blend are sampled into several stops.
+//! - Ultra Fractal `.ugr`: any number of named gradients with colours at
+//!   indices from 0 to 399 that wrap round. Smoothing and opacity are
+//!   ignored.
+//!
+//! These programs blend in sRGB rather than linear RGB, so a gradient looks
+//! slightly different between stops here and there.
+
+use std::fmt::Write;
+use std::path::Path;
+
+use super::{
+    Interpolation, Palette, PaletteError, Stop, TABLE_SIZE, decode_srgb, encode_srgb, hsv_to_rgb,
+    mix, rgb_to_hsv,
+};
+
+/// Stops each curved or HSV `.ggr` segment is sampled into, and segments
+/// each gap between stops is written as when not blending in RGB.
+const SEGMENT_SAMPLES: usize = 16;
+/// Positions in an Ultra Fractal gradient.
+const UGR_INDICES: i64 = 400;
+
+/// The file formats palettes are read from and written to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// This renderer's own format, see the [module](super) docs.
+    Native,
+    Map,
+    Ggr,
+    Ugr,
+}
+
+impl Format {
+    /// The format a file is in, going by its extension.
+    pub fn from_path(path: &Path) -> Self {
+        let extension = path.extension().and_then(|extension| extension.to_str());
+        match extension.map(str::to_ascii_lowercase).as_deref() {
+            Some("map") => Format::Map,
+            Some("ggr") => Format::Ggr,
+            Some("ugr") => Format::Ugr,
+            _ => Format::Native,
+        }
+    }
+
+    /// Every palette in `text`. Only `.ugr` files hold more than one; the
+    /// others are called `name`.
+    pub fn parse(self, name: &str, text: &str) -> Result<Vec<Palette>, PaletteError> {
+        match self {
+            Format::Native => Ok(vec![Palette::parse(name, text)?]),
+            Format::Map => Ok(vec![parse_map(name, text)?]),
+            Format::Ggr => Ok(vec![parse_ggr(name, text)?]),
+            Format::Ugr => parse_ugr(text),
+        }
+    }
+
+    /// `palette` as a file in this format. `.map` and `.ggr` files hold the
+    /// palette blended in `interpolation`; the others hold its stops.
+    pub fn write(self, palette: &Palette, interpolation: Interpolation) -> String {
+        match self {
+            Format::Native => write_native(palette),
+            Format::Map => write_map(palette, interpolation),
+            Format::Ggr => write_ggr(palette, interpolation),
+            Format::Ugr => write_ugr(palette),
+        }
+    }
+}
+
+fn syntax(line: usize, message: String) -> PaletteError {
+    PaletteError::Syntax { line, message }
+}
+
+/// The error for a file that ends before it should, on its last line.
+fn early_end(text: &str, message: String) -> PaletteError {
+    syntax(text.lines().count().max(1), message)
+}
+
+fn parse_map(name: &str, text: &str) -> Result<Palette, PaletteError> {
+    let mut colours = Vec::new();
+    for (idx, line) in text.lines().enumerate() {
+        let words: Vec<&str> = line.split_whitespace().take(3).collect();
+        if words.is_empty() {
+            continue;
+        }
+
+        let channel = |word: &str| word.parse::<u8>().ok().map(srgb_byte);
+        let colour = match words[..] {
+            [r, g, b] => channel(r).zip(channel(g)).zip(channel(b)),
+            _ => None,
+        };
+        let Some(((r, g), b)) = colour else {
+            return Err(syntax(
+                idx + 1,
+                format!(
+                    "expected three numbers from 0 to 255, got {}",
+                    words.join(" ")
+                ),
+            ));
+        };
+        colours.push([r, g, b]);
+    }
+
+    if colours.is_empty() {
+        return Err(early_end(text, "the map has no colours".to_string()));
+    }
+    let last = (colours.len() - 1).max(1) as f32;
+    let stops = colours
+        .into_iter()
+        .enumerate()
+        .map(|(k, colour)| Stop {
+            position: k as f32 / last,
+            colour,
+        })
+        .collect();
+    Ok(Palette {
+        name: name.to_string(),
+        stops,
+    })
+}
+
+/// How a `.ggr` segment moves from its left colour to its right.
+#[derive(Clone, Copy, PartialEq)]
+enum Blend {
+    Linear,
+    Curved,
+    Sine,
+    SphereIncreasing,
+    SphereDecreasing,
+    Step,
+}
+
+/// The colour space a `.ggr` segment blends in.
+#[derive(Clone, Copy, PartialEq)]
+enum Colouring {
+    Rgb,
+    HsvCounterClockwise,
+    HsvClockwise,
+}
+
+struct Segment {
+    left: f32,
+    middle: f32,
+    right: f32,
+    /// sRGB.
+    left_colour: [f32; 3],
+    right_colour: [f32; 3],
+    blend: Blend,
+    colouring: Colouring,
+}
+
+fn parse_ggr(name: &str, text: &str) -> Result<Palette, PaletteError> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(idx, line)| (idx + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty());
+
+    match lines.next() {
+        Some((_, "GIMP Gradient")) => (),
+        Some((line, _)) => return Err(syntax(line, "expected GIMP Gradient".to_string())),
+        None => return Err(early_end(text, "the file is empty".to_string())),
+    }
+
+    let mut name = name.to_string();
+    let mut count = None;
+    for (line, words) in lines.by_ref() {
+        if let Some(title) = words.strip_prefix("Name:") {
+            name = title.trim().to_string();
+            continue;
+        }
+        count = Some(
+            words
+                .parse::<usize>()
+                .map_err(|_| syntax(line, format!("expected the segment count, got {words}")))?,
+        );
+        break;
+    }
+    let Some(count) = count else {
+        return Err(early_end(text, "missing the segment count".to_string()));
+    };
+    if count == 0 {
+        return Err(early_end(text, "the gradient has no segments".to_string()));
+    }
+
+    let mut stops: Vec<Stop> = Vec::new();
+    let mut end = 0.0;
+    for k in 0..count {
+        let Some((line, words)) = lines.next() else {
+            return Err(early_end(
+                text,
+                format!("expected {count} segments, found {k}"),
+            ));
+        };
+        let segment = parse_segment(words).map_err(|message| syntax(line, message))?;
+        if (segment.left - end).abs() > 1e-4 {
+            return Err(syntax(
+                line,
+                format!(
+                    "segment starts at {} but the last ended at {end}",
+                    segment.left
+                ),
+            ));
+        }
+        end = segment.right;
+        segment.push_stops(&mut stops);
+    }
+    if let Some((line, _)) = lines.next() {
+        return Err(syntax(line, format!("expected only {count} segments")));
+    }
+
+    Ok(Palette { name, stops })
+}
+
+/// One segment line: positions, left and right RGBA, blend and colouring,
+/// and optionally where the end colours come from, which is ignored.
+fn parse_segment(words: &str) -> Result<Segment, String> {
+    let numbers = words
+        .split_whitespace()
+        .map(|word| word.parse::<f32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| format!("expected a segment of numbers, got {words}"))?;
+    if numbers.len() != 13 && numbers.len() != 15 {
+        return Err(format!(
+            "expected 13 or 15 numbers in a segment, got {}",
+            numbers.len()
+        ));
+    }
+
+    let (left, middle, right) = (numbers[0], numbers[1], numbers[2]);
+    if !(0.0 <= left && left <= middle && middle <= right && right <= 1.0) {
+        return Err(format!(
+            "segment positions {left} {middle} {right} are not in order from 0 to 1"
+        ));
+    }
+    let blend = match numbers[11] as u32 {
+        0 => Blend::Linear,
+        1 => Blend::Curved,
+        2 => Blend::Sine,
+        3 => Blend::SphereIncreasing,
+        4 => Blend::SphereDecreasing,
+        5 => Blend::Step,
+        other => return Err(format!("unknown blend function {other}")),
+    };
+    let colouring = match numbers[12] as u32 {
+        0 => Colouring::Rgb,
+        1 => Colouring::HsvCounterClockwise,
+        2 => Colouring::HsvClockwise,
+        other => return Err(format!("unknown colouring {other}")),
+    };
+    Ok(Segment {
+        left,
+        middle,
+        right,
+        left_colour: [numbers[3], numbers[4], numbers[5]],
+        right_colour: [numbers[7], numbers[8], numbers[9]],
+        blend,
+        colouring,
+    })
+}
+
+impl Segment {
+    /// Add stops that follow this segment to `stops`.
+    fn push_stops(&self, stops: &mut Vec<Stop>) {
+        let stop = |position: f32, colour: [f32; 3]| Stop {
+            position,
+            colour: colour.map(decode_srgb),
+        };
+        if self.blend == Blend::Step {
+            stops.extend([
+                stop(self.left, self.left_colour),
+                stop(self.middle, self.left_colour),
+                stop(self.middle, self.right_colour),
+                stop(self.right, self.right_colour),
+            ]);
+            return;
+        }
+
+        // A plain blend is left to the palette's interpolation, which is
+        // close to GIMP's blend in sRGB.
+        let centred = (self.middle - (self.left + self.right) / 2.0).abs() < 1e-4;
+        let samples = if self.blend == Blend::Linear && self.colouring == Colouring::Rgb && centred
+        {
+            1
+        } else {
+            SEGMENT_SAMPLES
+        };
+        for k in 0..=samples {
+            let t = k as f32 / samples as f32;
+            stops.push(stop(
+                self.left + (self.right - self.left) * t,
+                self.colour_at(t),
+            ));
+        }
+    }
+
+    /// The sRGB colour `t` of the way across the segment, as GIMP blends it.
+    fn colour_at(&self, t: f32) -> [f32; 3] {
+        let width = self.right - self.left;
+        let middle = if width > 0.0 {
+            (self.middle - self.left) / width
+        } else {
+            0.5
+        };
+        let linear = if t <= middle {
+            if middle > 0.0 { 0.5 * t / middle } else { 0.0 }
+        } else if middle < 1.0 {
+            0.5 + 0.5 * (t - middle) / (1.0 - middle)
+        } else {
+            1.0
+        };
+        let factor = match self.blend {
+            Blend::Linear => linear,
+            Blend::Curved if middle <= f32::EPSILON => 1.0,
+            Blend::Curved if middle >= 1.0 - f32::EPSILON => 0.0,
+            Blend::Curved => t.powf(0.5f32.ln() / middle.ln()),
+            Blend::Sine => ((std::f32::consts::PI * (linear - 0.5)).sin() + 1.0) / 2.0,
+            Blend::SphereIncreasing => (1.0 - (linear - 1.0) * (linear - 1.0)).sqrt(),
+            Blend::SphereDecreasing => 1.0 - (1.0 - linear * linear).sqrt(),
+            Blend::Step => (t >= middle) as u32 as f32,
+        };
+
+        let (a, b) = (self.left_colour, self.right_colour);
+        if self.colouring == Colouring::Rgb {
+            return mix(a, b, factor);
+        }
+        let (a, b) = (rgb_to_hsv(a), rgb_to_hsv(b));
+        // Each way round goes the long way if it has to.
+        let turn = match self.colouring {
+            Colouring::HsvCounterClockwise => (b[0] - a[0]).rem_euclid(1.0),
+            _ => -(a[0] - b[0]).rem_euclid(1.0),
+        };
+        hsv_to_rgb([
+            (a[0] + turn * factor).rem_euclid(1.0),
+            a[1] + (b[1] - a[1]) * factor,
+            a[2] + (b[2] - a[2]) * factor,
+        ])
+    }
+}
+
+/// A gradient in a `.ugr` file while it is read.
+struct UgrGradient {
+    name: String,
+    /// The line it starts on.
+    line: usize,
+    rotation: i64,
+    /// Index and sRGB colour.
+    points: Vec<(i64, [f32; 3])>,
+    /// An index waiting for its colour.
+    index: Option<i64>,
+    in_gradient: bool,
+}
+
+fn parse_ugr(text: &str) -> Result<Vec<Palette>, PaletteError> {
+    let mut palettes = Vec::new();
+    let mut gradient: Option<UgrGradient> = None;
+
+    for (idx, line) in text.lines().enumerate() {
+        let line_number = idx + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') {
+            continue;
+        }
+
+        let Some(current) = gradient.as_mut() else {
+            let name = line
+                .strip_suffix('{')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .ok_or_else(|| {
+                    syntax(
+                        line_number,
+                        format!("expected a gradient name followed by {{, got {line}"),
+                    )
+                })?;
+            gradient = Some(UgrGradient {
+                name: name.to_string(),
+                line: line_number,
+                rotation: 0,
+                points: Vec::new(),
+                index: None,
+                in_gradient: false,
+            });
+            continue;
+        };
+
+        if line == "}" {
+            let finished = gradient.take().unwrap();
+            palettes.push(
+                finished
+                    .palette()
+                    .map_err(|message| syntax(line_number, message))?,
+            );
+            continue;
+        }
+        if let Some(section) = line.strip_suffix(':') {
+            current.in_gradient = section == "gradient";
+            continue;
+        }
+        if !current.in_gradient {
+            continue;
+        }
+        for (key, value) in ugr_pairs(line).map_err(|message| syntax(line_number, message))? {
+            current
+                .set(key, value)
+                .map_err(|message| syntax(line_number, message))?;
+        }
+    }
+
+    if let Some(unfinished) = gradient {
+        return Err(syntax(
+            unfinished.line,
+            format!("gradient {} is missing its closing }}", unfinished.name),
+        ));
+    }
+    if palettes.is_empty() {
+        return Err(early_end(text, "the file has no gradients".to_string()));
+    }
+    Ok(palettes)
+}
+
+/// The `key=value` pairs on a line, where values can be quoted.
+fn ugr_pairs(line: &str) -> Result<Vec<(&str, &str)>, String> {
+    let mut pairs = Vec::new();
+    let mut rest = line.trim_start();
+    while !rest.is_empty() {
+        let (key, after) = rest
+            .split_once('=')
+            .ok_or_else(|| format!("expected key=value, got {rest}"))?;
+        let (value, after) = match after.strip_prefix('"') {
+            Some(quoted) => quoted
+                .split_once('"')
+                .ok_or_else(|| format!("unterminated quote in {line}"))?,
+            None => after.split_once(char::is_whitespace).unwrap_or((after, "")),
+        };
+        pairs.push((key.trim(), value));
+        rest = after.trim_start();
+    }
+    Ok(pairs)
+}
+
+impl UgrGradient {
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let number = || {
+            value
+                .parse::<i64>()
+                .map_err(|_| format!("expected a whole number for {key}, got {value}"))
+        };
+        match key {
+            "title" => self.name = value.to_string(),
+            "rotation" => self.rotation = number()?,
+            "index" => self.index = Some(number()?),
+            "color" => {
+                let index = self
+                    .index
+                    .take()
+                    .ok_or_else(|| format!("color={value} has no index before it"))?;
+                let bgr = number()?;
+                if !(0..=0xff_ffff).contains(&bgr) {
+                    return Err(format!("color {bgr} is out of range"));
+                }
+                let channel = |shift: i64| (bgr >> shift & 0xff) as f32 / 255.0;
+                self.points
+                    .push((index, [channel(0), channel(8), channel(16)]));
+            }
+            // Smoothing and the like.
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn palette(mut self) -> Result<Palette, String> {
+        if self.points.is_empty() {
+            return Err(format!("gradient {} has no colours", self.name));
+        }
+        for point in &mut self.points {
+            point.0 = (point.0 + self.rotation).rem_euclid(UGR_INDICES);
+        }
+        self.points.sort_by_key(|(index, _)| *index);
+
+        // The gradient wraps, so the colour at either end is between its
+        // last and first points.
+        let (first, first_colour) = self.points[0];
+        let (last, last_colour) = self.points[self.points.len() - 1];
+        let gap = (UGR_INDICES - last + first) as f32;
+        let wrap = mix(last_colour, first_colour, (UGR_INDICES - last) as f32 / gap);
+
+        let mut stops = Vec::with_capacity(self.points.len() + 2);
+        if first > 0 {
+            stops.push(Stop {
+                position: 0.0,
+                colour: wrap,
+            });
+        }
+        stops.extend(self.points.iter().map(|(index, colour)| Stop {
+            position: *index as f32 / UGR_INDICES as f32,
+            colour: *colour,
+        }));
+        stops.push(Stop {
+            position: 1.0,
+            colour: if first > 0 { wrap } else { first_colour },
+        });
+        for stop in &mut stops {
+            stop.colour = stop.colour.map(decode_srgb);
+        }
+        Ok(Palette {
+            name: self.name,
+            stops,
+        })
+    }
+}
+
+fn write_native(palette: &Palette) -> String {
+    let mut text = format!("// {}\n", palette.name);
+    for Stop { position, colour } in &palette.stops {
+        let [r, g, b] = colour;
+        writeln!(text, "{position} {r} {g} {b}").unwrap();
+    }
+    text
+}
+
+fn write_map(palette: &Palette, interpolation: Interpolation) -> String {
+    let table = palette.bake(interpolation);
+    let mut text = String::with_capacity(TABLE_SIZE * 12);
+    for [r, g, b, _] in table.colours {
+        let [r, g, b] = [r, g, b].map(byte_srgb);
+        writeln!(text, "{r} {g} {b}").unwrap();
+    }
+    text
+}
+
+fn write_ggr(palette: &Palette, interpolation: Interpolation) -> String {
+    let mut stops = palette.stops.clone();
+    // Segments must cover 0 to 1, so the end colours carry on to the ends.
+    if stops[0].position > 0.0 {
+        stops.insert(
+            0,
+            Stop {
+                position: 0.0,
+                ..stops[0]
+            },
+        );
+    }
+    if stops[stops.len() - 1].position < 1.0 || stops.len() == 1 {
+        stops.push(Stop {
+            position: 1.0,
+            ..stops[stops.len() - 1]
+        });
+    }
+
+    // Segments blend in RGB, so other interpolations are followed by splitting
+    // each gap between stops into several.
+    let samples = match interpolation {
+        Interpolation::LinearRgb => 1,
+        _ => SEGMENT_SAMPLES,
+    };
+    let mut segments = Vec::new();
+    for pair in stops.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        if b.position <= a.position {
+            continue;
+        }
+        let position =
+            |k: usize| a.position + (b.position - a.position) * k as f32 / samples as f32;
+        let colour = |k: usize| match k {
+            0 => a.colour,
+            k if k == samples => b.colour,
+            _ => palette.colour_at(position(k), interpolation),
+        };
+        segments
+            .extend((0..samples).map(|k| (position(k), colour(k), position(k + 1), colour(k + 1))));
+    }
+
+    let mut text = format!(
+        "GIMP Gradient\nName: {}\n{}\n",
+        palette.name,
+        segments.len()
+    );
+    for (left, left_colour, right, right_colour) in segments {
+        let [ar, ag, ab] = left_colour.map(encode_srgb);
+        let [br, bg, bb] = right_colour.map(encode_srgb);
+        writeln!(
+            text,
+            "{left:.6} {:.6} {right:.6} {ar:.6} {ag:.6} {ab:.6} 1.000000 {br:.6} {bg:.6} {bb:.6} 1.000000 0 0 0 0",
+            (left + right) / 2.0,
+        )
+        .unwrap();
+    }
+    text
+}
+
+fn write_ugr(palette: &Palette) -> String {
+    let name: String = palette
+        .name
+        .chars()
+        .map(|c| if c.is_whitespace() { '_' } else { c })
+        .collect();
+    let mut text = format!(
+        "{name} {{\ngradient:\n  title=\"{}\" smooth=no\n",
+        palette.name.replace('"', "'")
+    );
+    for Stop { position, colour } in &palette.stops {
+        // The last index wraps round to the first, so the end of the
+        // palette stops one short of it.
+        let index = ((position * UGR_INDICES as f32).round() as i64).min(UGR_INDICES - 1);
+        let [r, g, b] = colour.map(|channel| byte_srgb(channel) as i64);
+        writeln!(text, "  index={index} color={}", b << 16 | g << 8 | r).unwrap();
+    }
+    text.push_str("opacity:\n  smooth=no index=0 opacity=255\n}\n");
+    text
+}
+
+/// An sRGB byte as a linear channel.
+fn srgb_byte(value: u8) -> f32 {
+    decode_srgb(value as f32 / 255.0)
+}
+
+/// A linear channel as an sRGB byte.
+fn byte_srgb(channel: f32) -> u8 {
+    (encode_srgb(channel.clamp(0.0, 1.0)) * 255.0).round() as u8
+}
@@ -0,0 +1,332 @@
+//! Reference orbits for perturbation rendering.
+//!
+//! Past about 1e-7 of zoom neighbouring pixels are the same `f32`. Rather
+//! than iterate every pixel in higher precision, one reference point is
+//! iterated on the CPU with an arbitrary-precision float, and the shader
+//! iterates only each pixel's small difference from it, which `f32` holds
+//! well. See `mandelbrot_deep.wgsl` for the per-pixel half.
+//!
+//! Early on every pixel's difference follows the same polynomial in its
+//! offset `dc`, so a [`Series`] fitted alongside the orbit lets the shader
+//! jump straight to the first iteration where pixels start to disagree.
+//!
+//! Some pixels glitch against the reference all the same, when they pass
+//! far closer to 0 than it does. A [`GlitchSearch`] draws the view small to
+//! find them, and [`Reference::orbit_without_glitches`] adds a reference
+//! centred on one for them to be drawn against instead, until none are left.
+
+use crate::cpu::{pixel_uv, view_delta};
+use crate::histogram::{self, Colouring};
+use crate::shading::Shading;
+use crate::uniforms::UniformData;
+use crate::viewport::BigFloat;
+
+/// Bits kept beyond those needed to tell pixels apart.
+const GUARD_BITS: usize = 32;
+/// Fewest bits a reference orbit is computed with.
+const MIN_PRECISION: usize = 64;
+/// Largest error relative to the true difference that the series may make
+/// at any probe point. Well under what `f32` iteration loses anyway.
+const SERIES_TOLERANCE: f64 = 1e-6;
+/// Points on the edge of the view where the series is checked.
+const SERIES_PROBES: usize = 8;
+/// The orbit of `c = 0`, which stands in for a missing reference.
+const ORIGIN_POINTS: [[f32; 2]; 2] = [[0.0, 0.0]; 2];
+/// Most references an orbit has, its own and those added for glitches.
+pub const MAX_REFERENCES: usize = 8;
+
+/// Where and how far to compute a reference orbit.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reference {
+    pub center: [BigFloat; 2],
+    /// Distance from the centre to the corners of the view, as far as the
+    /// series approximation has to hold.
+    pub radius: f64,
+    /// Mantissa bits used while iterating.
+    pub precision: usize,
+    pub max_iterations: u32,
+}
+
+/// Reference orbit points with the series that skips their start.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Orbit {
+    pub series: Series,
+    /// `Z_0 = 0, Z_1, ...` rounded to `f32`. Empty when there is no
+    /// reference.
+    pub points: Vec<[f32; 2]>,
+    /// References for the pixels that glitch against `points`, each centred
+    /// on a pixel that glitched against all those before it. A glitched
+    /// pixel is drawn again against each in turn, until one holds.
+    pub secondaries: Vec<Secondary>,
+}
+
+/// A reference added for glitched pixels, which pixels iterate against
+/// from the start, without a series.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Secondary {
+    /// Its centre less the main reference's, taken off each pixel's `dc`.
+    pub offset: [f32; 2],
+    /// `Z_0 = 0, Z_1, ...` rounded to `f32`, at least two of them.
+    pub points: Vec<[f32; 2]>,
+}
+
+/// Finds glitched pixels by drawing a view small with
+/// [`Colouring::Glitches`], at the size of the first pass of histogram
+/// colouring. Glitches smaller than its pixels may be missed.
+#[derive(Clone, Copy)]
+pub struct GlitchSearch {
+    pub width: u32,
+    pub height: u32,
+    /// The view's uniforms, drawing glitches.
+    pub uniform_data: UniformData,
+}
+
+/// The first `skip` iterations of every pixel's difference from the
+/// reference as a cubic in its offset:
+///
+/// `dz_skip = a u + b u^2 + c u^3` with `u = dc / scale`
+///
+/// Scaling by the view's radius keeps the coefficients near 1 where the
+/// plain ones would overflow `f32`. Laid out as the header of the shaders'
+/// orbit buffer.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Series {
+    /// `a`, `b` and `c` as complex numbers.
+    pub coefficients: [[f32; 2]; 3],
+    pub scale: f32,
+    /// Iterations the series stands in for. 0 when it is unused.
+    pub skip: u32,
+}
+
+impl Orbit {
+    /// The points to iterate against: [`Orbit::points`], or the orbit of
+    /// `c = 0` when there are fewer than the two the shaders step between.
+    /// Against that, pixels iterate from `Z_0 = 0` as if on their own, with
+    /// the view centred on 0.
+    pub fn padded_points(&self) -> &[[f32; 2]] {
+        if self.points.len() < 2 {
+            &ORIGIN_POINTS
+        } else {
+            &self.points
+        }
+    }
+}
+
+impl Reference {
+    /// A reference at `center` precise enough for a view reaching
+    /// `half_extents` either side of it.
+    pub fn new(center: [BigFloat; 2], half_extents: [f64; 2], max_iterations: u32) -> Self {
+        // Past the range of f64 the view is 0 across; any precision will do
+        // for the single colour it renders.
+        let half_extent = half_extents[0].min(half_extents[1]).max(f64::MIN_POSITIVE);
+        let view_bits = (-half_extent.log2()).max(0.0).ceil() as usize;
+        Self {
+            center,
+            radius: half_extents[0].hypot(half_extents[1]),
+            precision: (view_bits + GUARD_BITS).max(MIN_PRECISION),
+            max_iterations,
+        }
+    }
+
+    /// Compute the orbit and fit a series to its start.
+    pub fn orbit(&self) -> Orbit {
+        let points = self.points(&self.center);
+        Orbit {
+            series: Series::fit(&points, self.radius),
+            points,
+            secondaries: Vec::new(),
+        }
+    }
+
+    /// [`Reference::orbit`] with secondary references for the pixels that
+    /// glitch against it, up to [`MAX_REFERENCES`] in all. `find_glitch`
+    /// draws the view against an orbit and gives the offset from the centre
+    /// of a pixel that glitched against every one of its references, if any
+    /// did, such as with a [`GlitchSearch`].
+    pub fn orbit_without_glitches(
+        &self,
+        mut find_glitch: impl FnMut(&Orbit) -> Option<[f32; 2]>,
+    ) -> Orbit {
+        let mut orbit = self.orbit();
+        while orbit.secondaries.len() + 1 < MAX_REFERENCES {
+            let Some(offset) = find_glitch(&orbit) else {
+                break;
+            };
+            let big = |offset: f32| BigFloat::try_from(offset as f64).expect("offsets are finite");
+            let [x, y] = &self.center;
+            let center = [x + big(offset[0]), y + big(offset[1])];
+            orbit.secondaries.push(Secondary {
+                offset,
+                points: self.points(&center),
+            });
+        }
+        orbit
+    }
+
+    /// Iterate `z = z^2 + c` from 0 at `center`, returning every `z` rounded
+    /// to `f32` until it escapes or `max_iterations` is reached. There are
+    /// always at least two points.
+    fn points(&self, center: &[BigFloat; 2]) -> Vec<[f32; 2]> {
+        let big = |value: &BigFloat| value.clone().with_precision(self.precision).value();
+        let [cx, cy] = center.each_ref().map(big);
+        let (mut x, mut y) = (big(&BigFloat::ZERO), big(&BigFloat::ZERO));
+
+        let mut orbit = Vec::with_capacity(self.max_iterations as usize + 1);
+        loop {
+            let point = [x.to_f32().value(), y.to_f32().value()];
+            orbit.push(point);
+
+            let escaped = point[0] * point[0] + point[1] * point[1] > 4.0;
+            if orbit.len() > self.max_iterations.max(1) as usize || escaped {
+                break;
+            }
+
+            let xy = &x * &y;
+            x = x.sqr() - y.sqr() + &cx;
+            y = &xy + &xy + &cy;
+        }
+
+        orbit
+    }
+}
+
+impl GlitchSearch {
+    /// The search of the view drawn with `uniform_data`.
+    pub fn new(uniform_data: UniformData) -> Self {
+        let (width, height) = histogram::sample_size(uniform_data.resolution);
+        Self {
+            width,
+            height,
+            uniform_data: UniformData {
+                tile: [0.0, 0.0, 1.0, 1.0],
+                colouring: Colouring::Glitches as u32,
+                shading: Shading::Flat as u32,
+                ..uniform_data
+            },
+        }
+    }
+
+    /// The offset from the centre of the view of a glitched pixel in
+    /// `pixels`, drawn as RGBA8 by this search, or `None` if none glitched.
+    /// Of them all it takes the one nearest their middle, which is most
+    /// likely inside the largest patch of them.
+    pub fn glitch(&self, pixels: &[u8]) -> Option<[f32; 2]> {
+        let width = self.width as usize;
+        let glitched: Vec<[f32; 2]> = pixels
+            .chunks_exact(4)
+            .enumerate()
+            // Glitched pixels are magenta and the rest black.
+            .filter(|(_, pixel)| pixel[2] > 127)
+            .map(|(idx, _)| {
+                let uv = pixel_uv(
+                    idx % width,
+                    idx / width,
+                    self.width,
+                    self.height,
+                    [0.0, 0.0, 1.0, 1.0],
+                );
+                view_delta(uv, &self.uniform_data)
+            })
+            .collect();
+        if glitched.is_empty() {
+            return None;
+        }
+
+        let count = glitched.len() as f32;
+        let middle = glitched.iter().fold([0.0; 2], |sum, offset| {
+            [sum[0] + offset[0] / count, sum[1] + offset[1] / count]
+        });
+        let distance = |offset: &[f32; 2]| (offset[0] - middle[0]).hypot(offset[1] - middle[1]);
+        glitched
+            .into_iter()
+            .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+    }
+}
+
+impl Series {
+    /// Fit the series to `points` for offsets up to `radius`.
+    ///
+    /// The coefficients follow from substituting the cubic into
+    /// `dz' = 2 Z dz + dz^2 + dc`:
+    ///
+    /// `A' = 2 Z A + 1`, `B' = 2 Z B + A^2`, `C' = 2 Z C + 2 A B`
+    ///
+    /// Alongside, a ring of probe offsets is iterated exactly as the shader
+    /// would. The series is used for as long as it agrees with every probe
+    /// and no probe escapes or would be rebased, either of which it cannot
+    /// represent. The shader must step on from `skip`, so it stays short of
+    /// the orbit's last point.
+    fn fit(points: &[[f32; 2]], radius: f64) -> Self {
+        let probes: [[f64; 2]; SERIES_PROBES] = std::array::from_fn(|k| {
+            let angle = std::f64::consts::TAU * k as f64 / SERIES_PROBES as f64;
+            [radius * angle.cos(), radius * angle.sin()]
+        });
+        let mut deltas = [[0.0; 2]; SERIES_PROBES];
+        let (mut a, mut b, mut c) = ([0.0; 2], [0.0; 2], [0.0; 2]);
+
+        let mut series = Self::default();
+        for n in 0..points.len().saturating_sub(2) {
+            let two_z = scale(widen(points[n]), 2.0);
+            (a, b, c) = (
+                add(mul(two_z, a), [1.0, 0.0]),
+                add(mul(two_z, b), mul(a, a)),
+                add(mul(two_z, c), scale(mul(a, b), 2.0)),
+            );
+
+            let next = widen(points[n + 1]);
+            let agrees = probes.iter().zip(&mut deltas).all(|(&dc, dz)| {
+                *dz = add(mul(add(two_z, *dz), *dz), dc);
+                let z = add(next, *dz);
+                let estimate = mul(add(mul(add(mul(c, dc), b), dc), a), dc);
+                let error = norm(sub(estimate, *dz));
+                error <= SERIES_TOLERANCE * SERIES_TOLERANCE * norm(*dz)
+                    && norm(z) <= 4.0
+                    && norm(z) >= norm(*dz)
+            });
+            if !agrees {
+                break;
+            }
+
+            series = Self {
+                coefficients: [
+                    narrow(scale(a, radius)),
+                    narrow(scale(b, radius * radius)),
+                    narrow(scale(c, radius * radius * radius)),
+                ],
+                scale: radius as f32,
+                skip: n as u32 + 1,
+            };
+        }
+
+        series
+    }
+}
+
+fn widen(z: [f32; 2]) -> [f64; 2] {
+    [z[0] as f64, z[1] as f64]
+}
+
+fn narrow(z: [f64; 2]) -> [f32; 2] {
+    [z[0] as f32, z[1] as f32]
+}
+
+fn add(a: [f64; 2], b: [f64; 2]) -> [f64; 2] {
+    [a[0] + b[0], a[1] + b[1]]
+}
+
+fn sub(a: [f64; 2], b: [f64; 2]) -> [f64; 2] {
+    [a[0] - b[0], a[1] - b[1]]
+}
+
+fn mul(a: [f64; 2], b: [f64; 2]) -> [f64; 2] {
+    [a[0] * b[0] - a[1] * b[1], a[0] * b[1] + a[1] * b[0]]
+}
+
+fn scale(a: [f64; 2], s: f64) -> [f64; 2] {
+    [a[0] * s, a[1] * s]
+}
+
+fn norm(a: [f64; 2]) -> f64 {
+    a[0] * a[0] + a[1] * a[1]
+}
@@ -0,0 +1,87 @@
+//! Complex polynomials, for fractals defined by a polynomial's roots.
+//!
+//! Newton's method only needs `f / f'`, which the roots give directly, so
+//! polynomials given by their coefficients are reduced to roots up front.
+
+/// Durand-Kerner sweeps made before settling for the roots found so far.
+const MAX_ITERATIONS: usize = 1000;
+/// Step, relative to the root's size, below which every root has settled.
+const TOLERANCE: f64 = 1e-14;
+
+/// The roots of the polynomial with `coefficients`, highest power first,
+/// each repeated by its multiplicity. Empty if the polynomial is constant.
+///
+/// Found with the Durand-Kerner method, which moves every root towards a
+/// zero of the polynomial divided by its distance to the others.
+pub fn roots(coefficients: &[[f64; 2]]) -> Vec<[f64; 2]> {
+    let Some(start) = coefficients.iter().position(|c| *c != [0.0, 0.0]) else {
+        return Vec::new();
+    };
+    let monic: Vec<_> = coefficients[start..]
+        .iter()
+        .map(|c| div(*c, coefficients[start]))
+        .collect();
+    let degree = monic.len() - 1;
+
+    // Powers of a point that is neither real nor a root of unity, so no two
+    // start out equal or symmetric about the real axis.
+    let mut roots: Vec<[f64; 2]> = (0..degree)
+        .scan([1.0, 0.0], |z, _| {
+            *z = mul(*z, [0.4, 0.9]);
+            Some(*z)
+        })
+        .collect();
+
+    for _ in 0..MAX_ITERATIONS {
+        let mut settled = true;
+        for k in 0..degree {
+            let z = roots[k];
+            let distance = roots
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != k)
+                .fold([1.0, 0.0], |product, (_, other)| {
+                    mul(product, sub(z, *other))
+                });
+            let step = div(evaluate(&monic, z), distance);
+            roots[k] = sub(z, step);
+            settled &= abs(step) <= TOLERANCE * abs(z).max(1.0);
+        }
+        if settled {
+            break;
+        }
+    }
+
+    roots
+}
+
+/// The polynomial with `coefficients`, highest power first, at `z`.
+pub fn evaluate(coefficients: &[[f64; 2]], z: [f64; 2]) -> [f64; 2] {
+    coefficients
+        .iter()
+        .fold([0.0, 0.0], |value, c| add(mul(value, z), *c))
+}
+
+fn add(a: [f64; 2], b: [f64; 2]) -> [f64; 2] {
+    [a[0] + b[0], a[1] + b[1]]
+}
+
+fn sub(a: [f64; 2], b: [f64; 2]) -> [f64; 2] {
+    [a[0] - b[0], a[1] - b[1]]
+}
+
+fn mul(a: [f64; 2], b: [f64; 2]) -> [f64; 2] {
+    [a[0] * b[0] - a[1] * b[1], a[0] * b[1] + a[1] * b[0]]
+}
+
+fn div(a: [f64; 2], b: [f64; 2]) -> [f64; 2] {
+    let norm = b[0] * b[0] + b[1] * b[1];
+    [
+        (a[0] * b[0] + a[1] * b[1]) / norm,
+        (a[1] * b[0] - a[0] * b[1]) / norm,
+    ]
+}
+
+fn abs(a: [f64; 2]) -> f64 {
+    a[0].hypot(a[1])
+}
@@ -0,0 +1,59 @@
+//! Choosing between `f32` and double-single arithmetic in the shaders.
+//!
+//! Double-single keeps each value as an unevaluated sum of two `f32`s,
+//! giving about 48 bits of mantissa where `f32` has 24. It costs several
+//! times as much per iteration, so it is only used once pixels get too
+//! close together for `f32` to tell them apart.
+
+use crate::fractals::Fractal;
+use crate::viewport::Viewport;
+
+/// Pixels closer together than this many `f32` ulps of the view centre
+/// start to band, so double-single takes over from there.
+const PIXEL_ULPS: f64 = 8.0;
+
+/// The arithmetic a shader iterates with, as passed in
+/// `UniformData::precision_mode`.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Precision {
+    Single = 0,
+    DoubleSingle = 1,
+}
+
+impl Precision {
+    pub fn name(self) -> &'static str {
+        match self {
+            Precision::Single => "single",
+            Precision::DoubleSingle => "double-single",
+        }
+    }
+
+    /// The cheapest precision that resolves every pixel of `viewport` drawn
+    /// at `resolution`.
+    pub fn required(viewport: &Viewport, resolution: [f32; 2]) -> Self {
+        let [x, y] = viewport.offset();
+        let ulp = f32::EPSILON as f64 * x.abs().max(y.abs()).max(1.0);
+
+        if viewport.pixel_spacing(resolution) < PIXEL_ULPS * ulp {
+            Precision::DoubleSingle
+        } else {
+            Precision::Single
+        }
+    }
+
+    /// The precision to render `fractal` with: `requested` if given, otherwise
+    /// whatever the view [requires](Precision::required), limited to what the
+    /// fractal's shader supports.
+    pub fn resolve(
+        requested: Option<Precision>,
+        fractal: &dyn Fractal,
+        viewport: &Viewport,
+        resolution: [f32; 2],
+    ) -> Self {
+        match requested.unwrap_or_else(|| Self::required(viewport, resolution)) {
+            Precision::DoubleSingle if fractal.supports_double_single() => Precision::DoubleSingle,
+            _ => Precision::Single,
+        }
+    }
+}
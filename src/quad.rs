@@ -1,6 +1,6 @@
 use wgpu::util::DeviceExt;
 
-use super::vertex::Vertex;
+use crate::vertex::Vertex;
 pub struct Quad {
     pub vertex_buffer: wgpu::Buffer,
     pub index_buffer: wgpu::Buffer,
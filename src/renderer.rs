@@ -0,0 +1,128 @@
+use wgpu::{Device, RenderPass, TextureFormat};
+
+use crate::fractals::{self, Custom, FRACTALS, Fractal};
+use crate::quad::Quad;
+use crate::shader::{self, ShaderBundle, ShaderError};
+
+pub struct Renderer {
+    quad: Quad,
+    surface_format: TextureFormat,
+    shader_bundles: Vec<ShaderBundle>,
+    /// The preprocessed source each of `shader_bundles` was built from.
+    sources: Vec<String>,
+    /// What the formula fractal's pipeline iterates.
+    formula: Custom,
+}
+
+impl Renderer {
+    pub fn new(device: &Device, surface_format: &TextureFormat) -> Self {
+        let quad = Quad::new(device);
+        let formula = Custom::default();
+        let sources: Vec<_> = FRACTALS
+            .iter()
+            .map(|fractal| preprocess(*fractal).unwrap_or_else(|e| panic!("{e}")))
+            .collect();
+        let shader_bundles = FRACTALS
+            .iter()
+            .zip(&sources)
+            .map(|(fractal, source)| {
+                build(device, surface_format, &quad, *fractal, source)
+                    .unwrap_or_else(|e| panic!("{e}"))
+            })
+            .collect();
+
+        Self {
+            quad,
+            surface_format: *surface_format,
+            shader_bundles,
+            sources,
+            formula,
+        }
+    }
+
+    /// Draw `formula` for the formula fractal from now on. If its pipeline
+    /// fails to build, the previous formula is kept.
+    pub fn set_formula(&mut self, device: &Device, formula: Custom) -> Result<(), ShaderError> {
+        let idx = fractals::find(formula.name()).expect("formula is registered");
+        let source = preprocess(&formula)?;
+        let bundle = build(device, &self.surface_format, &self.quad, &formula, &source)?;
+        self.shader_bundles[idx] = bundle;
+        self.sources[idx] = source;
+        self.formula = formula;
+        Ok(())
+    }
+
+    /// Rebuild the pipelines whose shader source has changed, such as after
+    /// [`crate::shader::load`]. A pipeline that fails to build is left as it
+    /// was and tried again on the next reload.
+    pub fn reload(&mut self, device: &Device) -> Vec<ShaderError> {
+        let mut errors = Vec::new();
+        for (idx, fractal) in FRACTALS.iter().enumerate() {
+            let fractal = self.formula.stand_in(*fractal);
+            let source = match preprocess(fractal) {
+                Ok(source) if source == self.sources[idx] => continue,
+                Ok(source) => source,
+                Err(e) => {
+                    errors.push(e);
+                    continue;
+                }
+            };
+            match build(device, &self.surface_format, &self.quad, fractal, &source) {
+                Ok(bundle) => {
+                    self.shader_bundles[idx] = bundle;
+                    self.sources[idx] = source;
+                }
+                Err(e) => errors.push(e),
+            }
+        }
+        errors
+    }
+
+    pub fn draw(
+        &self,
+        render_pass: &mut RenderPass,
+        uniform_bind_group: &wgpu::BindGroup,
+        bundle_idx: usize,
+    ) {
+        // set shader
+        render_pass.set_pipeline(&self.shader_bundles[bundle_idx].pipeline);
+        // set buffers
+        render_pass.set_bind_group(0, uniform_bind_group, &[]);
+
+        // Draw the quad
+        render_pass.set_vertex_buffer(0, self.quad.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.quad.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        render_pass.draw_indexed(0..self.quad.index_count, 0, 0..1);
+    }
+}
+
+/// The shader `fractal` is built from, after preprocessing.
+fn preprocess(fractal: &dyn Fractal) -> Result<String, ShaderError> {
+    shader::preprocess(fractal.name(), &fractal.wgsl_source()).map_err(|e| ShaderError {
+        fractal: fractal.name(),
+        message: e.to_string(),
+    })
+}
+
+/// Build the pipeline drawing `fractal` from its preprocessed `source`.
+fn build(
+    device: &Device,
+    surface_format: &TextureFormat,
+    quad: &Quad,
+    fractal: &dyn Fractal,
+    source: &str,
+) -> Result<ShaderBundle, ShaderError> {
+    ShaderBundle::new(
+        device,
+        surface_format,
+        &quad.vertex_buffer_layout,
+        wgpu::ShaderModuleDescriptor {
+            label: Some(fractal.name()),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        },
+    )
+    .map_err(|e| ShaderError {
+        fractal: fractal.name(),
+        message: e.to_string(),
+    })
+}
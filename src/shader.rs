@@ -0,0 +1,161 @@
+//! The fractal shaders, as WGSL source and as compiled pipelines.
+//!
+//! The files in `src/shaders` are built in, but can be read from disk at
+//! run time with [`load`] so they can be edited without a rebuild. Sources
+//! go through [`preprocess`] before they are compiled.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::sync::{LazyLock, RwLock};
+
+use wgpu::{Device, ShaderModuleDescriptor, VertexBufferLayout};
+
+use crate::uniforms;
+
+mod preprocess;
+
+pub use preprocess::{PreprocessError, preprocess};
+
+/// Every WGSL file the fractals are built from, as built in.
+const FILES: &[(&str, &str)] = &[
+    (
+        "double_single.wgsl",
+        include_str!("shaders/double_single.wgsl"),
+    ),
+    ("escape_time.wgsl", include_str!("shaders/escape_time.wgsl")),
+    ("formula.wgsl", include_str!("shaders/formula.wgsl")),
+    ("julia.wgsl", include_str!("shaders/julia.wgsl")),
+    ("mandelbrot.wgsl", include_str!("shaders/mandelbrot.wgsl")),
+    (
+        "mandelbrot_deep.wgsl",
+        include_str!("shaders/mandelbrot_deep.wgsl"),
+    ),
+    ("nova.wgsl", include_str!("shaders/nova.wgsl")),
+    ("prelude.wgsl", include_str!("shaders/prelude.wgsl")),
+];
+
+/// Files read with [`load`], in place of the built-in ones.
+static LOADED: LazyLock<RwLock<HashMap<&'static str, String>>> = LazyLock::new(Default::default);
+
+/// The shader file called `name`, as last loaded or else as built in.
+///
+/// # Panics
+///
+/// If `name` is not one of [`files`].
+pub fn source(name: &str) -> Cow<'static, str> {
+    if let Some(text) = LOADED.read().unwrap().get(name) {
+        return text.clone().into();
+    }
+    built_in(name)
+        .unwrap_or_else(|| panic!("no shader file {name}"))
+        .into()
+}
+
+/// Names of the shader files.
+pub fn files() -> impl Iterator<Item = &'static str> {
+    FILES.iter().map(|(name, _)| *name)
+}
+
+/// Read the shader file `name` from `directory` in place of the built-in
+/// one, returning whether its text changed. Pipelines built before keep the
+/// old text until rebuilt.
+pub fn load(directory: &Path, name: &'static str) -> io::Result<bool> {
+    let text = std::fs::read_to_string(directory.join(name))?;
+    let mut loaded = LOADED.write().unwrap();
+    let previous = loaded.get(name).map(String::as_str).or(built_in(name));
+    let changed = previous != Some(text.as_str());
+    loaded.insert(name, text);
+    Ok(changed)
+}
+
+fn built_in(name: &str) -> Option<&'static str> {
+    FILES
+        .iter()
+        .find(|(file, _)| *file == name)
+        .map(|(_, text)| *text)
+}
+
+/// A fractal's shader that failed to compile.
+#[derive(Debug)]
+pub struct ShaderError {
+    pub fractal: &'static str,
+    pub message: String,
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} shader failed to compile: {}",
+            self.fractal, self.message
+        )
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+pub struct ShaderBundle {
+    _shader: wgpu::ShaderModule,
+    pub pipeline: wgpu::RenderPipeline,
+}
+
+impl ShaderBundle {
+    pub fn new(
+        device: &Device,
+        surface_format: &wgpu::TextureFormat,
+        buffer_layout: &VertexBufferLayout,
+        shader_module_descriptor: ShaderModuleDescriptor,
+    ) -> Result<Self, wgpu::Error> {
+        // Catch invalid WGSL here rather than in the device's error handler,
+        // which panics.
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let shader = device.create_shader_module(shader_module_descriptor);
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Fractal shader layout"),
+            bind_group_layouts: &[&uniforms::UniformData::bind_group_layout(device)],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Fractal shader render pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: std::slice::from_ref(buffer_layout),
+            },
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: *surface_format,
+                    // Float targets such as `histogram::VALUE_FORMAT` cannot blend.
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+            cache: None,
+        });
+
+        if let Some(e) = pollster::block_on(device.pop_error_scope()) {
+            return Err(e);
+        }
+
+        Ok(Self {
+            _shader: shader,
+            pipeline,
+        })
+    }
+}
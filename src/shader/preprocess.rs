@@ -0,0 +1,146 @@
+//! A small preprocessor run over WGSL before it is compiled.
+//!
+//! Two directives are understood, each on a line of its own:
+//!
+//! ```text
+//! #include "prelude.wgsl"
+//! #define MAX_ROOTS 6u
+//! ```
+//!
+//! `#include` pastes in one of the shader files, see [`super::files`], the
+//! first time it is named and is ignored after that, so files can include
+//! what they use without clashing. `#define` replaces every later use of the
+//! name outside comments with the rest of the line.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::uniforms::MAX_PARAMS;
+
+/// Names every shader can use without defining them.
+fn predefined() -> HashMap<String, String> {
+    HashMap::from([(
+        // vec4s in `Uniforms::params`.
+        "PARAM_VECTORS".to_string(),
+        format!("{}", MAX_PARAMS / 4),
+    )])
+}
+
+/// A directive that could not be followed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreprocessError {
+    /// The file the directive is in, or the name given to the source.
+    pub file: String,
+    /// Counts from 1.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for PreprocessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.file, self.line, self.message)
+    }
+}
+
+impl std::error::Error for PreprocessError {}
+
+/// Follow the directives in `source`, which is called `name` in errors.
+pub fn preprocess(name: &str, source: &str) -> Result<String, PreprocessError> {
+    let mut preprocessor = Preprocessor {
+        output: String::with_capacity(source.len()),
+        defines: predefined(),
+        included: HashSet::new(),
+    };
+    preprocessor.run(name, source)?;
+    Ok(preprocessor.output)
+}
+
+struct Preprocessor {
+    output: String,
+    defines: HashMap<String, String>,
+    included: HashSet<&'static str>,
+}
+
+impl Preprocessor {
+    fn run(&mut self, file: &str, source: &str) -> Result<(), PreprocessError> {
+        for (idx, line) in source.lines().enumerate() {
+            let error = |message: String| PreprocessError {
+                file: file.to_string(),
+                line: idx + 1,
+                message,
+            };
+
+            let Some(directive) = line.trim_start().strip_prefix('#') else {
+                self.output.push_str(&self.substitute(line));
+                self.output.push('\n');
+                continue;
+            };
+
+            let (keyword, rest) = directive
+                .split_once(char::is_whitespace)
+                .unwrap_or((directive, ""));
+            let rest = rest.trim();
+            match keyword {
+                "include" => {
+                    let include = rest
+                        .strip_prefix('"')
+                        .and_then(|rest| rest.strip_suffix('"'))
+                        .ok_or_else(|| error(format!("expected a quoted file name, got {rest}")))?;
+                    let name = super::files()
+                        .find(|name| *name == include)
+                        .ok_or_else(|| error(format!("no shader file {include}")))?;
+                    if self.included.insert(name) {
+                        self.run(name, &super::source(name))?;
+                    }
+                }
+                "define" => {
+                    let (name, value) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                    if !is_identifier(name) {
+                        return Err(error(format!("expected a name to define, got {name:?}")));
+                    }
+                    let value = self.substitute(value.trim());
+                    if value.is_empty() {
+                        return Err(error(format!("{name} is defined as nothing")));
+                    }
+                    if self.defines.insert(name.to_string(), value).is_some() {
+                        return Err(error(format!("{name} is already defined")));
+                    }
+                }
+                _ => return Err(error(format!("unknown directive #{keyword}"))),
+            }
+        }
+        Ok(())
+    }
+
+    /// `line` with every defined name outside a comment replaced.
+    fn substitute(&self, line: &str) -> String {
+        let (code, comment) = match line.find("//") {
+            Some(start) => line.split_at(start),
+            None => (line, ""),
+        };
+
+        let mut output = String::with_capacity(line.len());
+        let mut rest = code;
+        while let Some(start) = rest.find(is_word_char) {
+            let end = rest[start..]
+                .find(|c: char| !is_word_char(c))
+                .map_or(rest.len(), |len| start + len);
+            let word = &rest[start..end];
+            output.push_str(&rest[..start]);
+            output.push_str(self.defines.get(word).map_or(word, String::as_str));
+            rest = &rest[end..];
+        }
+        output.push_str(rest);
+        output.push_str(comment);
+        output
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+fn is_identifier(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(is_word_char)
+}
@@ -0,0 +1,96 @@
+//! Reloading the shader files from disk while they are being edited.
+
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, channel};
+
+use notify::{EventKind, RecursiveMode, Watcher};
+
+use crate::shader;
+
+/// Where the shader files were when this was built, for editing them in
+/// place.
+pub const SOURCE_DIRECTORY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/shaders");
+
+#[derive(Debug)]
+pub enum WatchError {
+    Watch(notify::Error),
+    Read { path: PathBuf, error: io::Error },
+}
+
+impl fmt::Display for WatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WatchError::Watch(e) => write!(f, "failed to watch the shaders: {e}"),
+            WatchError::Read { path, error } => {
+                write!(f, "failed to read {}: {error}", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for WatchError {}
+
+impl From<notify::Error> for WatchError {
+    fn from(e: notify::Error) -> Self {
+        WatchError::Watch(e)
+    }
+}
+
+/// Loads the shader files in a directory in place of the built-in ones, and
+/// again whenever they change.
+pub struct ShaderWatcher {
+    directory: PathBuf,
+    _watcher: notify::RecommendedWatcher,
+    events: Receiver<notify::Result<notify::Event>>,
+}
+
+impl ShaderWatcher {
+    /// Load every shader file from `directory` and start watching it.
+    /// Pipelines pick up the files when they are rebuilt, see
+    /// [`crate::renderer::Renderer::reload`].
+    pub fn new(directory: &Path) -> Result<Self, WatchError> {
+        let (sender, events) = channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+        watcher.watch(directory, RecursiveMode::NonRecursive)?;
+
+        let watcher = Self {
+            directory: directory.to_path_buf(),
+            _watcher: watcher,
+            events,
+        };
+        for name in shader::files() {
+            watcher.load(name)?;
+        }
+        Ok(watcher)
+    }
+
+    /// Load the shader files that changed on disk since the last call,
+    /// returning whether any text changed. Never blocks.
+    pub fn poll(&self) -> Result<bool, WatchError> {
+        let mut changed = false;
+        // Editors often write a file in several steps, which all arrive
+        // together by the next frame.
+        for event in self.events.try_iter() {
+            let event = event?;
+            if matches!(event.kind, EventKind::Access(_) | EventKind::Remove(_)) {
+                continue;
+            }
+            for path in &event.paths {
+                let name = path.file_name().and_then(|name| name.to_str());
+                if let Some(name) = shader::files().find(|file| Some(*file) == name) {
+                    changed |= self.load(name)?;
+                }
+            }
+        }
+        Ok(changed)
+    }
+
+    fn load(&self, name: &'static str) -> Result<bool, WatchError> {
+        shader::load(&self.directory, name).map_err(|error| WatchError::Read {
+            path: self.directory.join(name),
+            error,
+        })
+    }
+}
@@ -0,0 +1,73 @@
+// double_single.wgsl
+// Double-single arithmetic, included by shaders that support it.
+//
+// A value is a vec2 of hi + lo with |lo| at most half an ulp of hi, giving
+// about 48 bits of mantissa. The error-free transforms below rely on every
+// operation being rounded exactly as written, which shader compilers do not
+// promise: they happily simplify (a + b) - a to b. Passing intermediate
+// results through opaque() stops that. Mirrored in src/cpu/double_single.rs.
+
+#include "prelude.wgsl"
+
+const PRECISION_DOUBLE_SINGLE: u32 = 1u;
+
+// Multiplies by uniforms.one, which is always 1 but unknown at compile time.
+fn opaque(a: f32) -> f32 {
+    return a * uniforms.one;
+}
+
+fn ds(a: f32) -> vec2<f32> {
+    return vec2<f32>(a, 0.0);
+}
+
+// s + e == a + b exactly.
+fn two_sum(a: f32, b: f32) -> vec2<f32> {
+    let s = opaque(a + b);
+    let v = opaque(s - a);
+    let e = (a - opaque(s - v)) + (b - v);
+    return vec2<f32>(s, e);
+}
+
+// As two_sum, for |a| >= |b|.
+fn quick_two_sum(a: f32, b: f32) -> vec2<f32> {
+    let s = opaque(a + b);
+    let e = b - opaque(s - a);
+    return vec2<f32>(s, e);
+}
+
+// Dekker's split of a into two 12 bit halves.
+fn split(a: f32) -> vec2<f32> {
+    let t = opaque(4097.0 * a);
+    let hi = t - opaque(t - a);
+    return vec2<f32>(hi, a - hi);
+}
+
+// p + e == a * b exactly.
+fn two_prod(a: f32, b: f32) -> vec2<f32> {
+    let p = opaque(a * b);
+    let sa = split(a);
+    let sb = split(b);
+    let e = ((sa.x * sb.x - p) + sa.x * sb.y + sa.y * sb.x) + sa.y * sb.y;
+    return vec2<f32>(p, e);
+}
+
+fn ds_add(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    let s = two_sum(a.x, b.x);
+    return quick_two_sum(s.x, s.y + a.y + b.y);
+}
+
+fn ds_sub(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    return ds_add(a, -b);
+}
+
+fn ds_mul(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    let p = two_prod(a.x, b.x);
+    return quick_two_sum(p.x, p.y + (a.x * b.y + a.y * b.x));
+}
+
+fn ds_div(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    let q = a.x / b.x;
+    let r = ds_sub(a, ds_mul(b, ds(q)));
+    return quick_two_sum(q, r.x / b.x);
+}
+
@@ -0,0 +1,114 @@
+// escape_time.wgsl
+//
+// FAMILY and JULIA are #defined ahead of this file, see `escape_time.rs`.
+
+#include "prelude.wgsl"
+
+// Values of FAMILY, see `Family`.
+const FAMILY_BURNING_SHIP: u32 = 0u;
+const FAMILY_TRICORN: u32 = 1u;
+// Square of the radius past which a point has escaped. Well past 2 so the
+// smooth iteration count is smooth.
+const ESCAPE: f32 = 256.0;
+
+// z^d for a real exponent d, exact for squares.
+fn power(z: vec2<f32>, d: f32) -> vec2<f32> {
+    if (d == 2.0) {
+        return vec2<f32>(z.x * z.x - z.y * z.y, 2.0 * z.x * z.y);
+    }
+    let r2 = dot(z, z);
+    if (r2 == 0.0) {
+        return vec2<f32>(0.0, 0.0);
+    }
+    let r = pow(r2, 0.5 * d);
+    let angle = d * atan2(z.y, z.x);
+    return r * vec2<f32>(cos(angle), sin(angle));
+}
+
+// The family's z' = f(z) + c, before adding c.
+fn step(z: vec2<f32>, d: f32) -> vec2<f32> {
+    switch FAMILY {
+        case FAMILY_BURNING_SHIP: {
+            return power(abs(z), d);
+        }
+        case FAMILY_TRICORN: {
+            return power(vec2<f32>(z.x, -z.y), d);
+        }
+        default: {
+            return power(z, d);
+        }
+    }
+}
+
+// The derivative of step at z, as a matrix. Folding z first makes it a
+// reflection followed by multiplying by d w^(d - 1), so the Burning Ship
+// and Tricorn are not holomorphic and need the whole matrix.
+fn step_derivative(z: vec2<f32>, d: f32) -> mat2x2<f32> {
+    var w = z;
+    var fold = mat2x2<f32>(1.0, 0.0, 0.0, 1.0);
+    switch FAMILY {
+        case FAMILY_BURNING_SHIP: {
+            w = abs(z);
+            fold = mat2x2<f32>(sign(z.x), 0.0, 0.0, sign(z.y));
+        }
+        case FAMILY_TRICORN: {
+            w = vec2<f32>(z.x, -z.y);
+            fold = mat2x2<f32>(1.0, 0.0, 0.0, -1.0);
+        }
+        default: {}
+    }
+    var p = 2.0 * w;
+    if (d != 2.0) {
+        p = d * power(w, d - 1.0);
+    }
+    return mat2x2<f32>(p.x, p.y, -p.y, p.x) * fold;
+}
+
+@fragment
+fn fs_main(vertex_out: VertexOut) -> @location(0) vec4<f32> {
+    let point = view_point(vertex_out.uv);
+
+    let max_iter = u32(uniforms.params[0].x);
+    let exponent = uniforms.params[0].y;
+
+    // Mandelbrot-style sets iterate from 0 over c, Julia-style ones over z.
+    // The derivative of z by the point, for shading, starts as that of
+    // whichever the point is.
+    let identity = mat2x2<f32>(1.0, 0.0, 0.0, 1.0);
+    var z = vec2<f32>(0.0);
+    var c = point;
+    var derivative = mat2x2<f32>(0.0, 0.0, 0.0, 0.0);
+    if (JULIA) {
+        z = point;
+        c = uniforms.params[0].zw;
+        derivative = identity;
+    }
+
+    let escape = shaded_escape(ESCAPE);
+    var i = 0u;
+    loop {
+        if (i >= max_iter || dot(z, z) > escape) {
+            break;
+        }
+        if (shaded()) {
+            derivative = step_derivative(z, exponent) * derivative;
+            if (!JULIA) {
+                derivative += identity;
+            }
+        }
+        z = step(z, exponent) + c;
+        i = i + 1u;
+    }
+
+    if (i >= max_iter) {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+
+    // |z| grows like |z|^d an iteration, so the fraction of an iteration
+    // left over is how far the escaping exponent overshot.
+    let smooth_i = f32(i) + 1.0 - log2(0.5 * log2(dot(z, z))) / log2(exponent);
+    let t = escape_intensity(smooth_i, max_iter);
+
+    let colour = shade(t, z, transpose(derivative) * z);
+    return vec4<f32>(colour, 1.0);
+}
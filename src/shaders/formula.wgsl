@@ -0,0 +1,145 @@
+// formula.wgsl
+//
+// Template for user formulas, see `formula.rs`, which fills in the bodies of
+// formula_z0 and formula_step.
+
+#include "prelude.wgsl"
+
+// Complex arithmetic on vec2 as (real, imaginary).
+
+fn c_mul(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(a.x * b.x - a.y * b.y, a.x * b.y + a.y * b.x);
+}
+
+fn c_div(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    let d = b.x * b.x + b.y * b.y;
+    return vec2<f32>((a.x * b.x + a.y * b.y) / d, (a.y * b.x - a.x * b.y) / d);
+}
+
+fn c_exp(a: vec2<f32>) -> vec2<f32> {
+    let r = exp(a.x);
+    return vec2<f32>(r * cos(a.y), r * sin(a.y));
+}
+
+fn c_log(a: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(log(length(a)), atan2(a.y, a.x));
+}
+
+fn c_pow(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    if (a.x * a.x + a.y * a.y == 0.0) {
+        return vec2<f32>(0.0, 0.0);
+    }
+    return c_exp(c_mul(b, c_log(a)));
+}
+
+// a^n by squaring.
+fn c_powi(a: vec2<f32>, n: i32) -> vec2<f32> {
+    var result = vec2<f32>(1.0, 0.0);
+    var base = a;
+    var k = u32(abs(n));
+    loop {
+        if (k == 0u) {
+            break;
+        }
+        if ((k & 1u) == 1u) {
+            result = c_mul(result, base);
+        }
+        base = c_mul(base, base);
+        k = k >> 1u;
+    }
+    if (n < 0) {
+        return c_div(vec2<f32>(1.0, 0.0), result);
+    }
+    return result;
+}
+
+fn c_sqrt(a: vec2<f32>) -> vec2<f32> {
+    let r = length(a);
+    let y = sqrt(0.5 * (r - a.x));
+    return vec2<f32>(sqrt(0.5 * (r + a.x)), select(y, -y, a.y < 0.0));
+}
+
+fn c_sin(a: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(sin(a.x) * cosh(a.y), cos(a.x) * sinh(a.y));
+}
+
+fn c_cos(a: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(cos(a.x) * cosh(a.y), -sin(a.x) * sinh(a.y));
+}
+
+fn c_tan(a: vec2<f32>) -> vec2<f32> {
+    return c_div(c_sin(a), c_cos(a));
+}
+
+fn c_sinh(a: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(sinh(a.x) * cos(a.y), cosh(a.x) * sin(a.y));
+}
+
+fn c_cosh(a: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(cosh(a.x) * cos(a.y), sinh(a.x) * sin(a.y));
+}
+
+fn c_tanh(a: vec2<f32>) -> vec2<f32> {
+    return c_div(c_sinh(a), c_cosh(a));
+}
+
+fn c_abs(a: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(length(a), 0.0);
+}
+
+fn c_conj(a: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(a.x, -a.y);
+}
+
+fn c_re(a: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(a.x, 0.0);
+}
+
+fn c_im(a: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(a.y, 0.0);
+}
+
+fn formula_z0(c: vec2<f32>, pixel: vec2<f32>) -> vec2<f32> {
+    return {{z0}};
+}
+
+fn formula_step(z: vec2<f32>, c: vec2<f32>, pixel: vec2<f32>) -> vec2<f32> {
+    return {{step}};
+}
+
+@fragment
+fn fs_main(vertex_out: VertexOut) -> @location(0) vec4<f32> {
+    let pixel = view_point(vertex_out.uv);
+
+    let max_iter = u32(uniforms.params[0].x);
+    let bailout = uniforms.params[0].y;
+
+    // Mandelbrot-style formulas iterate over c from z0, Julia-style ones
+    // over z with c fixed.
+    var c = pixel;
+    var z: vec2<f32>;
+    if (uniforms.params[0].z > 0.5) {
+        c = vec2<f32>(uniforms.params[0].w, uniforms.params[1].x);
+        z = pixel;
+    } else {
+        z = formula_z0(c, pixel);
+    }
+
+    var i = 0u;
+    loop {
+        if (i >= max_iter || dot(z, z) > bailout * bailout) {
+            break;
+        }
+        z = formula_step(z, c, pixel);
+        i = i + 1u;
+    }
+
+    if (i >= max_iter) {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+
+    let t = escape_intensity(f32(i), max_iter);
+
+    let colour = get_colour(t);
+    return vec4<f32>(colour, 1.0);
+}
@@ -1,93 +1,92 @@
-struct VertexIn {
-    @location(0) position: vec2<f32>,
-    @location(1) uv: vec2<f32>,
-};
-
-struct VertexOut {
-    @builtin(position) position: vec4<f32>,
-    @location(0) uv: vec2<f32>,
-};
-
-@vertex
-fn vs_main(in: VertexIn) -> VertexOut {
-    var out: VertexOut;
-    out.position = vec4<f32>(in.position, 0.0, 1.0);
-    out.uv = in.uv;
-    return out;
-}
-struct Uniforms {
-    time: f32,
-    zoom: f32,
-    offset: vec2<f32>,
-};
-@group(0) @binding(0)
-var<uniform> uniforms: Uniforms;
-
-fn get_colour( intensity: f32) -> vec3<f32> {
-   let t0: f32 = 0.0;
-   let t1: f32 = 0.25;
-   let t2: f32 = 0.50;
-   let t3: f32 = 0.85;
-   let t4: f32 = 1.0;
-
-    let c0 = vec3(0.0, 0.0, 0.0);
-    let c1 = vec3(0.2, 0.0, 0.6);
-    let c2 = vec3(0.8, 0.2, 0.1);
-    let c3 = vec3(1.0, 0.8, 0.1);
-    let c4 = vec3(1.0, 1.0, 0.5);
-
-    if (intensity < t1){
-        return mix(c0, c1, (intensity - t0) / (t1 - t0));
-    }
-    else if (intensity < t2) {
-        return mix(c1, c2, (intensity - t1) / (t2 - t1));
-    }
-    else if (intensity < t3){
-        return mix(c2, c3, (intensity - t2) / (t3 - t2));
-    }
-    else {
-        return mix(c3, c4, (intensity - t3) / (t4 - t3));
+// julia.wgsl
+
+#include "prelude.wgsl"
+#include "double_single.wgsl"
+
+// Values of the path parameter, see `JuliaPath`.
+const PATH_FIXED: u32 = 1u;
+const PATH_LINE: u32 = 2u;
+
+// The constant c at the current time, moving along the chosen path.
+fn julia_c() -> vec2<f32> {
+    let radius = uniforms.params[0].x;
+    let speed = uniforms.params[0].y;
+    let path = u32(uniforms.params[0].z);
+    let start = vec2<f32>(uniforms.params[0].w, uniforms.params[1].x);
+    let end = uniforms.params[1].yz;
+    let angle = uniforms.time * speed;
+
+    switch path {
+        case PATH_FIXED: {
+            return start;
+        }
+        case PATH_LINE: {
+            // Back and forth between start and end.
+            return mix(start, end, 0.5 - 0.5 * cos(angle));
+        }
+        default: {
+            return start + vec2<f32>(radius * cos(angle), radius * sin(angle));
+        }
     }
 }
 
-
 @fragment
 fn fs_main(vertex_out: VertexOut) -> @location(0) vec4<f32> {
 
-
     let zoom = pow(1.2, uniforms.zoom);
-    let bound_x = 1.5 / zoom;
-    let bound_y = 1.5 / zoom;
+    let delta = view_delta(vertex_out.uv);
+    let z = delta - vec2(uniforms.offset.x, -uniforms.offset.y);
 
-    let uv = vertex_out.uv * 2.0 - vec2(1.0, 1.0);
-    let z = uv * vec2(bound_x, bound_y) - vec2(uniforms.offset.x, -uniforms.offset.y);
-
-    let c = vec2<f32>(
-        0.7885 * cos(uniforms.time * 0.05),
-        0.7885 * sin(uniforms.time * 0.05)
-    );
+    let c = julia_c();
 
     var value = z;
+    // d value / d z, for shading.
+    var dz = vec2<f32>(1.0, 0.0);
     var i = 0u;
     let iterations = clamp(u32(100 + 100 * zoom), 0, 1000);
-    loop {
-        if (i >= iterations || dot(value, value) > 4.0) {
-            break;
+    let escape = shaded_escape(4.0);
+    if (uniforms.precision_mode == PRECISION_DOUBLE_SINGLE) {
+        var x = ds_add(-vec2(uniforms.offset.x, uniforms.offset_lo.x), ds(delta.x));
+        var y = ds_add(vec2(uniforms.offset.y, uniforms.offset_lo.y), ds(delta.y));
+        loop {
+            if (i >= iterations || x.x * x.x + y.x * y.x > escape) {
+                break;
+            }
+            if (shaded()) {
+                dz = 2.0 * complex_mul(vec2<f32>(x.x, y.x), dz);
+            }
+            let xy = ds_mul(x, y);
+            x = ds_add(ds_sub(ds_mul(x, x), ds_mul(y, y)), ds(c.x));
+            y = ds_add(ds_add(xy, xy), ds(c.y));
+            i = i + 1u;
+        }
+        value = vec2<f32>(x.x, y.x);
+    } else {
+        loop {
+            if (i >= iterations || dot(value, value) > escape) {
+                break;
+            }
+            if (shaded()) {
+                dz = 2.0 * complex_mul(value, dz);
+            }
+            value = vec2<f32>(
+                value.x * value.x - value.y * value.y + c.x,
+                2 * value.x * value.y + c.y
+            );
+            i = i + 1u;
         }
-        value = vec2<f32>(
-            value.x * value.x - value.y * value.y + c.x,
-            2 * value.x * value.y + c.y
-        );
-        i = i + 1u;
     }
 
+    // Points that never escaped have no distance to shade by.
+    if (i >= iterations && shaded()) {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
 
     var t = f32(i) - log2(log2(dot(value,value))) + 4;
     let max_iter = 1000.0;
     t = clamp(t / max_iter, 0.0, 1.0);
     t = pow(t, 0.6); // optional contrast enhancement
 
-
-    let colour = get_colour(t);
+    let colour = shade(t, value, holomorphic_gradient(value, dz));
     return vec4<f32>(colour, 1.0);
 }
@@ -1,60 +1,75 @@
 // mandelbrot.wgsl
 
-struct VertexIn {
-    @location(0) position: vec2<f32>,
-    @location(1) uv: vec2<f32>,
-};
-
-struct VertexOut {
-    @builtin(position) position: vec4<f32>,
-    @location(0) uv: vec2<f32>,
-};
-
-@vertex
-fn vs_main(in: VertexIn) -> VertexOut {
-    var out: VertexOut;
-    out.position = vec4<f32>(in.position, 0.0, 1.0);
-    out.uv = in.uv;
-    return out;
-}
-
-struct Uniforms {
-    time: f32,
-    zoom: f32,
-    offset: vec2<f32>,
-}
-
-@group(0) @binding(0)
-var<uniform> uniforms: Uniforms;
+#include "prelude.wgsl"
+#include "double_single.wgsl"
 
 @fragment
 fn fs_main(vertex_out: VertexOut) -> @location(0) vec4<f32> {
-    let zoom = pow(0.8, -25* cos(uniforms.time * 0.05)+ 20);
-    // let center = vec2(-0.97, -0.252);
-    let center = vec2(-1.006, -0.2506);
-    let uv = (vertex_out.uv - vec2(0.5)) * zoom;
-
-    // Instead of adding center, subtract it to bring it to (0,0)
-    let c = uv + center;
+    let demo = uniforms.params[0].y > 0.5;
+    var c: vec2<f32>;
+    if (demo) {
+        let aspect = uniforms.resolution.x / uniforms.resolution.y;
+        let scale = vec2(max(aspect, 1.0), max(1.0 / aspect, 1.0));
+        // Demo: zoom in and out of the target point over time.
+        let zoom = pow(0.8, -25* cos(uniforms.time * 0.05)+ 20);
+        let center = uniforms.params[0].zw;
+        let uv = (vertex_out.uv - vec2(0.5)) * scale * zoom;
 
+        // Instead of adding center, subtract it to bring it to (0,0)
+        c = uv + center;
+    } else {
+        c = view_point(vertex_out.uv);
+    }
 
     var z = vec2<f32>(0.0);
+    // dz/dc, for shading.
+    var dz = vec2<f32>(0.0);
     var i = 0u;
 
-    let max_iter = u32(1000); 
+    let max_iter = u32(uniforms.params[0].x);
+    let escape = shaded_escape(4.0);
 
-    loop {
-        if (i >= max_iter || dot(z, z) > 4.0) {
-            break;
+    // The demo stays shallow enough for f32.
+    if (uniforms.precision_mode == PRECISION_DOUBLE_SINGLE && !demo) {
+        let delta = view_delta(vertex_out.uv);
+        let cx = ds_add(-vec2(uniforms.offset.x, uniforms.offset_lo.x), ds(delta.x));
+        let cy = ds_add(vec2(uniforms.offset.y, uniforms.offset_lo.y), ds(delta.y));
+        var x = ds(0.0);
+        var y = ds(0.0);
+        loop {
+            if (i >= max_iter || x.x * x.x + y.x * y.x > escape) {
+                break;
+            }
+            if (shaded()) {
+                dz = 2.0 * complex_mul(vec2<f32>(x.x, y.x), dz) + vec2<f32>(1.0, 0.0);
+            }
+            let xy = ds_mul(x, y);
+            x = ds_add(ds_sub(ds_mul(x, x), ds_mul(y, y)), cx);
+            y = ds_add(ds_add(xy, xy), cy);
+            i = i + 1u;
+        }
+        z = vec2<f32>(x.x, y.x);
+    } else {
+        loop {
+            if (i >= max_iter || dot(z, z) > escape) {
+                break;
+            }
+            if (shaded()) {
+                dz = 2.0 * complex_mul(z, dz) + vec2<f32>(1.0, 0.0);
+            }
+            z = vec2<f32>(
+                z.x * z.x - z.y * z.y + c.x,
+                2.0 * z.x * z.y + c.y
+            );
+            i = i + 1u;
         }
-        z = vec2<f32>(
-            z.x * z.x - z.y * z.y + c.x,
-            2.0 * z.x * z.y + c.y
-        );
-        i = i + 1u;
     }
 
-    let t = f32(i) / f32(max_iter);
+    if (i >= max_iter) {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+
+    let t = escape_intensity(f32(i), max_iter);
 
-    return vec4<f32>(t*0.9 * sin(uniforms.time * 0.05), t * 0.3, t, 1.0);
+    return vec4<f32>(shade(t, z, holomorphic_gradient(z, dz)), 1.0);
 }
@@ -0,0 +1,152 @@
+// mandelbrot_deep.wgsl
+// Mandelbrot set by perturbation: each pixel iterates only its difference
+// from a reference orbit computed on the CPU in arbitrary precision, so the
+// view can zoom far past f32 resolution. The iterations every pixel shares
+// at the start are skipped by a series approximation, also from the CPU.
+// Pixels that glitch against the reference are drawn again against further
+// references, centred on glitched pixels.
+
+#include "prelude.wgsl"
+
+// dz after the first `skip` iterations is a u + b u^2 + c u^3, where
+// u = dc / scale. See `perturbation::Series`.
+struct Series {
+    a: vec2<f32>,
+    b: vec2<f32>,
+    c: vec2<f32>,
+    scale: f32,
+    skip: u32,
+};
+
+// Where a reference's points lie in Orbit::points. offset is its centre
+// less the main reference's, taken off each pixel's dc. See
+// `perturbation::Secondary`.
+struct Span {
+    offset: vec2<f32>,
+    start: u32,
+    len: u32,
+};
+
+// points holds Z_0 = 0, Z_1, ... of the reference at the centre of the view,
+// ending where it escaped or ran out of iterations, then those of the
+// references added for glitched pixels. Unused references have len 0.
+struct Orbit {
+    series: Series,
+    references: array<Span, MAX_REFERENCES>,
+    points: array<vec2<f32>>,
+};
+
+@group(0) @binding(1)
+var<storage, read> orbit: Orbit;
+
+// Most references the orbit holds. See `perturbation::MAX_REFERENCES`.
+const MAX_REFERENCES: u32 = 8u;
+// Pauldelbrot's criterion: once |z|^2 drops below this fraction of |Z|^2,
+// z is mostly cancellation error and the pixel is glitched.
+const GLITCH_TOLERANCE: f32 = 1e-6;
+// Iterations per cycle of the palette.
+const COLOUR_PERIOD: f32 = 64.0;
+
+// Where a pixel's iteration ended.
+struct Iterated {
+    z: vec2<f32>,
+    // dz/dc, which is also the derivative of z, for shading.
+    derivative: vec2<f32>,
+    i: u32,
+    glitched: bool,
+};
+
+// Iterate the pixel at offset dc from the centre against the reference
+// at index, skipping the start by the series for the main one.
+fn iterate(dc_main: vec2<f32>, index: u32) -> Iterated {
+    let reference = orbit.references[index];
+    // Offset of this pixel's c from the reference's.
+    let dc = dc_main - reference.offset;
+    let max_iter = u32(uniforms.params[0].x);
+    let series = orbit.series;
+
+    // z = orbit.points[reference.start + n] + dz
+    var dz = vec2<f32>(0.0);
+    var z = vec2<f32>(0.0);
+    var derivative = vec2<f32>(0.0);
+    var n = 0u;
+    var i = 0u;
+    var glitched = false;
+    if (index == 0u && series.skip > 0u) {
+        let u = dc / series.scale;
+        dz = complex_mul(complex_mul(complex_mul(series.c, u) + series.b, u) + series.a, u);
+        // The series' own derivative, a + 2 b u + 3 c u^2, by u.
+        let du = complex_mul(complex_mul(3.0 * series.c, u) + 2.0 * series.b, u) + series.a;
+        derivative = du / series.scale;
+        n = series.skip;
+        i = series.skip;
+        z = orbit.points[reference.start + n] + dz;
+    }
+    let escape = shaded_escape(4.0);
+    loop {
+        if (i >= max_iter) {
+            break;
+        }
+
+        if (shaded()) {
+            derivative = 2.0 * complex_mul(z, derivative) + vec2<f32>(1.0, 0.0);
+        }
+
+        // dz' = 2 Z dz + dz^2 + dc
+        dz = complex_mul(2.0 * orbit.points[reference.start + n] + dz, dz) + dc;
+        n = n + 1u;
+        i = i + 1u;
+
+        let point = orbit.points[reference.start + n];
+        z = point + dz;
+        let r2 = dot(z, z);
+        if (r2 > escape) {
+            break;
+        }
+
+        if (r2 < GLITCH_TOLERANCE * dot(point, point)) {
+            glitched = true;
+        }
+
+        // Rebase once z is closer to 0 than to the reference, or the
+        // reference has escaped: carry on from z itself as the difference
+        // from Z_0 = 0. Keeping dz smaller than z avoids almost every glitch.
+        // Those the reference causes itself survive this, and are drawn
+        // again against the next reference.
+        if (r2 < dot(dz, dz) || n + 1u >= reference.len) {
+            dz = z;
+            n = 0u;
+        }
+    }
+    return Iterated(z, derivative, i, glitched);
+}
+
+@fragment
+fn fs_main(vertex_out: VertexOut) -> @location(0) vec4<f32> {
+    let dc = view_delta(vertex_out.uv);
+    let max_iter = u32(uniforms.params[0].x);
+    let show_glitches = uniforms.params[0].y > 0.5;
+
+    // Draw a glitched pixel again against each further reference in turn,
+    // until one holds.
+    var iterated = iterate(dc, 0u);
+    for (var index = 1u; index < MAX_REFERENCES && iterated.glitched; index += 1u) {
+        if (orbit.references[index].len == 0u) {
+            break;
+        }
+        iterated = iterate(dc, index);
+    }
+    let z = iterated.z;
+
+    if (iterated.glitched && (show_glitches || uniforms.colouring == COLOURING_GLITCHES)) {
+        return vec4<f32>(1.0, 0.0, 1.0, 1.0);
+    }
+    if (iterated.i >= max_iter) {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+
+    let smooth_i = f32(iterated.i) - log2(log2(dot(z, z))) + 4.0;
+    let t = 1.0 - abs(fract(smooth_i / COLOUR_PERIOD) * 2.0 - 1.0);
+    let colour = shade(mix(0.2, 1.0, t), z, holomorphic_gradient(z, iterated.derivative));
+    return vec4<f32>(colour, 1.0);
+}
@@ -1,112 +1,152 @@
-struct VertexIn {
-    @location(0) position: vec2<f32>,
-    @location(1) uv: vec2<f32>,
-};
-
-struct VertexOut {
-    @builtin(position) position: vec4<f32>,
-    @location(0) uv: vec2<f32>,
-};
-
-@vertex
-fn vs_main(in: VertexIn) -> VertexOut {
-    var out: VertexOut;
-    out.position = vec4<f32>(in.position, 0.0, 1.0);
-    out.uv = in.uv;
-    return out;
-}
-struct Uniforms {
-    time: f32,
-    zoom: f32,
-    offset: vec2<f32>,
-};
-@group(0) @binding(0)
-var<uniform> uniforms: Uniforms;
-
-fn get_colour( intensity: f32) -> vec3<f32> {
-   let t0: f32 = 0.0;
-   let t1: f32 = 0.25;
-   let t2: f32 = 0.50;
-   let t3: f32 = 0.85;
-   let t4: f32 = 1.0;
-
-    let c0 = vec3(0.0, 0.0, 0.0);
-    let c1 = vec3(0.2, 0.0, 0.6);
-    let c2 = vec3(0.8, 0.2, 0.1);
-    let c3 = vec3(1.0, 0.8, 0.1);
-    let c4 = vec3(1.0, 1.0, 0.5);
-
-    if (intensity < t1){
-        return mix(c0, c1, (intensity - t0) / (t1 - t0));
-    }
-    else if (intensity < t2) {
-        return mix(c1, c2, (intensity - t1) / (t2 - t1));
-    }
-    else if (intensity < t3){
-        return mix(c2, c3, (intensity - t2) / (t3 - t2));
+// nova.wgsl
+
+#include "prelude.wgsl"
+#include "double_single.wgsl"
+
+// Values of the colouring parameter, see `NovaColouring`.
+const COLOURING_SPEED: u32 = 1u;
+// Most roots the polynomial can have, see `MAX_ROOTS`.
+const MAX_ROOTS: u32 = 6u;
+// A point has converged once a step moves it less than the square root of
+// this.
+const CONVERGED: f32 = 1e-6;
+// Iterations to converge that span the whole palette.
+const SPEED_ITERATIONS: f32 = 32.0;
+
+// Root k of the polynomial. The roots follow the first four parameters, two
+// to a vec4.
+fn root(k: u32) -> vec2<f32> {
+    let packed = uniforms.params[1u + k / 2u];
+    if (k % 2u == 0u) {
+        return packed.xy;
     }
-    else {
-        return mix(c3, c4, (intensity - t3) / (t4 - t3));
+    return packed.zw;
+}
+
+// 1 / z, or 0 at 0.
+fn reciprocal(z: vec2<f32>) -> vec2<f32> {
+    let n = dot(z, z);
+    if (n > 0.0) {
+        return vec2<f32>(z.x, -z.y) / n;
     }
+    return vec2<f32>(0.0, 0.0);
 }
 
+// Hue of the basin of root k.
+fn root_colour(k: u32, degree: u32) -> vec3<f32> {
+    let hue = f32(k) / f32(degree);
+    return 0.5 + 0.5 * cos(6.2831855 * (hue + vec3<f32>(0.0, 1.0 / 3.0, 2.0 / 3.0)));
+}
 
 @fragment
 fn fs_main(vertex_out: VertexOut) -> @location(0) vec4<f32> {
     let zoom = pow(1.2, uniforms.zoom);
-    let bound_x = 1.5 / zoom;
-    let bound_y = 1.5 / zoom;
-
-    let uv = vertex_out.uv * 2.0 - vec2(1.0, 1.0);
-    var z = uv * vec2(bound_x, bound_y) - vec2(uniforms.offset.x, -uniforms.offset.y);
+    let delta = view_delta(vertex_out.uv);
+    var z = delta - vec2(uniforms.offset.x, -uniforms.offset.y);
 
     // Small perturbation constant to animate over time
+    let amplitude = uniforms.params[0].x;
     let c = vec2<f32>(
-        0.3 * cos(uniforms.time * 0.2),
-        0.3 * sin(uniforms.time * 0.35)
+        amplitude * cos(uniforms.time * 0.2),
+        amplitude * sin(uniforms.time * 0.35)
     );
+    let relaxation = uniforms.params[0].y;
+    let colouring = u32(uniforms.params[0].z);
+    let degree = clamp(u32(uniforms.params[0].w), 1u, MAX_ROOTS);
 
+    // Square of how far the last step moved.
+    var moved = 0.0;
+    var converged = false;
     var i = 0u;
     let max_iter = clamp(u32(100 + 100 * zoom), 0, 1000);
-    loop {
-        if (i >= max_iter) {
-            break;
+    if (uniforms.precision_mode == PRECISION_DOUBLE_SINGLE) {
+        var x = ds_add(-vec2(uniforms.offset.x, uniforms.offset_lo.x), ds(delta.x));
+        var y = ds_add(vec2(uniforms.offset.y, uniforms.offset_lo.y), ds(delta.y));
+        loop {
+            if (i >= max_iter) {
+                break;
+            }
+
+            // f'(z) / f(z) = sum of 1 / (z - root)
+            var s_x = ds(0.0);
+            var s_y = ds(0.0);
+            for (var k = 0u; k < degree; k += 1u) {
+                let r = root(k);
+                let d_x = ds_sub(x, ds(r.x));
+                let d_y = ds_sub(y, ds(r.y));
+                let n = ds_add(ds_mul(d_x, d_x), ds_mul(d_y, d_y));
+                if (n.x > 0.0) {
+                    s_x = ds_add(s_x, ds_div(d_x, n));
+                    s_y = ds_sub(s_y, ds_div(d_y, n));
+                }
+            }
+
+            // z = z - relaxation * f(z)/f'(z) + c
+            var step_x = ds(0.0);
+            var step_y = ds(0.0);
+            let n = ds_add(ds_mul(s_x, s_x), ds_mul(s_y, s_y));
+            if (n.x > 0.0) {
+                step_x = ds_div(ds_mul(ds(relaxation), s_x), n);
+                step_y = ds_div(ds_mul(ds(-relaxation), s_y), n);
+            }
+            let move_x = ds_sub(ds(c.x), step_x);
+            let move_y = ds_sub(ds(c.y), step_y);
+            x = ds_add(x, move_x);
+            y = ds_add(y, move_y);
+
+            moved = move_x.x * move_x.x + move_y.x * move_y.x;
+            if (moved < CONVERGED) {
+                converged = true;
+                break;
+            }
+
+            i += 1u;
         }
-
-        // f(z) = z^3 - 1
-        let r2 = z.x * z.x - z.y * z.y;
-        let i2 = 2.0 * z.x * z.y;
-        let fz = vec2<f32>(
-            r2 * z.x - 3.0 * z.x * z.y * z.y - 1.0,
-            3.0 * z.x * z.x * z.y - z.y * z.y * z.y
-        );
-
-        // f'(z) = 3z^2
-        let dfz = vec2<f32>(
-            3.0 * (z.x * z.x - z.y * z.y),
-            6.0 * z.x * z.y
-        );
-
-        // z = z - f(z)/f'(z) + c
-        let denom = dot(dfz, dfz) + 1e-6;
-        let correction = vec2<f32>(
-            (fz.x * dfz.x + fz.y * dfz.y) / denom,
-            (fz.y * dfz.x - fz.x * dfz.y) / denom
-        );
-
-        z = z - correction + c;
-
-        if (dot(fz, fz) < 1e-6) {
-            break;
+        z = vec2<f32>(x.x, y.x);
+    } else {
+        loop {
+            if (i >= max_iter) {
+                break;
+            }
+
+            // f'(z) / f(z) = sum of 1 / (z - root)
+            var s = vec2<f32>(0.0, 0.0);
+            for (var k = 0u; k < degree; k += 1u) {
+                s += reciprocal(z - root(k));
+            }
+
+            // z = z - relaxation * f(z)/f'(z) + c
+            let step = c - relaxation * reciprocal(s);
+            z += step;
+
+            moved = dot(step, step);
+            if (moved < CONVERGED) {
+                converged = true;
+                break;
+            }
+
+            i += 1u;
         }
-
-        i += 1u;
     }
 
-    var t = f32(i) - log2(log2(dot(z,z) + 1e-6)) + 4.0;
-    t = clamp(t / f32(max_iter), 0.0, 1.0);
+    // Newton's method converges quadratically, so the fraction of a step
+    // left over is how far the final step's exponent overshot.
+    var t = 1.0;
+    if (converged) {
+        let overshoot = log(max(moved, 1e-30)) / log(CONVERGED);
+        t = clamp((f32(i) - clamp(log2(overshoot), 0.0, 1.0)) / SPEED_ITERATIONS, 0.0, 1.0);
+    }
 
+    var colour = get_colour(t);
+    if (colouring != COLOURING_SPEED) {
+        var nearest = 0u;
+        for (var k = 1u; k < degree; k += 1u) {
+            if (distance(z, root(k)) < distance(z, root(nearest))) {
+                nearest = k;
+            }
+        }
+        colour = root_colour(nearest, degree) * (1.0 - t);
+    }
 
-    let colour = get_colour(t);
     return vec4<f32>(colour, 1.0);
 }
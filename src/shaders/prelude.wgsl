@@ -0,0 +1,224 @@
+// prelude.wgsl
+// What every fractal shader shares: the vertex stage, the uniforms, the view,
+// the palette and shading. Included with #include "prelude.wgsl".
+//
+// Uniforms mirrors `UniformData` in uniforms.rs; the uniforms test checks
+// that the layouts agree. PARAM_VECTORS is defined by the preprocessor from
+// `MAX_PARAMS`.
+
+struct VertexIn {
+    @location(0) position: vec2<f32>,
+    @location(1) uv: vec2<f32>,
+};
+
+struct VertexOut {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(in: VertexIn) -> VertexOut {
+    var out: VertexOut;
+    out.position = vec4<f32>(in.position, 0.0, 1.0);
+    out.uv = uniforms.tile.xy + in.uv * uniforms.tile.zw;
+    return out;
+}
+
+struct Uniforms {
+    time: f32,
+    zoom: f32,
+    offset: vec2<f32>,
+    resolution: vec2<f32>,
+    offset_lo: vec2<f32>,
+    tile: vec4<f32>,
+    params: array<vec4<f32>, PARAM_VECTORS>,
+    precision_mode: u32,
+    one: f32,
+    palette_offset: f32,
+    palette_density: f32,
+    colouring: u32,
+    shading: u32,
+    light_angle: f32,
+};
+
+@group(0) @binding(0)
+var<uniform> uniforms: Uniforms;
+
+// How far the point at uv is from the centre of the view, where the
+// shorter side of the view spans 3 at zoom 0. Mirrored by `cpu::view_delta`.
+fn view_delta(uv: vec2<f32>) -> vec2<f32> {
+    let zoom = pow(1.2, uniforms.zoom);
+    let aspect = uniforms.resolution.x / uniforms.resolution.y;
+    let scale = vec2(max(aspect, 1.0), max(1.0 / aspect, 1.0));
+    return (uv * 2.0 - vec2(1.0, 1.0)) * scale * (1.5 / zoom);
+}
+
+// The point of the plane at uv. Mirrored by `cpu::view_point`.
+fn view_point(uv: vec2<f32>) -> vec2<f32> {
+    return view_delta(uv) - vec2(uniforms.offset.x, -uniforms.offset.y);
+}
+
+// The intensity of a point that escaped after iterations, out of max_iter.
+// Spread over the palette on a log scale, as most points escape early.
+// Mirrored by `cpu::escape_intensity`.
+fn escape_intensity(iterations: f32, max_iter: u32) -> f32 {
+    return clamp(log2(max(iterations, 0.0) + 1.0) / log2(f32(max_iter) + 1.0), 0.0, 1.0);
+}
+
+// Evenly spaced linear RGB colours, see `palette::Table`.
+struct Palette {
+    colours: array<vec4<f32>>,
+};
+
+@group(0) @binding(2)
+var<storage, read> palette: Palette;
+
+// Values of colouring, see `Colouring`.
+const COLOURING_HISTOGRAM: u32 = 1u;
+const COLOURING_VALUES: u32 = 2u;
+const COLOURING_GLITCHES: u32 = 3u;
+
+// Intensities at evenly spaced ranks through the view, see `Equaliser`.
+struct Equaliser {
+    quantiles: array<f32>,
+};
+
+@group(0) @binding(3)
+var<storage, read> equaliser: Equaliser;
+
+// The fraction of the view at or below intensity. Mirrored by
+// `Equaliser::equalise`.
+fn equalise(intensity: f32) -> f32 {
+    let last = arrayLength(&equaliser.quantiles) - 1u;
+    if (intensity <= equaliser.quantiles[0]) {
+        return 0.0;
+    }
+    if (intensity >= equaliser.quantiles[last]) {
+        return 1.0;
+    }
+
+    // Narrow down to the quantiles either side of the intensity.
+    var low = 0u;
+    var high = last;
+    loop {
+        if (high - low <= 1u) {
+            break;
+        }
+        let middle = (low + high) / 2u;
+        if (equaliser.quantiles[middle] <= intensity) {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    let below = equaliser.quantiles[low];
+    let fraction = (intensity - below) / (equaliser.quantiles[high] - below);
+    return (f32(low) + fraction) / f32(last);
+}
+
+// The palette's colour for intensity in [0, 1], repeated palette_density
+// times and shifted by palette_offset. A whole number of repetitions ends on
+// the last colour rather than wrapping to the first. With histogram
+// colouring the intensity is equalised first, and its first pass writes out
+// the intensity in place of the colour. The search for glitches draws
+// everything but them black. Mirrored by `cpu::get_colour`.
+fn get_colour(intensity: f32) -> vec3<f32> {
+    if (uniforms.colouring == COLOURING_VALUES) {
+        // Read back by `value_intensity`.
+        return vec3<f32>(-1.0 - clamp(intensity, 0.0, 1.0), 0.0, 0.0);
+    }
+    if (uniforms.colouring == COLOURING_GLITCHES) {
+        return vec3<f32>(0.0);
+    }
+    var t = intensity;
+    if (uniforms.colouring == COLOURING_HISTOGRAM) {
+        t = equalise(intensity);
+    }
+
+    let u = t * uniforms.palette_density + uniforms.palette_offset;
+    var wrapped = u - floor(u);
+    if (wrapped == 0.0 && u > 0.0) {
+        wrapped = 1.0;
+    }
+
+    let last = arrayLength(&palette.colours) - 1u;
+    let x = wrapped * f32(last);
+    let i = min(u32(x), last - 1u);
+    return mix(palette.colours[i].rgb, palette.colours[i + 1u].rgb, x - f32(i));
+}
+
+// Values of shading, see `Shading`.
+const SHADING_FLAT: u32 = 0u;
+const SHADING_BOUNDARY: u32 = 1u;
+// See the constants in shading.rs.
+const SHADED_ESCAPE: f32 = 1e6;
+const BOUNDARY_PIXELS: f32 = 2.0;
+const LIGHT_ELEVATION: f32 = 0.7853982;
+const AMBIENT: f32 = 0.2;
+
+fn complex_mul(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(a.x * b.x - a.y * b.y, a.x * b.y + a.y * b.x);
+}
+
+// Whether escaping points are shaded, so fractals must track the derivative
+// of z. Mirrored by `shading::shaded`.
+fn shaded() -> bool {
+    return uniforms.shading != SHADING_FLAT;
+}
+
+// The square escape radius to use in place of a fractal's own escape, as
+// the distance estimate only holds for large z. Mirrored by
+// `shading::shaded_escape`.
+fn shaded_escape(escape: f32) -> f32 {
+    if (shaded()) {
+        return max(escape, SHADED_ESCAPE);
+    }
+    return escape;
+}
+
+// The gradient for a derivative dz that multiplies like a complex number:
+// z conj(dz). Mirrored by `shading::holomorphic_gradient`.
+fn holomorphic_gradient(z: vec2<f32>, dz: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(z.x * dz.x + z.y * dz.y, z.y * dz.x - z.x * dz.y);
+}
+
+// The distance across a pixel in the plane. Mirrored by
+// `shading::pixel_size`.
+fn pixel_size() -> f32 {
+    let zoom = pow(1.2, uniforms.zoom);
+    return 3.0 / zoom / min(uniforms.resolution.x, uniforms.resolution.y);
+}
+
+// Roughly how far from the set a point is, given where it escaped to and
+// the gradient there. Mirrored by `shading::distance_estimate`.
+fn distance_estimate(z: vec2<f32>, gradient: vec2<f32>) -> f32 {
+    let r2 = dot(z, z);
+    return 0.5 * r2 * log(r2) / length(gradient);
+}
+
+// The colour for intensity of a point that escaped to z, where gradient is
+// transpose(J) z for the derivative J of z, shaded as the uniforms ask.
+// Mirrored by `shading::shade`.
+fn shade(intensity: f32, z: vec2<f32>, gradient: vec2<f32>) -> vec3<f32> {
+    let colour = get_colour(intensity);
+    if (!shaded() || uniforms.colouring == COLOURING_VALUES) {
+        return colour;
+    }
+
+    var brightness: f32;
+    if (uniforms.shading == SHADING_BOUNDARY) {
+        let pixels = distance_estimate(z, gradient) / pixel_size();
+        brightness = smoothstep(0.0, BOUNDARY_PIXELS, pixels);
+    } else {
+        // The surface slopes at 45 degrees, facing away from the set.
+        let normal = vec3<f32>(normalize(gradient), 1.0) * sqrt(0.5);
+        let angle = uniforms.light_angle;
+        let light = vec3<f32>(
+            cos(angle) * cos(LIGHT_ELEVATION),
+            sin(angle) * cos(LIGHT_ELEVATION),
+            sin(LIGHT_ELEVATION)
+        );
+        brightness = AMBIENT + (1.0 - AMBIENT) * max(dot(normal, light), 0.0);
+    }
+    return colour * brightness;
+}
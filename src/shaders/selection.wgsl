@@ -0,0 +1,34 @@
+// selection.wgsl
+// Draws the rubber-band box zoom rectangle over the fractal.
+
+struct Selection {
+    // min.x, min.y, max.x, max.y in framebuffer pixels
+    rect: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> selection: Selection;
+
+// Full-screen triangle, no vertex buffer needed.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let p = position.xy;
+    let lo = selection.rect.xy;
+    let hi = selection.rect.zw;
+
+    if (any(p < lo) || any(p > hi)) {
+        discard;
+    }
+
+    let edge = min(min(p.x - lo.x, hi.x - p.x), min(p.y - lo.y, hi.y - p.y));
+    if (edge < 1.5) {
+        return vec4<f32>(1.0, 1.0, 1.0, 0.9);
+    }
+    return vec4<f32>(1.0, 1.0, 1.0, 0.15);
+}
@@ -0,0 +1,128 @@
+//! Shading escape-time fractals by their distance from the set.
+//!
+//! Alongside `z`, the escape-time shaders track its derivative with respect
+//! to the pixel's point, which gives an estimate of how far the point is
+//! from the set. [`Shading::Boundary`] darkens points within a couple of
+//! pixels of it, so the boundary stays a crisp line at any zoom, and
+//! [`Shading::Lighting`] lights the escape potential as a height map, from
+//! `UniformData::light_angle`.
+//!
+//! Both only need the escaped `z` and the gradient `Jᵀz`, where `J` is the
+//! derivative as a 2x2 matrix, so that families which are not holomorphic,
+//! such as the Burning Ship, shade too. Where the iteration is holomorphic
+//! `J` multiplies by a complex `dz` and the gradient is `z conj(dz)`, see
+//! [`holomorphic_gradient`]. Nova converges rather than escapes and the
+//! formula fractal tracks no derivative, so neither is shaded.
+
+use std::f32::consts::FRAC_PI_4;
+
+use crate::cpu::{ColourTables, get_colour};
+use crate::histogram::Colouring;
+use crate::named::impl_named;
+use crate::uniforms::UniformData;
+
+/// Square of the radius shaded points iterate out to, well past any
+/// fractal's own, as the estimate only holds for large `z`.
+pub const SHADED_ESCAPE: f32 = 1e6;
+/// Width in pixels over which [`Shading::Boundary`] fades in from black.
+pub const BOUNDARY_PIXELS: f32 = 2.0;
+/// Angle of the light above the plane for [`Shading::Lighting`].
+pub const LIGHT_ELEVATION: f32 = FRAC_PI_4;
+/// Brightness of the side facing away from the light.
+pub const AMBIENT: f32 = 0.2;
+/// Where the light starts, in radians anticlockwise from the right.
+pub const DEFAULT_LIGHT_ANGLE: f32 = FRAC_PI_4;
+
+/// How escaping points are shaded, as passed in `UniformData::shading`.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shading {
+    /// Not at all, only the palette.
+    Flat = 0,
+    /// Darkened by their distance from the set.
+    Boundary = 1,
+    /// Lit as a height map rising towards the set.
+    Lighting = 2,
+}
+
+impl_named!(Shading {
+    Flat => "flat",
+    Boundary => "boundary",
+    Lighting => "lighting",
+});
+
+/// CPU version of `shaded` in `prelude.wgsl`: whether `uniforms` shade, so
+/// the derivative is needed.
+pub fn shaded(uniforms: &UniformData) -> bool {
+    uniforms.shading != Shading::Flat as u32
+}
+
+/// CPU version of `shaded_escape` in `prelude.wgsl`: the square escape
+/// radius to use in place of a fractal's own `escape`.
+pub fn shaded_escape(escape: f32, uniforms: &UniformData) -> f32 {
+    if shaded(uniforms) {
+        escape.max(SHADED_ESCAPE)
+    } else {
+        escape
+    }
+}
+
+/// CPU version of `holomorphic_gradient` in `prelude.wgsl`: the gradient
+/// for a derivative `dz` that multiplies like a complex number.
+pub fn holomorphic_gradient(z: [f32; 2], dz: [f32; 2]) -> [f32; 2] {
+    [z[0] * dz[0] + z[1] * dz[1], z[1] * dz[0] - z[0] * dz[1]]
+}
+
+/// CPU version of `pixel_size` in `prelude.wgsl`: the distance across a
+/// pixel in the plane, for the views every fractal but the demo shares.
+pub fn pixel_size(uniforms: &UniformData) -> f32 {
+    let zoom = 1.2f32.powf(uniforms.zoom);
+    3.0 / zoom / uniforms.resolution[0].min(uniforms.resolution[1])
+}
+
+/// CPU version of `distance_estimate` in `prelude.wgsl`: roughly how far
+/// from the set a point is, given where it escaped to and the gradient
+/// there. Within a small factor of the true distance.
+pub fn distance_estimate(z: [f32; 2], gradient: [f32; 2]) -> f32 {
+    let r2 = z[0] * z[0] + z[1] * z[1];
+    0.5 * r2 * r2.ln() / gradient[0].hypot(gradient[1])
+}
+
+/// CPU version of `shade` in `prelude.wgsl`: the colour for `intensity` of
+/// a point that escaped to `z`, shaded as `uniforms` ask.
+pub fn shade(
+    intensity: f32,
+    z: [f32; 2],
+    gradient: [f32; 2],
+    uniforms: &UniformData,
+    tables: ColourTables,
+) -> [f32; 3] {
+    let colour = get_colour(intensity, uniforms, tables);
+    if !shaded(uniforms) || uniforms.colouring == Colouring::Values as u32 {
+        return colour;
+    }
+
+    let brightness = if uniforms.shading == Shading::Boundary as u32 {
+        let pixels = distance_estimate(z, gradient) / pixel_size(uniforms);
+        smoothstep(0.0, BOUNDARY_PIXELS, pixels)
+    } else {
+        // The surface slopes at 45 degrees, facing away from the set.
+        let length = gradient[0].hypot(gradient[1]);
+        let normal = [gradient[0] / length, gradient[1] / length, 1.0].map(|n| n * 0.5f32.sqrt());
+        let angle = uniforms.light_angle;
+        let light = [
+            angle.cos() * LIGHT_ELEVATION.cos(),
+            angle.sin() * LIGHT_ELEVATION.cos(),
+            LIGHT_ELEVATION.sin(),
+        ];
+        let lambert = normal[0] * light[0] + normal[1] * light[1] + normal[2] * light[2];
+        AMBIENT + (1.0 - AMBIENT) * lambert.max(0.0)
+    };
+    colour.map(|channel| channel * brightness)
+}
+
+/// CPU version of WGSL's `smoothstep`.
+fn smoothstep(low: f32, high: f32, x: f32) -> f32 {
+    let t = ((x - low) / (high - low)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
@@ -0,0 +1,91 @@
+/// Upper bound on the pixels held in memory at once while stitching a row of
+/// tiles, in bytes.
+const STRIP_BUDGET: u64 = 256 * 1024 * 1024;
+
+/// Splits an image into equally sized tiles that each fit in one texture.
+///
+/// Tiles in the last column and row may hang over the edge of the image;
+/// the overhang is rendered and then cropped so every tile can share one
+/// offscreen target.
+pub struct TileGrid {
+    pub width: u32,
+    pub height: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+}
+
+impl TileGrid {
+    pub fn new(width: u32, height: u32, max_dimension: u32) -> Self {
+        let max_rows = (STRIP_BUDGET / (width as u64 * 4)).clamp(1, u32::MAX as u64) as u32;
+
+        let columns = width.div_ceil(max_dimension);
+        let rows = height.div_ceil(max_dimension.min(max_rows));
+
+        Self {
+            width,
+            height,
+            tile_width: width.div_ceil(columns),
+            tile_height: height.div_ceil(rows),
+        }
+    }
+
+    pub fn columns(&self) -> u32 {
+        self.width.div_ceil(self.tile_width)
+    }
+
+    pub fn rows(&self) -> u32 {
+        self.height.div_ceil(self.tile_height)
+    }
+
+    /// The `tile` uniform for a tile: the offset and size of its window
+    /// into the full image in uv space. uv has its origin in the bottom left
+    /// while tile rows count down from the top.
+    pub fn tile_window(&self, column: u32, row: u32) -> [f32; 4] {
+        let x = (column * self.tile_width) as f64 / self.width as f64;
+        let bottom = ((row + 1) * self.tile_height) as f64;
+        let y = 1.0 - bottom / self.height as f64;
+        let scale_x = self.tile_width as f64 / self.width as f64;
+        let scale_y = self.tile_height as f64 / self.height as f64;
+
+        [x as f32, y as f32, scale_x as f32, scale_y as f32]
+    }
+
+    /// Render every tile in row order, stitching each row of tiles into a
+    /// strip of full-width scanlines that is handed to `write_rows` before
+    /// the next row is rendered.
+    ///
+    /// `draw_tile` receives the tile window and must return
+    /// `tile_width * tile_height` RGBA8 pixels, top row first.
+    pub fn render<E>(
+        &self,
+        mut draw_tile: impl FnMut([f32; 4]) -> Vec<u8>,
+        mut write_rows: impl FnMut(&[u8]) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let row_bytes = self.width as usize * 4;
+        let tile_row_bytes = self.tile_width as usize * 4;
+
+        for row in 0..self.rows() {
+            let strip_rows = self.tile_height.min(self.height - row * self.tile_height) as usize;
+            let mut strip = vec![0u8; row_bytes * strip_rows];
+
+            for column in 0..self.columns() {
+                let pixels = draw_tile(self.tile_window(column, row));
+
+                let x = (column * self.tile_width) as usize * 4;
+                let copy_bytes = tile_row_bytes.min(row_bytes - x);
+                for (y, tile_row) in pixels
+                    .chunks_exact(tile_row_bytes)
+                    .take(strip_rows)
+                    .enumerate()
+                {
+                    let start = y * row_bytes + x;
+                    strip[start..start + copy_bytes].copy_from_slice(&tile_row[..copy_bytes]);
+                }
+            }
+
+            write_rows(&strip)?;
+        }
+
+        Ok(())
+    }
+}
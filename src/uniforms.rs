@@ -0,0 +1,365 @@
+use std::sync::Arc;
+
+use wgpu::{BindGroupLayout, util::DeviceExt};
+
+use crate::histogram::{Colouring, Equalised, Equaliser};
+use crate::palette::Table;
+use crate::perturbation::{MAX_REFERENCES, Orbit, Series};
+use crate::precision::Precision;
+use crate::shading::{DEFAULT_LIGHT_ANGLE, Shading};
+use crate::viewport::Viewport;
+
+/// Number of fractal parameters that fit in `UniformData::params`.
+pub const MAX_PARAMS: usize = 16;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct UniformData {
+    pub time: f32,
+    pub zoom: f32,
+    /// The view offset rounded to `f32`. See `offset_lo`.
+    pub offset: [f32; 2],
+    /// Size in pixels of the whole image being rendered, even when drawing
+    /// one tile of it. Gives the aspect ratio, and the size of a pixel for
+    /// shading.
+    pub resolution: [f32; 2],
+    /// What `offset` lost to rounding, so that `offset + offset_lo` carries
+    /// the view's `f64` offset to double-single shaders.
+    pub offset_lo: [f32; 2],
+    /// Window into the view in uv space as `[x, y, width, height]`, used to
+    /// render one tile of a larger image. `[0, 0, 1, 1]` is the whole view.
+    pub tile: [f32; 4],
+    /// Fractal specific values, described by `Fractal::params`.
+    pub params: [f32; MAX_PARAMS],
+    /// A [`Precision`] as `u32`, selecting the arithmetic the shader uses.
+    pub precision_mode: u32,
+    /// Always 1. Shaders multiply by it where the compiler must not simplify
+    /// the arithmetic, which it cannot do with a value it does not know.
+    pub one: f32,
+    /// Shifts the palette along, for cycling its colours.
+    pub palette_offset: f32,
+    /// Times the palette repeats over the fractal's intensity range.
+    pub palette_density: f32,
+    /// A [`Colouring`] as `u32`, selecting how intensities pick colours.
+    pub colouring: u32,
+    /// A [`Shading`] as `u32`, selecting how escaping points are shaded.
+    pub shading: u32,
+    /// Direction the light comes from for [`Shading::Lighting`], in radians
+    /// anticlockwise from the right.
+    pub light_angle: f32,
+    pub _padding: [u32; 1],
+}
+
+/// The buffers every fractal shader can read: the uniforms at binding 0, a
+/// reference orbit (see [`crate::perturbation`]) at binding 1, the palette
+/// (see [`crate::palette`]) at binding 2 and the histogram equaliser (see
+/// [`crate::histogram`]) at binding 3.
+pub struct Uniforms {
+    pub uniform_bind_group: wgpu::BindGroup,
+    uniform_buffer: wgpu::Buffer,
+    uniform_data: UniformData,
+    orbit_buffer: wgpu::Buffer,
+    /// Size of `orbit_buffer` in bytes.
+    orbit_size: usize,
+    palette_buffer: wgpu::Buffer,
+    /// The palette in `palette_buffer`.
+    palette: Arc<Table>,
+    equaliser_buffer: wgpu::Buffer,
+    /// What the equaliser in `equaliser_buffer` was worked out for.
+    equalised: Option<Equalised>,
+}
+
+impl Default for UniformData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UniformData {
+    pub fn new() -> Self {
+        Self {
+            time: 0.0,
+            zoom: 1.0,
+            offset: [0.0, 0.0],
+            resolution: [1.0, 1.0],
+            offset_lo: [0.0, 0.0],
+            tile: [0.0, 0.0, 1.0, 1.0],
+            params: [0.0; MAX_PARAMS],
+            precision_mode: Precision::Single as u32,
+            one: 1.0,
+            palette_offset: 0.0,
+            palette_density: 1.0,
+            colouring: Colouring::Direct as u32,
+            shading: Shading::Flat as u32,
+            light_angle: DEFAULT_LIGHT_ANGLE,
+            _padding: [0; 1],
+        }
+    }
+
+    /// Uniforms looking through `viewport`, with everything else at its
+    /// default.
+    pub fn from_viewport(viewport: &Viewport) -> Self {
+        let [x, y] = viewport.offset();
+        let offset = [x as f32, y as f32];
+        Self {
+            zoom: viewport.zoom(),
+            offset,
+            offset_lo: [(x - offset[0] as f64) as f32, (y - offset[1] as f64) as f32],
+            ..Self::new()
+        }
+    }
+
+    /// The same uniforms drawing only `tile` of the view, see
+    /// `UniformData::tile`.
+    pub fn with_tile(self, tile: [f32; 4]) -> Self {
+        Self { tile, ..self }
+    }
+
+    pub fn bind_group_layout(device: &wgpu::Device) -> BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("uniform_bind_group_layout"),
+        })
+    }
+}
+
+impl Uniforms {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let uniform_data = UniformData::new();
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Uniform Buffer"),
+            contents: bytemuck::bytes_of(&uniform_data),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let orbit_buffer = create_orbit_buffer(device, &orbit_bytes(&Orbit::default()));
+        let palette = Arc::new(Table::default());
+        let palette_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Palette Buffer"),
+            contents: bytemuck::cast_slice(&palette.colours),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
+        let equaliser_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Equaliser Buffer"),
+            contents: bytemuck::cast_slice(&Equaliser::default().quantiles),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
+        let uniform_bind_group = create_bind_group(
+            device,
+            &uniform_buffer,
+            &orbit_buffer,
+            &palette_buffer,
+            &equaliser_buffer,
+        );
+
+        Self {
+            uniform_bind_group,
+            uniform_buffer,
+            uniform_data,
+            orbit_size: orbit_buffer.size() as usize,
+            orbit_buffer,
+            palette_buffer,
+            palette,
+            equaliser_buffer,
+            equalised: None,
+        }
+    }
+
+    pub fn data(&self) -> UniformData {
+        self.uniform_data
+    }
+
+    /// Upload `uniform_data`.
+    pub fn update(&mut self, queue: &wgpu::Queue, uniform_data: UniformData) {
+        self.uniform_data = uniform_data;
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::bytes_of(&self.uniform_data),
+        );
+    }
+
+    /// Upload the palette to colour with, unless it is already the one
+    /// uploaded.
+    pub fn set_palette(&mut self, queue: &wgpu::Queue, palette: &Arc<Table>) {
+        if !Arc::ptr_eq(palette, &self.palette) {
+            // Every table is the same size, so it fits the buffer.
+            queue.write_buffer(
+                &self.palette_buffer,
+                0,
+                bytemuck::cast_slice(&palette.colours),
+            );
+            self.palette = palette.clone();
+        }
+    }
+
+    /// What the uploaded equaliser was worked out for, if anything.
+    pub fn equalised(&self) -> Option<Equalised> {
+        self.equalised
+    }
+
+    /// Upload the equaliser for histogram colouring, worked out for
+    /// `equalised`.
+    pub fn set_equaliser(
+        &mut self,
+        queue: &wgpu::Queue,
+        equaliser: &Equaliser,
+        equalised: Equalised,
+    ) {
+        self.equalised = Some(equalised);
+        // Every equaliser is the same size, so it fits the buffer.
+        queue.write_buffer(
+            &self.equaliser_buffer,
+            0,
+            bytemuck::cast_slice(&equaliser.quantiles),
+        );
+    }
+
+    /// Upload the reference orbit and its secondaries, padded as by
+    /// [`Orbit::padded_points`] as the buffer cannot be empty.
+    pub fn set_orbit(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, orbit: &Orbit) {
+        let bytes = orbit_bytes(orbit);
+        // Intensities follow the orbit.
+        self.equalised = None;
+
+        // Shaders take the orbit's length from the buffer, so it is sized
+        // to fit exactly.
+        if bytes.len() == self.orbit_size {
+            queue.write_buffer(&self.orbit_buffer, 0, &bytes);
+        } else {
+            self.orbit_buffer = create_orbit_buffer(device, &bytes);
+            self.orbit_size = bytes.len();
+            self.uniform_bind_group = create_bind_group(
+                device,
+                &self.uniform_buffer,
+                &self.orbit_buffer,
+                &self.palette_buffer,
+                &self.equaliser_buffer,
+            );
+        }
+    }
+}
+
+/// Where a reference's points lie in the orbit buffer, as laid out in
+/// `mandelbrot_deep.wgsl`. Unused references have no points.
+#[repr(C)]
+#[derive(Clone, Copy, Default, bytemuck::Pod, bytemuck::Zeroable)]
+struct Span {
+    /// See `Secondary::offset`, and 0 for the main reference.
+    offset: [f32; 2],
+    start: u32,
+    len: u32,
+}
+
+/// The orbit buffer's contents: the series, a [`Span`] for each of
+/// [`MAX_REFERENCES`], then the main reference's points followed by those
+/// of the secondaries.
+fn orbit_bytes(orbit: &Orbit) -> Vec<u8> {
+    let main = orbit.padded_points();
+    let references = std::iter::once(([0.0; 2], main))
+        .chain(
+            orbit
+                .secondaries
+                .iter()
+                .map(|secondary| (secondary.offset, &secondary.points[..])),
+        )
+        .take(MAX_REFERENCES);
+
+    let mut spans = [Span::default(); MAX_REFERENCES];
+    let mut points = Vec::new();
+    for (span, (offset, reference)) in spans.iter_mut().zip(references) {
+        *span = Span {
+            offset,
+            start: points.len() as u32,
+            len: reference.len() as u32,
+        };
+        points.extend_from_slice(reference);
+    }
+
+    let mut bytes =
+        Vec::with_capacity(size_of::<Series>() + size_of_val(&spans) + size_of_val(&points[..]));
+    bytes.extend_from_slice(bytemuck::bytes_of(&orbit.series));
+    bytes.extend_from_slice(bytemuck::cast_slice(&spans));
+    bytes.extend_from_slice(bytemuck::cast_slice(&points));
+    bytes
+}
+
+fn create_orbit_buffer(device: &wgpu::Device, contents: &[u8]) -> wgpu::Buffer {
+    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Orbit Buffer"),
+        contents,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+    })
+}
+
+fn create_bind_group(
+    device: &wgpu::Device,
+    uniform_buffer: &wgpu::Buffer,
+    orbit_buffer: &wgpu::Buffer,
+    palette_buffer: &wgpu::Buffer,
+    equaliser_buffer: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &UniformData::bind_group_layout(device),
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: orbit_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: palette_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: equaliser_buffer.as_entire_binding(),
+            },
+        ],
+        label: Some("uniform_bind_group"),
+    })
+}
@@ -0,0 +1,432 @@
+use std::fmt;
+use std::str::FromStr;
+
+use dashu_float::round::mode::HalfEven;
+use dashu_float::{DBig, FBig};
+
+/// Half the width of the view at zoom 0, in complex-plane units.
+const BASE_HALF_EXTENT: f64 = 1.5;
+/// How much each unit of zoom magnifies the view.
+const ZOOM_BASE: f64 = 1.2;
+/// Bits the centre keeps beyond those needed to tell pixels apart.
+const CENTER_GUARD_BITS: usize = 64;
+/// Fewest bits the centre is kept with.
+const MIN_CENTER_PRECISION: usize = 64;
+/// Largest power of two a view may be larger or smaller than the unit by,
+/// which keeps the centre to a few megabytes.
+const MAX_SCALE_EXPONENT: u64 = 1 << 24;
+
+/// Binary float with as many bits as it is given, for coordinates deeper
+/// than `f64` can hold.
+pub type BigFloat = FBig<HalfEven>;
+
+/// A positive size as `mantissa * 2^exponent` with `mantissa` in `[1, 2)`,
+/// so views can shrink past the range of `f64`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Scale {
+    pub mantissa: f64,
+    pub exponent: i64,
+}
+
+impl Scale {
+    /// `value`, which must be positive and finite.
+    pub fn new(value: f64) -> Self {
+        Self::from_log2(value.log2())
+    }
+
+    /// The size `2^log2`.
+    pub fn from_log2(log2: f64) -> Self {
+        let exponent = log2.floor();
+        Self {
+            mantissa: (log2 - exponent).exp2(),
+            exponent: exponent as i64,
+        }
+        .normalized()
+    }
+
+    pub fn log2(&self) -> f64 {
+        self.exponent as f64 + self.mantissa.log2()
+    }
+
+    /// The size as `f64`, which is 0 past its range.
+    pub fn to_f64(&self) -> f64 {
+        let exponent = self.exponent.clamp(i32::MIN as i64, i32::MAX as i64) as i32;
+        self.mantissa * 2f64.powi(exponent)
+    }
+
+    /// This size multiplied by `factor`, which must be positive.
+    pub fn scaled(&self, factor: f64) -> Self {
+        Self {
+            mantissa: self.mantissa * factor,
+            exponent: self.exponent,
+        }
+        .normalized()
+    }
+
+    /// `value` times this size.
+    fn times(&self, value: f64) -> BigFloat {
+        (big(value) * big(self.mantissa)) << self.exponent as isize
+    }
+
+    fn normalized(self) -> Self {
+        let shift = self.mantissa.log2().floor();
+        Self {
+            mantissa: self.mantissa / shift.exp2(),
+            exponent: self.exponent + shift as i64,
+        }
+    }
+}
+
+/// The camera over the complex plane.
+///
+/// Matches the mapping in the shaders: the view is centred on
+/// `(-offset.x, offset.y)` and spans [`Viewport::half_extent`] either side of
+/// it, which shrinks exponentially with [`Viewport::zoom`]. The centre is a
+/// [`BigFloat`] that gains bits as the view shrinks and the size a
+/// [`Scale`], so the camera itself survives any depth; each renderer takes
+/// what its precision can use.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Viewport {
+    center: [BigFloat; 2],
+    /// Half the length of the shorter side.
+    scale: Scale,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self::new(1.0, [0.0, 0.0]).expect("the default view is valid")
+    }
+}
+
+impl Viewport {
+    /// A view at a zoom level with the offset the shaders take.
+    pub fn new(zoom: f32, offset: [f64; 2]) -> Result<Self, InvalidViewError> {
+        if !offset.iter().all(|coordinate| coordinate.is_finite()) {
+            return Err(InvalidViewError::NotFinite);
+        }
+        let mut viewport = Self {
+            center: [big(-offset[0]), big(offset[1])],
+            scale: Scale::new(BASE_HALF_EXTENT),
+        };
+        viewport.set_zoom(zoom)?;
+        Ok(viewport)
+    }
+
+    /// A view centred on `center` spanning `half_extent` either side of it
+    /// along its shorter side.
+    pub fn from_center(
+        center: [BigFloat; 2],
+        half_extent: Scale,
+    ) -> Result<Self, InvalidViewError> {
+        check_scale(&half_extent)?;
+        let mut viewport = Self {
+            center,
+            scale: half_extent,
+        };
+        viewport.shift(0.0, 0.0);
+        Ok(viewport)
+    }
+
+    /// Zoom level, as passed to the shaders.
+    pub fn zoom(&self) -> f32 {
+        ((BASE_HALF_EXTENT.log2() - self.scale.log2()) / ZOOM_BASE.log2()) as f32
+    }
+
+    /// Move to another zoom level around the same centre. A zoom that is
+    /// not finite, or too deep to keep the centre for, leaves the view as it
+    /// was.
+    pub fn set_zoom(&mut self, zoom: f32) -> Result<(), InvalidViewError> {
+        if !zoom.is_finite() {
+            return Err(InvalidViewError::NotFinite);
+        }
+        let scale = Scale::from_log2(BASE_HALF_EXTENT.log2() - zoom as f64 * ZOOM_BASE.log2());
+        check_scale(&scale)?;
+        self.resize(scale);
+        Ok(())
+    }
+
+    /// Distance from the centre of the view to the edge of its shorter side.
+    pub fn scale(&self) -> Scale {
+        self.scale
+    }
+
+    /// [`Viewport::scale`] as `f64`, which is 0 past its range.
+    pub fn half_extent(&self) -> f64 {
+        self.scale.to_f64()
+    }
+
+    /// Horizontal and vertical distance from the centre of the view to its
+    /// edges, for a view `aspect` times wider than it is tall.
+    pub fn half_extents(&self, aspect: f32) -> [f64; 2] {
+        let [x, y] = aspect_scale(aspect);
+        let half_extent = self.half_extent();
+        [half_extent * x, half_extent * y]
+    }
+
+    /// The complex number at the centre of the view, at full precision.
+    pub fn center(&self) -> &[BigFloat; 2] {
+        &self.center
+    }
+
+    /// The offset the shaders take, rounded to `f64`.
+    pub fn offset(&self) -> [f64; 2] {
+        let [x, y] = &self.center;
+        [-x.to_f64().value(), y.to_f64().value()]
+    }
+
+    /// The complex number under a point of the view, given as fractions of
+    /// the view size from the top left, rounded to `f64`.
+    pub fn point_at(&self, x: f32, y: f32, aspect: f32) -> [f64; 2] {
+        let [dx, dy] = from_center(x, y, aspect);
+        let [cx, cy] = &self.center;
+        [
+            (cx + self.scale.times(dx)).to_f64().value(),
+            (cy + self.scale.times(dy)).to_f64().value(),
+        ]
+    }
+
+    /// Drag the view by a distance given as fractions of the view size, so
+    /// the point under the cursor follows it.
+    pub fn pan(&mut self, dx: f32, dy: f32, aspect: f32) {
+        let [x, y] = aspect_scale(aspect);
+        self.shift(-2.0 * dx as f64 * x, 2.0 * dy as f64 * y);
+    }
+
+    /// Change the zoom by `amount` while keeping the complex number under a
+    /// point of the view (given as for [`Viewport::point_at`]) fixed.
+    pub fn zoom_at(&mut self, x: f32, y: f32, amount: f32, aspect: f32) {
+        let factor = ZOOM_BASE.powf(-amount as f64);
+        let [dx, dy] = from_center(x, y, aspect);
+        self.shift(dx * (1.0 - factor), dy * (1.0 - factor));
+        self.resize(self.scale.scaled(factor));
+    }
+
+    /// Centre the view on a rectangle given by two opposite corners (as for
+    /// [`Viewport::point_at`]) and zoom in until it just fits, so all of it
+    /// stays visible without being stretched. A rectangle with no extent
+    /// either way, or a corner that is not finite, has no zoom to fit, so
+    /// leaves the view as it was.
+    pub fn fit_rect(
+        &mut self,
+        x0: f32,
+        y0: f32,
+        x1: f32,
+        y1: f32,
+        aspect: f32,
+    ) -> Result<(), EmptyRectError> {
+        let extent = (x1 - x0).abs().max((y1 - y0).abs());
+        if extent == 0.0 || ![x0, y0, x1, y1].iter().all(|corner| corner.is_finite()) {
+            return Err(EmptyRectError);
+        }
+        let [dx, dy] = from_center((x0 + x1) * 0.5, (y0 + y1) * 0.5, aspect);
+
+        self.shift(dx, dy);
+        self.resize(self.scale.scaled(extent as f64));
+        Ok(())
+    }
+
+    /// Distance between neighbouring pixels of a view `resolution` pixels in
+    /// size, in complex-plane units.
+    pub fn pixel_spacing(&self, resolution: [f32; 2]) -> f64 {
+        let aspect = resolution[0] / resolution[1];
+        2.0 * self.half_extents(aspect)[1] / resolution[1] as f64
+    }
+
+    /// Change the half extent, keeping the centre.
+    fn resize(&mut self, scale: Scale) {
+        self.scale = scale;
+        self.shift(0.0, 0.0);
+    }
+
+    /// Move the centre by multiples of the half extent, first giving it
+    /// enough bits for the current scale.
+    fn shift(&mut self, dx: f64, dy: f64) {
+        let precision = center_precision(&self.scale);
+        let [x, y] = &self.center;
+        let widen = |value: &BigFloat| {
+            let precision = precision.max(value.precision());
+            value.clone().with_precision(precision).value()
+        };
+        self.center = [
+            widen(x) + self.scale.times(dx),
+            widen(y) + self.scale.times(dy),
+        ];
+    }
+}
+
+/// Saved as lines of `key = value`, with the centre in as many decimal
+/// digits as it has bits and `half_extent` the shorter side's.
+impl fmt::Display for Viewport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (key, value) in ["center_x", "center_y"].iter().zip(&self.center) {
+            let digits = (value.precision() as f64 * std::f64::consts::LOG10_2).ceil() as usize + 2;
+            let decimal = value.clone().with_base_and_precision::<10>(digits).value();
+            writeln!(f, "{key} = {decimal}")?;
+        }
+
+        let log10 = self.scale.log2() * std::f64::consts::LOG10_2;
+        let exponent = log10.floor();
+        let mantissa = 10f64.powf(log10 - exponent);
+        writeln!(f, "half_extent = {mantissa}e{exponent}")
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ParseViewportError {
+    Missing(&'static str),
+    Invalid { key: String, value: String },
+    UnknownKey(String),
+}
+
+impl fmt::Display for ParseViewportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseViewportError::Missing(key) => write!(f, "view has no {key}"),
+            ParseViewportError::Invalid { key, value } => {
+                write!(f, "invalid {key} in view: {value}")
+            }
+            ParseViewportError::UnknownKey(key) => write!(f, "unknown key in view: {key}"),
+        }
+    }
+}
+
+impl std::error::Error for ParseViewportError {}
+
+/// A rectangle passed to [`Viewport::fit_rect`] that has no extent, or
+/// corners that are not finite.
+#[derive(Debug, PartialEq)]
+pub struct EmptyRectError;
+
+impl fmt::Display for EmptyRectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "rectangle has no extent to zoom to")
+    }
+}
+
+impl std::error::Error for EmptyRectError {}
+
+/// A view given by [`Viewport::new`], [`Viewport::set_zoom`] or
+/// [`Viewport::from_center`] that cannot be shown.
+#[derive(Debug, PartialEq)]
+pub enum InvalidViewError {
+    /// A coordinate or the zoom is infinite or NaN.
+    NotFinite,
+    /// The view is larger or smaller than [`Scale`] keeps.
+    OutOfRange,
+}
+
+impl fmt::Display for InvalidViewError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidViewError::NotFinite => write!(f, "view coordinates must be finite"),
+            InvalidViewError::OutOfRange => write!(f, "zoom is out of range"),
+        }
+    }
+}
+
+impl std::error::Error for InvalidViewError {}
+
+impl FromStr for Viewport {
+    type Err = ParseViewportError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (mut x, mut y, mut half_extent) = (None, None, None);
+
+        for line in s.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| ParseViewportError::Invalid {
+                    key: "line".to_string(),
+                    value: line.to_string(),
+                })?;
+            let (key, value) = (key.trim(), value.trim());
+            let invalid = || ParseViewportError::Invalid {
+                key: key.to_string(),
+                value: value.to_string(),
+            };
+
+            match key {
+                "center_x" => x = Some(DBig::from_str(value).map_err(|_| invalid())?),
+                "center_y" => y = Some(DBig::from_str(value).map_err(|_| invalid())?),
+                "half_extent" => half_extent = Some(parse_scale(value).ok_or_else(invalid)?),
+                _ => return Err(ParseViewportError::UnknownKey(key.to_string())),
+            }
+        }
+
+        let half_extent = half_extent.ok_or(ParseViewportError::Missing("half_extent"))?;
+        let precision = center_precision(&half_extent);
+        let x = x.ok_or(ParseViewportError::Missing("center_x"))?;
+        let y = y.ok_or(ParseViewportError::Missing("center_y"))?;
+        let viewport = Self::from_center(
+            [to_binary(x, precision), to_binary(y, precision)],
+            half_extent,
+        );
+        Ok(viewport.expect("parse_scale only gives scales that can be shown"))
+    }
+}
+
+/// How many half extents the view reaches horizontally and vertically.
+fn aspect_scale(aspect: f32) -> [f64; 2] {
+    let aspect = aspect as f64;
+    [aspect.max(1.0), (1.0 / aspect).max(1.0)]
+}
+
+/// A point of the view as multiples of the half extent from its centre.
+fn from_center(x: f32, y: f32, aspect: f32) -> [f64; 2] {
+    let [scale_x, scale_y] = aspect_scale(aspect);
+    [
+        (x as f64 * 2.0 - 1.0) * scale_x,
+        (1.0 - y as f64 * 2.0) * scale_y,
+    ]
+}
+
+/// Whether a view of this size can be shown: its size is finite and the
+/// bits its centre needs fit in memory.
+fn check_scale(scale: &Scale) -> Result<(), InvalidViewError> {
+    if !(scale.mantissa.is_finite() && scale.mantissa > 0.0) {
+        return Err(InvalidViewError::NotFinite);
+    }
+    if scale.exponent.unsigned_abs() > MAX_SCALE_EXPONENT {
+        return Err(InvalidViewError::OutOfRange);
+    }
+    Ok(())
+}
+
+fn big(value: f64) -> BigFloat {
+    BigFloat::try_from(value).expect("viewport coordinates are finite")
+}
+
+/// Bits a centre needs to tell apart the pixels of a view of this size.
+fn center_precision(scale: &Scale) -> usize {
+    let view_bits = (-scale.log2()).max(0.0).ceil() as usize;
+    (view_bits + CENTER_GUARD_BITS).max(MIN_CENTER_PRECISION)
+}
+
+/// A decimal number in binary, with at least `precision` bits and as many
+/// as its digits carry.
+fn to_binary(decimal: DBig, precision: usize) -> BigFloat {
+    let digit_bits = (decimal.precision() as f64 / std::f64::consts::LOG10_2).ceil() as usize + 1;
+    decimal
+        .with_base_and_precision::<2>(precision.max(digit_bits))
+        .value()
+        .with_rounding()
+}
+
+/// A positive number in scientific notation, whose exponent may be beyond
+/// the range of `f64` but not of a view.
+fn parse_scale(value: &str) -> Option<Scale> {
+    let (mantissa, exponent) = value.split_once(['e', 'E']).unwrap_or((value, "0"));
+    let mantissa: f64 = mantissa.trim().parse().ok()?;
+    let exponent: i64 = exponent.trim().parse().ok()?;
+    if !(mantissa.is_finite() && mantissa > 0.0) {
+        return None;
+    }
+    let scale = Scale::from_log2(mantissa.log2() + exponent as f64 / std::f64::consts::LOG10_2);
+    check_scale(&scale).ok()?;
+    Some(scale)
+}
@@ -0,0 +1,75 @@
+//! Helpers shared by the integration tests.
+//!
+//! The GPU tests fail when no adapter is found, so that a machine without
+//! one does not pass having checked nothing. Set `FRACTAL_NO_GPU=1` to skip
+//! them there instead.
+
+// Each test crate uses only some of these.
+#![allow(dead_code)]
+
+use std::sync::Arc;
+
+use fractal::palette::Table;
+use fractal::perturbation::Orbit;
+use fractal::{FRACTALS, HeadlessState, UniformData, cpu};
+
+/// Largest per-channel difference at which two pixels still match.
+pub const CHANNEL_TOLERANCE: u8 = 8;
+/// Fraction of pixels allowed to differ. Points on the boundary of the set are
+/// chaotic, so a few flip with any change in float rounding between drivers.
+pub const MISMATCH_TOLERANCE: f64 = 0.02;
+
+/// The GPU renderer on the fallback adapter, or `None` if there is none and
+/// `FRACTAL_NO_GPU` is set.
+pub fn headless() -> Option<HeadlessState> {
+    match pollster::block_on(HeadlessState::new(true)) {
+        Ok(headless) => Some(headless),
+        Err(e) if std::env::var_os("FRACTAL_NO_GPU").is_some() => {
+            eprintln!("skipping GPU test: {e}");
+            None
+        }
+        Err(e) => panic!("{e} (set FRACTAL_NO_GPU=1 to skip the GPU tests)"),
+    }
+}
+
+/// Whether two RGBA8 pixels differ by more than [`CHANNEL_TOLERANCE`] in any
+/// channel.
+pub fn differ(a: &[u8], b: &[u8]) -> bool {
+    a.iter()
+        .zip(b)
+        .any(|(a, b)| a.abs_diff(*b) > CHANNEL_TOLERANCE)
+}
+
+/// Render `uniform_data` of the fractal at `bundle_idx`, with its reference
+/// `orbit` and coloured with `palette`, on the GPU and the CPU, and check
+/// that at most a `tolerance` fraction of the pixels differ. Returns the
+/// GPU's pixels.
+pub fn assert_gpu_matches_cpu(
+    headless: &mut HeadlessState,
+    bundle_idx: usize,
+    uniform_data: UniformData,
+    orbit: &Orbit,
+    palette: &Arc<Table>,
+    tolerance: f64,
+) -> Vec<u8> {
+    let [width, height] = uniform_data.resolution.map(|side| side as u32);
+    headless.set_palette(palette.clone());
+    let gpu = headless.draw(width, height, uniform_data, orbit, bundle_idx);
+    let fractal = FRACTALS[bundle_idx];
+    let cpu = cpu::render(width, height, &uniform_data, orbit, fractal, palette);
+
+    let mismatched = gpu
+        .chunks_exact(4)
+        .zip(cpu.chunks_exact(4))
+        .filter(|(gpu, cpu)| differ(gpu, cpu))
+        .count();
+    let pixels = (width * height) as usize;
+    assert!(
+        mismatched as f64 <= tolerance * pixels as f64,
+        "{} with colouring {} and shading {}: {mismatched} of {pixels} pixels differ",
+        fractal.name(),
+        uniform_data.colouring,
+        uniform_data.shading
+    );
+    gpu
+}
@@ -0,0 +1,110 @@
+use fractal::cpu::ColourTables;
+use fractal::formula::{Formula, FormulaError, Function};
+use fractal::fractals::{Custom, DEFAULT_FORMULA};
+use fractal::histogram::Equaliser;
+use fractal::named::Named;
+use fractal::palette::Table;
+use fractal::{FRACTALS, UniformData, Viewport, fractals};
+
+fn assert_near(a: [f32; 2], b: [f32; 2]) {
+    let error = (a[0] - b[0]).hypot(a[1] - b[1]);
+    assert!(error < 1e-5, "{a:?} is not {b:?}");
+}
+
+fn syntax_error(text: &str) -> (usize, usize) {
+    match Formula::parse(text) {
+        Err(FormulaError::Syntax { line, column, .. }) => (line, column),
+        other => panic!("{text:?} gave {other:?}"),
+    }
+}
+
+#[test]
+fn formulas_evaluate_on_the_cpu() {
+    let formula = Formula::parse("z = z^2 + c").unwrap();
+    assert_near(formula.start([1.0, 2.0], [0.0, 0.0]), [0.0, 0.0]);
+    assert_near(
+        formula.step([1.0, 2.0], [0.5, 0.0], [0.0, 0.0]),
+        [-2.5, 4.0],
+    );
+
+    let formula = Formula::parse("z0 = pixel\nz = conj(z) * i - 2 / c").unwrap();
+    assert_near(formula.start([0.0, 0.0], [3.0, -1.0]), [3.0, -1.0]);
+    assert_near(
+        formula.step([3.0, -1.0], [0.0, 2.0], [0.0, 0.0]),
+        [-1.0, 4.0],
+    );
+
+    // Powers bind tighter than negation and group to the right.
+    let formula = Formula::parse("z = -z^2^2").unwrap();
+    assert_near(
+        formula.step([2.0, 0.0], [0.0, 0.0], [0.0, 0.0]),
+        [-16.0, 0.0],
+    );
+}
+
+#[test]
+fn every_function_compiles() {
+    for function in Function::ALL {
+        let text = format!("z0 = c; z = {}(z) + c", function.name());
+        let formula = Formula::parse(&text).unwrap_or_else(|e| panic!("{text}: {e}"));
+        assert!(formula.wgsl().contains("formula_step"));
+    }
+    Formula::parse("z = (z^3 + c - 1) / (3 * z^2) + z^2.5 + e^(pi * i)").unwrap();
+}
+
+#[test]
+fn syntax_errors_give_their_position() {
+    assert_eq!(syntax_error("z = z^2 + + c"), (1, 11));
+    assert_eq!(syntax_error("z = foo(z)"), (1, 5));
+    assert_eq!(syntax_error("z0 = c\nz = (z + c"), (2, 11));
+    // z has no value before the first iteration.
+    assert_eq!(syntax_error("z0 = z; z = z"), (1, 6));
+    // Only z and z0 can be assigned, and z must be.
+    assert_eq!(syntax_error("c = z"), (1, 1));
+    // Nesting stops well before the stack or naga's recursion limit runs out.
+    let brackets = format!("z = {}z{}", "(".repeat(3000), ")".repeat(3000));
+    assert_eq!(syntax_error(&brackets), (1, 69));
+    let sum = format!("z = {}", vec!["z"; 3000].join("+"));
+    assert_eq!(syntax_error(&sum), (1, 132));
+    assert!(matches!(
+        Formula::parse("z0 = c"),
+        Err(FormulaError::Syntax { .. })
+    ));
+}
+
+#[test]
+fn formula_fractals_keep_their_own_formula() {
+    let registered = FRACTALS[fractals::find("formula").unwrap()];
+    let mandelbrot = FRACTALS[fractals::find("mandelbrot").unwrap()];
+    let cubic = Custom::new(Formula::parse("z = z^3 + c").unwrap());
+    assert_eq!(
+        cubic.formula().wgsl(),
+        Formula::parse("z = z^3 + c").unwrap().wgsl()
+    );
+    assert_eq!(
+        Custom::default().formula().wgsl(),
+        Formula::parse(DEFAULT_FORMULA).unwrap().wgsl()
+    );
+
+    // The cubic stands in for the registered formula fractal only.
+    assert_eq!(cubic.stand_in(mandelbrot).name(), "mandelbrot");
+    let uniforms = UniformData {
+        resolution: [1.0, 1.0],
+        params: registered.default_params(),
+        ..UniformData::from_viewport(&Viewport::new(0.0, [0.0, 0.0]).unwrap())
+    };
+    // c = -1.5, which z^2 + c keeps but z^3 + c lets escape.
+    let uv = [0.0, 0.5];
+    let orbit = Default::default();
+    let (palette, equaliser) = (Table::default(), Equaliser::default());
+    let tables = ColourTables {
+        palette: &palette,
+        equaliser: &equaliser,
+    };
+    assert_ne!(
+        cubic
+            .stand_in(registered)
+            .sample(uv, &uniforms, &orbit, tables),
+        registered.sample(uv, &uniforms, &orbit, tables)
+    );
+}
@@ -0,0 +1,332 @@
+//! Golden-image regression tests for the fractal shaders.
+//!
+//! Each case is rendered at fixed uniforms and compared with a checked-in
+//! reference image in `tests/golden`. On failure the rendered image and a
+//! diff image are written to `target/golden`. Run with `FRACTAL_BLESS=1` to
+//! (re)generate the reference images after an intended change.
+
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use fractal::export::write_png;
+use fractal::fractals::{JuliaPath, NovaColouring};
+use fractal::palette::Table;
+use fractal::perturbation::Orbit;
+use fractal::tiled::TileGrid;
+use fractal::{FRACTALS, Precision, UniformData, Viewport, cpu, fractals};
+
+mod common;
+
+use common::MISMATCH_TOLERANCE;
+
+const WIDTH: u32 = 128;
+const HEIGHT: u32 = 128;
+
+struct Case {
+    name: &'static str,
+    bundle_idx: usize,
+    uniform_data: UniformData,
+    /// The view in `uniform_data`, at full precision.
+    viewport: Viewport,
+}
+
+impl Case {
+    /// A case for the named fractal with its default parameters.
+    fn new(name: &'static str, uniform_data: UniformData) -> Self {
+        let bundle_idx = fractals::find(name).unwrap();
+        Self {
+            name,
+            bundle_idx,
+            uniform_data: UniformData {
+                resolution: [WIDTH as f32, HEIGHT as f32],
+                params: FRACTALS[bundle_idx].default_params(),
+                ..uniform_data
+            },
+            viewport: Viewport::new(
+                uniform_data.zoom,
+                uniform_data.offset.map(|offset| offset as f64),
+            )
+            .unwrap(),
+        }
+    }
+
+    /// Look through `viewport` rather than the zoom and offset in the case's
+    /// uniforms.
+    fn view(mut self, viewport: Viewport) -> Self {
+        let view = UniformData::from_viewport(&viewport);
+        self.uniform_data.zoom = view.zoom;
+        self.uniform_data.offset = view.offset;
+        self.uniform_data.offset_lo = view.offset_lo;
+        self.viewport = viewport;
+        self
+    }
+
+    /// Store the reference image under another name, for a second case of
+    /// the same fractal.
+    fn named(self, name: &'static str) -> Self {
+        Self { name, ..self }
+    }
+
+    fn param(mut self, name: &str, value: f32) -> Self {
+        let idx = FRACTALS[self.bundle_idx].param_index(name).unwrap();
+        self.uniform_data.params[idx] = value;
+        self
+    }
+
+    fn orbit(&self) -> Orbit {
+        FRACTALS[self.bundle_idx]
+            .reference(
+                &self.viewport,
+                &self.uniform_data.params,
+                WIDTH as f32 / HEIGHT as f32,
+            )
+            .map(|reference| reference.orbit())
+            .unwrap_or_default()
+    }
+}
+
+fn cases() -> Vec<Case> {
+    vec![
+        Case::new(
+            "mandelbrot",
+            UniformData {
+                time: 20.0,
+                ..UniformData::new()
+            },
+        )
+        .param("demo", 1.0),
+        // The same neighbourhood through the view rather than the demo.
+        Case::new("mandelbrot", UniformData::new())
+            .view(Viewport::new(12.0, [1.006, -0.2506]).unwrap())
+            .named("mandelbrot_view"),
+        Case::new(
+            "julia",
+            UniformData {
+                time: 60.0,
+                offset: [0.1, -0.1],
+                ..UniformData::new()
+            },
+        ),
+        // c held at a point picked from the Mandelbrot set.
+        Case::new(
+            "julia",
+            UniformData {
+                time: 60.0,
+                ..UniformData::new()
+            },
+        )
+        .named("julia_fixed")
+        .param("path", JuliaPath::Fixed as u32 as f32)
+        .param("c_x", -0.8)
+        .param("c_y", 0.156),
+        Case::new(
+            "nova",
+            UniformData {
+                time: 3.0,
+                zoom: -5.0,
+                ..UniformData::new()
+            },
+        ),
+        // Plain, over-relaxed Newton's method on a quartic, by speed.
+        Case::new("nova", UniformData::new())
+            .named("nova_quartic")
+            .param("amplitude", 0.0)
+            .param("relaxation", 1.3)
+            .param("colouring", NovaColouring::Speed as u32 as f32)
+            .param("degree", 4.0)
+            .param("root1_x", 1.0)
+            .param("root1_y", 0.5)
+            .param("root2_x", -1.0)
+            .param("root2_y", 0.5)
+            .param("root3_x", 0.0)
+            .param("root3_y", -1.0)
+            .param("root4_x", 0.2)
+            .param("root4_y", 0.0),
+        // Few iterations, as long orbits along the hull are chaotic.
+        Case::new("burning_ship", UniformData::new())
+            .view(Viewport::new(-0.5, [0.37, -0.53]).unwrap())
+            .param("max_iterations", 100.0),
+        Case::new("burning_ship_julia", UniformData::new()),
+        Case::new("tricorn_julia", UniformData::new()),
+        // A real exponent between the integer powers.
+        Case::new(
+            "multibrot",
+            UniformData {
+                zoom: 0.0,
+                ..UniformData::new()
+            },
+        )
+        .param("exponent", 2.5),
+        // The default formula, z = z^2 + c, as a Julia set.
+        Case::new(
+            "formula",
+            UniformData {
+                zoom: 0.0,
+                ..UniformData::new()
+            },
+        )
+        .param("julia", 1.0),
+        // Past f32 resolution: the c = 1/4 cauliflower's edge, which is a
+        // single flat colour in f32. c is exact so both renderers agree.
+        Case::new(
+            "julia",
+            UniformData {
+                precision_mode: Precision::DoubleSingle as u32,
+                ..UniformData::new()
+            },
+        )
+        .view(Viewport::new(100.0, [-0.5623016955564457, 0.9371694925940761]).unwrap())
+        .named("julia_double_single")
+        .param("radius", 0.25),
+        // Past f32 resolution on the antenna of the set.
+        Case::new(
+            "mandelbrot",
+            UniformData {
+                precision_mode: Precision::DoubleSingle as u32,
+                ..UniformData::new()
+            },
+        )
+        .view(Viewport::new(100.0, [1.9, 0.0]).unwrap())
+        .named("mandelbrot_double_single")
+        .param("max_iterations", 100.0),
+        // Also past f32 resolution, in seahorse valley.
+        Case::new("mandelbrot_deep", UniformData::new())
+            .view(Viewport::new(100.0, [0.743643887037151, 0.131825904205330]).unwrap())
+            .param("max_iterations", 1000.0),
+    ]
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{name}.png"))
+}
+
+fn output_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("target/golden")
+        .join(format!("{name}.png"))
+}
+
+fn save(path: &Path, width: u32, height: u32, pixels: &[u8]) {
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    let grid = TileGrid::new(width, height, width.max(height));
+    write_png(path, &grid, |_| pixels.to_vec()).unwrap();
+}
+
+fn load(path: &Path) -> Option<(u32, u32, Vec<u8>)> {
+    let decoder = png::Decoder::new(File::open(path).ok()?);
+    let mut reader = decoder.read_info().unwrap();
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).unwrap();
+    assert_eq!(info.color_type, png::ColorType::Rgba, "{}", path.display());
+    pixels.truncate(info.buffer_size());
+    Some((info.width, info.height, pixels))
+}
+
+/// Compare `pixels` with the reference image for `case`, returning a
+/// description of the failure if they differ.
+fn check(case: &Case, renderer: &str, pixels: &[u8]) -> Result<(), String> {
+    let golden = golden_path(case.name);
+
+    if std::env::var_os("FRACTAL_BLESS").is_some() {
+        save(&golden, WIDTH, HEIGHT, pixels);
+        return Ok(());
+    }
+
+    let Some((width, height, expected)) = load(&golden) else {
+        return Err(format!(
+            "{}: missing {}, run with FRACTAL_BLESS=1 to create it",
+            case.name,
+            golden.display()
+        ));
+    };
+    if (width, height) != (WIDTH, HEIGHT) {
+        return Err(format!(
+            "{}: reference is {width}x{height}, expected {WIDTH}x{HEIGHT}",
+            case.name
+        ));
+    }
+
+    let mut mismatched = 0;
+    let mut diff = Vec::with_capacity(pixels.len());
+    for (actual, expected) in pixels.chunks_exact(4).zip(expected.chunks_exact(4)) {
+        if common::differ(actual, expected) {
+            mismatched += 1;
+            diff.extend_from_slice(&[255, 0, 255, 255]);
+        } else {
+            // Dimmed copy of the reference so mismatches stand out.
+            diff.extend(expected[..3].iter().map(|c| c / 4));
+            diff.push(255);
+        }
+    }
+
+    let fraction = mismatched as f64 / (WIDTH * HEIGHT) as f64;
+    if fraction <= MISMATCH_TOLERANCE {
+        return Ok(());
+    }
+
+    let name = format!("{}.{renderer}", case.name);
+    let actual_path = output_path(&format!("{name}.actual"));
+    let diff_path = output_path(&format!("{name}.diff"));
+    save(&actual_path, WIDTH, HEIGHT, pixels);
+    save(&diff_path, WIDTH, HEIGHT, &diff);
+
+    Err(format!(
+        "{name}: {:.2}% of pixels differ from {} (see {} and {})",
+        fraction * 100.0,
+        golden.display(),
+        actual_path.display(),
+        diff_path.display()
+    ))
+}
+
+fn assert_all(failures: Vec<String>) {
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+#[test]
+fn gpu_matches_golden_images() {
+    let Some(mut headless) = common::headless() else {
+        return;
+    };
+
+    let failures = cases()
+        .iter()
+        .filter_map(|case| {
+            let pixels = headless.draw(
+                WIDTH,
+                HEIGHT,
+                case.uniform_data,
+                &case.orbit(),
+                case.bundle_idx,
+            );
+            check(case, "gpu", &pixels).err()
+        })
+        .collect();
+    assert_all(failures);
+}
+
+#[test]
+fn cpu_matches_golden_images() {
+    // Blessing is done from the GPU output only.
+    if std::env::var_os("FRACTAL_BLESS").is_some() {
+        return;
+    }
+
+    let failures = cases()
+        .iter()
+        .filter_map(|case| {
+            let pixels = cpu::render(
+                WIDTH,
+                HEIGHT,
+                &case.uniform_data,
+                &case.orbit(),
+                FRACTALS[case.bundle_idx],
+                &Table::default(),
+            );
+            check(case, "cpu", &pixels).err()
+        })
+        .collect();
+    assert_all(failures);
+}
@@ -0,0 +1,126 @@
+use std::sync::Arc;
+
+use fractal::histogram::{
+    Colouring, EQUALISE_INTERVAL, Equalised, Equaliser, QUANTILES, SAMPLE_DIMENSION, sample_size,
+    value_intensity,
+};
+use fractal::{FRACTALS, UniformData, fractals};
+
+mod common;
+
+use common::MISMATCH_TOLERANCE;
+
+#[test]
+fn default_equaliser_changes_nothing() {
+    let equaliser = Equaliser::default();
+    for intensity in [0.0, 0.1, 0.5, 0.73, 1.0] {
+        assert!((equaliser.equalise(intensity) - intensity).abs() < 1e-6);
+    }
+    assert_eq!(equaliser.equalise(-1.0), 0.0);
+    assert_eq!(equaliser.equalise(2.0), 1.0);
+}
+
+#[test]
+fn equalisers_spread_values_evenly() {
+    // Bunched up near 0.1, with a few far out.
+    let values = (0..1000).map(|k| 0.1 + (k as f32 / 1000.0).powi(8));
+    let equaliser = Equaliser::from_values(values);
+    assert_eq!(equaliser.quantiles.len(), QUANTILES);
+    assert_eq!(equaliser.quantiles[0], 0.1);
+    assert!(equaliser.quantiles.is_sorted());
+
+    // Half the values are below the median, however close it is to the lowest.
+    let median = 0.1 + 0.5f32.powi(8);
+    assert!((equaliser.equalise(median) - 0.5).abs() < 1e-2);
+    assert_eq!(equaliser.equalise(0.1), 0.0);
+    assert_eq!(equaliser.equalise(1.1), 1.0);
+
+    assert_eq!(Equaliser::from_values([]), Equaliser::default());
+}
+
+#[test]
+fn shared_intensities_equalise_to_the_top_of_their_share() {
+    // A quarter of the view at 0.2, half at 0.4 and a quarter at 0.6.
+    let values = [(0.2, 25), (0.4, 50), (0.6, 25)]
+        .into_iter()
+        .flat_map(|(intensity, count)| vec![intensity; count]);
+    let equaliser = Equaliser::from_values(values);
+    assert_eq!(equaliser.equalise(0.2), 0.0);
+    let share = equaliser.equalise(0.4);
+    assert!((share - 0.75).abs() < 1e-2, "{share}");
+    assert_eq!(equaliser.equalise(0.6), 1.0);
+}
+
+#[test]
+fn values_stand_apart_from_colours() {
+    for intensity in [0.0, 0.123_456_7, 0.5, 1.0] {
+        let value = value_intensity(-1.0 - intensity, f32::EPSILON).unwrap();
+        assert!(value <= intensity && intensity - value < 1e-6, "{value}");
+    }
+    // Black inside the set, and any other colour, never picked one.
+    assert_eq!(value_intensity(0.0, f32::EPSILON), None);
+    assert_eq!(value_intensity(1.0, f32::EPSILON), None);
+}
+
+#[test]
+fn samples_keep_their_shape() {
+    assert_eq!(sample_size([100.0, 50.0]), (100, 50));
+    assert_eq!(sample_size([1920.0, 1080.0]), (SAMPLE_DIMENSION, 144));
+    assert_eq!(sample_size([10.0, 4000.0]), (1, SAMPLE_DIMENSION));
+    assert_eq!(sample_size([0.0, 0.0]), (1, 1));
+}
+
+#[test]
+fn animations_are_equalised_again_as_they_move() {
+    let at = |name: &str, time: f32| {
+        let bundle_idx = fractals::find(name).unwrap();
+        let fractal = FRACTALS[bundle_idx];
+        let uniform_data = UniformData {
+            time,
+            params: fractal.default_params(),
+            colouring: Colouring::Histogram as u32,
+            ..UniformData::from_viewport(&fractal.default_view())
+        };
+        Equalised::new(uniform_data, bundle_idx, 0)
+    };
+
+    // Julia's constant circles by default.
+    assert!(at("julia", 0.0) != at("julia", 5.0));
+    assert!(at("julia", 0.0) == at("julia", 0.5 * EQUALISE_INTERVAL));
+    // The Mandelbrot set stands still unless its demo is on.
+    assert!(at("mandelbrot", 0.0) == at("mandelbrot", 5.0));
+}
+
+#[test]
+fn gpu_and_cpu_equalise_alike() {
+    let Some(mut headless) = common::headless() else {
+        return;
+    };
+
+    let bundle_idx = fractals::find("mandelbrot").unwrap();
+    let fractal = FRACTALS[bundle_idx];
+    let (width, height) = (96, 64);
+    let direct = UniformData {
+        resolution: [width as f32, height as f32],
+        params: fractal.default_params(),
+        ..UniformData::from_viewport(&fractal.default_view())
+    };
+    let histogram = UniformData {
+        colouring: Colouring::Histogram as u32,
+        ..direct
+    };
+
+    let orbit = Default::default();
+    let gpu = common::assert_gpu_matches_cpu(
+        &mut headless,
+        bundle_idx,
+        histogram,
+        &orbit,
+        &Arc::default(),
+        MISMATCH_TOLERANCE,
+    );
+
+    // Equalising has to have changed the picture.
+    let plain = headless.draw(width, height, direct, &orbit, bundle_idx);
+    assert_ne!(gpu, plain);
+}
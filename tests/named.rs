@@ -0,0 +1,30 @@
+use fractal::formula::Function;
+use fractal::histogram::Colouring;
+use fractal::named::Named;
+use fractal::palette::Interpolation;
+use fractal::shading::Shading;
+
+fn assert_named<T: Named + std::fmt::Debug>() {
+    for &option in T::ALL {
+        assert_eq!(T::from_name(option.name()), Some(option));
+    }
+    let mut option = T::ALL[0];
+    for expected in T::ALL.iter().cycle().skip(1).take(T::ALL.len()) {
+        option = option.next();
+        assert_eq!(option, *expected);
+    }
+}
+
+#[test]
+fn options_are_found_by_name_and_cycle() {
+    assert_named::<Shading>();
+    assert_named::<Colouring>();
+    assert_named::<Interpolation>();
+    assert_named::<Function>();
+
+    assert_eq!(Shading::from_name("phong"), None);
+    // Values has a name but is not one to choose from.
+    assert_eq!(Colouring::Values.name(), "values");
+    assert_eq!(Colouring::from_name("values"), None);
+    assert_eq!(Colouring::Values.next(), Colouring::Direct);
+}
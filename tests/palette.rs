@@ -0,0 +1,312 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use fractal::palette::{self, Format, Interpolation, Palette, PaletteError, TABLE_SIZE};
+use fractal::{FRACTALS, UniformData, fractals};
+
+mod common;
+
+use common::MISMATCH_TOLERANCE;
+
+/// Within the step between neighbouring table colours.
+fn assert_near(a: [f32; 3], b: [f32; 3]) {
+    let error = a
+        .iter()
+        .zip(b)
+        .map(|(a, b)| (a - b).abs())
+        .fold(0.0, f32::max);
+    assert!(error < 5e-3, "{a:?} is not {b:?}");
+}
+
+fn syntax_error(text: &str) -> usize {
+    match Palette::parse("test", text) {
+        Err(PaletteError::Syntax { line, .. }) => line,
+        other => panic!("{text:?} gave {other:?}"),
+    }
+}
+
+fn parse_as(format: Format, text: &str) -> Result<Vec<Palette>, PaletteError> {
+    format.parse("test", text)
+}
+
+fn format_error(format: Format, text: &str) -> usize {
+    match parse_as(format, text) {
+        Err(PaletteError::Syntax { line, .. }) => line,
+        other => panic!("{text:?} gave {other:?}"),
+    }
+}
+
+/// Colours the same from 0 to `end`, to within 8-bit rounding.
+fn assert_same_colours(expected: &Palette, saved: &Palette, end: f32) {
+    for k in 0..=64 {
+        let position = k as f32 / 64.0;
+        if position > end {
+            break;
+        }
+        let (a, b) = (
+            expected.colour_at(position, Interpolation::LinearRgb),
+            saved.colour_at(position, Interpolation::LinearRgb),
+        );
+        let error = a
+            .iter()
+            .zip(b)
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0, f32::max);
+        assert!(
+            error < 1e-2,
+            "{a:?} is not {b:?} at {position} in {}",
+            saved.name
+        );
+    }
+}
+
+const GGR: &str = "GIMP Gradient
+Name: Sunset
+2
+0.000000 0.250000 0.500000 0.000000 0.000000 0.000000 1.000000 1.000000 0.000000 0.000000 1.000000 0 0
+0.500000 0.750000 1.000000 1.000000 0.000000 0.000000 1.000000 1.000000 1.000000 1.000000 1.000000 1 0 0 0
+";
+
+const UGR: &str = "; two gradients
+Blues {
+gradient:
+  title=\"Deep blues\" smooth=no
+  index=100 color=16711680
+  index=300 color=0
+opacity:
+  smooth=no index=0 opacity=255
+}
+Shifted {
+gradient:
+  title=\"Shifted\" smooth=yes rotation=-100
+  index=100 color=255
+  index=300 color=65280
+}
+";
+
+#[test]
+fn palettes_parse_hex_and_linear_colours() {
+    let palette =
+        Palette::parse("test", "// comment\n\n0 #000000\n0.5 #ffffff\n1 0.5 0.25 1").unwrap();
+    assert_eq!(palette.stops.len(), 3);
+    assert_eq!(palette.stops[1].position, 0.5);
+    assert_near(palette.stops[1].colour, [1.0, 1.0, 1.0]);
+    assert_near(palette.stops[2].colour, [0.5, 0.25, 1.0]);
+    // #808080 is sRGB, about a fifth of full brightness in linear light.
+    let grey = Palette::parse("grey", "0 #808080").unwrap();
+    assert_near(grey.stops[0].colour, [0.2158; 3]);
+
+    for palette in palette::built_in() {
+        assert!(!palette.stops.is_empty(), "{} has no stops", palette.name);
+    }
+}
+
+#[test]
+fn syntax_errors_give_their_line() {
+    assert_eq!(syntax_error("0 #000000\nhalf #ffffff"), 2);
+    assert_eq!(syntax_error("0 #000000\n\n1.5 #ffffff"), 3);
+    assert_eq!(syntax_error("0.5 #000000\n0.25 #ffffff"), 2);
+    assert_eq!(syntax_error("0 #00000"), 1);
+    assert_eq!(syntax_error("0 1 1"), 1);
+    assert_eq!(syntax_error("// nothing\n"), 1);
+}
+
+#[test]
+fn tables_repeat_and_shift() {
+    let classic = &palette::built_in()[0];
+    let table = classic.bake(Interpolation::LinearRgb);
+    assert_eq!(table.colours.len(), TABLE_SIZE);
+
+    assert_near(table.lookup(0.25, 0.0, 1.0), [0.2, 0.0, 0.6]);
+    assert_near(table.lookup(0.0, 0.0, 1.0), [0.0, 0.0, 0.0]);
+    assert_near(table.lookup(1.0, 0.0, 1.0), [1.0, 1.0, 0.5]);
+    // Twice as dense reaches the same colour at half the intensity and ends
+    // on the last colour rather than wrapping.
+    assert_near(table.lookup(0.125, 0.0, 2.0), [0.2, 0.0, 0.6]);
+    assert_near(table.lookup(1.0, 0.0, 2.0), [1.0, 1.0, 0.5]);
+    // The offset shifts the palette and wraps round.
+    assert_near(table.lookup(0.0, 0.25, 1.0), [0.2, 0.0, 0.6]);
+    assert_near(table.lookup(0.5, 0.75, 1.0), [0.2, 0.0, 0.6]);
+}
+
+#[test]
+fn interpolation_modes_blend_differently() {
+    let red_to_blue = Palette::parse("test", "0 1 0 0\n1 0 0 1").unwrap();
+
+    let rgb = red_to_blue.colour_at(0.5, Interpolation::LinearRgb);
+    assert_near(rgb, [0.5, 0.0, 0.5]);
+
+    // Halfway round the hue circle the short way, through magenta.
+    let hsv = red_to_blue.colour_at(0.5, Interpolation::Hsv);
+    assert_near(hsv, [1.0, 0.0, 1.0]);
+
+    // OKLab keeps the ends and goes through neither of the others.
+    let oklab = |t| red_to_blue.colour_at(t, Interpolation::Oklab);
+    assert_near(oklab(0.0), [1.0, 0.0, 0.0]);
+    assert_near(oklab(1.0), [0.0, 0.0, 1.0]);
+    let middle = oklab(0.5);
+    assert!(middle != rgb && middle != hsv);
+    assert!(middle[0] > middle[1] && middle[2] > middle[1], "{middle:?}");
+}
+
+#[test]
+fn gpu_and_cpu_colour_alike() {
+    let Some(mut headless) = common::headless() else {
+        return;
+    };
+
+    let fire = Arc::new(Palette::find("fire").unwrap().bake(Interpolation::Hsv));
+
+    let bundle_idx = fractals::find("julia").unwrap();
+    let fractal = FRACTALS[bundle_idx];
+    let (width, height) = (96, 96);
+    let uniform_data = UniformData {
+        resolution: [width as f32, height as f32],
+        params: fractal.default_params(),
+        palette_offset: 0.3,
+        palette_density: 3.0,
+        ..UniformData::from_viewport(&fractal.default_view())
+    };
+
+    common::assert_gpu_matches_cpu(
+        &mut headless,
+        bundle_idx,
+        uniform_data,
+        &Default::default(),
+        &fire,
+        MISMATCH_TOLERANCE,
+    );
+}
+
+#[test]
+fn fractint_maps_spread_their_colours_evenly() {
+    let palettes = parse_as(Format::Map, "0 0 0 black\n\n255 0 0\n255 255 255 white\n").unwrap();
+    let map = &palettes[0];
+    assert_eq!(map.name, "test");
+    let positions: Vec<f32> = map.stops.iter().map(|stop| stop.position).collect();
+    assert_eq!(positions, [0.0, 0.5, 1.0]);
+    assert_near(map.stops[1].colour, [1.0, 0.0, 0.0]);
+
+    assert_eq!(format_error(Format::Map, "0 0 0\n12 300 4"), 2);
+    assert_eq!(format_error(Format::Map, "0 0 0\n\n1 2"), 3);
+    assert_eq!(format_error(Format::Map, "\n"), 1);
+}
+
+#[test]
+fn gimp_gradients_follow_their_segments() {
+    let gradient = &parse_as(Format::Ggr, GGR).unwrap()[0];
+    assert_eq!(gradient.name, "Sunset");
+    assert_near(gradient.colour_at(0.0, Interpolation::LinearRgb), [0.0; 3]);
+    assert_near(
+        gradient.colour_at(0.5, Interpolation::LinearRgb),
+        [1.0, 0.0, 0.0],
+    );
+    assert_near(gradient.colour_at(1.0, Interpolation::LinearRgb), [1.0; 3]);
+    assert_near(
+        gradient.colour_at(0.25, Interpolation::LinearRgb),
+        [0.5, 0.0, 0.0],
+    );
+    // The curved second segment is half way in sRGB at its midpoint.
+    let half = palette::decode_srgb(0.5);
+    let middle = gradient.colour_at(0.75, Interpolation::LinearRgb);
+    assert_near(middle, [1.0, half, half]);
+
+    assert_eq!(format_error(Format::Ggr, "GIMP Palette\n"), 1);
+    assert_eq!(
+        format_error(Format::Ggr, "GIMP Gradient\nName: x\ntwo\n"),
+        3
+    );
+    assert_eq!(
+        format_error(
+            Format::Ggr,
+            &GGR.replace("0.500000 0.750000", "0.600000 0.750000")
+        ),
+        5
+    );
+    assert_eq!(
+        format_error(Format::Ggr, &GGR.replace(" 1 0 0 0\n", " 9 0\n")),
+        5
+    );
+    assert_eq!(format_error(Format::Ggr, &GGR.replace("\n2\n", "\n3\n")), 5);
+}
+
+#[test]
+fn ultra_fractal_gradients_wrap_round() {
+    let gradients = parse_as(Format::Ugr, UGR).unwrap();
+    assert_eq!(gradients.len(), 2);
+
+    let blues = &gradients[0];
+    assert_eq!(blues.name, "Deep blues");
+    // Colours are stored blue first.
+    assert_near(
+        blues.colour_at(0.25, Interpolation::LinearRgb),
+        [0.0, 0.0, 1.0],
+    );
+    assert_near(blues.colour_at(0.75, Interpolation::LinearRgb), [0.0; 3]);
+    // The ends are half way from the last colour back round to the first.
+    let half = palette::decode_srgb(0.5);
+    assert_near(
+        blues.colour_at(0.0, Interpolation::LinearRgb),
+        [0.0, 0.0, half],
+    );
+    assert_near(
+        blues.colour_at(1.0, Interpolation::LinearRgb),
+        [0.0, 0.0, half],
+    );
+
+    // Rotation moves every colour along.
+    let shifted = &gradients[1];
+    assert_near(
+        shifted.colour_at(0.0, Interpolation::LinearRgb),
+        [1.0, 0.0, 0.0],
+    );
+    assert_near(
+        shifted.colour_at(0.5, Interpolation::LinearRgb),
+        [0.0, 1.0, 0.0],
+    );
+
+    assert_eq!(
+        format_error(Format::Ugr, "a {\ngradient:\n  index=1 color=1\n"),
+        1
+    );
+    assert_eq!(format_error(Format::Ugr, "a {\ngradient:\n  color=1\n}"), 3);
+    assert_eq!(format_error(Format::Ugr, "a {\ngradient:\n}"), 3);
+    assert_eq!(format_error(Format::Ugr, "index=1 color=1"), 1);
+}
+
+#[test]
+fn palettes_survive_saving_in_every_format() {
+    let directory = std::env::temp_dir().join(format!("fractal_palettes_{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+
+    let gradients = parse_as(Format::Ugr, UGR).unwrap();
+    for palette in palette::built_in().iter().chain(&gradients) {
+        for extension in ["palette", "map", "ggr", "ugr"] {
+            let path = directory.join(format!("saved_{}.{extension}", palette.name));
+            palette.save(&path, Interpolation::LinearRgb).unwrap();
+            let loaded = Palette::load(&path).unwrap();
+            // The end of a .ugr wraps round to its start, so its last stop
+            // moves in a little.
+            let end = if extension == "ugr" { 0.875 } else { 1.0 };
+            assert_same_colours(palette, &loaded, end);
+        }
+    }
+
+    std::fs::write(directory.join("two.ugr"), UGR).unwrap();
+    let path = directory.join("two.ugr").display().to_string();
+    assert_eq!(Palette::find(&path).unwrap().name, "Deep blues");
+    assert_eq!(
+        Palette::find(&format!("{path}#Shifted")).unwrap().name,
+        "Shifted"
+    );
+    assert!(matches!(
+        Palette::find(&format!("{path}#Reds")),
+        Err(PaletteError::Missing { .. })
+    ));
+    assert!(matches!(
+        Palette::load(Path::new("no/such.map")),
+        Err(PaletteError::Io { .. })
+    ));
+
+    std::fs::remove_dir_all(&directory).unwrap();
+}
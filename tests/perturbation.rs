@@ -0,0 +1,128 @@
+mod common;
+
+use fractal::cpu::{self, ColourTables};
+use fractal::histogram::Equaliser;
+use fractal::palette::Table;
+use fractal::perturbation::{Orbit, Reference};
+use fractal::viewport::{BigFloat, Scale};
+use fractal::{FRACTALS, UniformData, Viewport, fractals};
+
+/// Period-3 minibrot on the real axis, inside the set so the orbit runs to
+/// the iteration limit.
+const CENTER: [f64; 2] = [-1.7497219229080458, 0.0];
+/// Nucleus of the period-3 minibrot on the real axis, which returns to 0
+/// every third iteration.
+const NUCLEUS: f64 = -1.7548776662466927;
+
+fn center() -> [BigFloat; 2] {
+    CENTER.map(|x| BigFloat::try_from(x).unwrap())
+}
+
+fn mul(a: [f64; 2], b: [f64; 2]) -> [f64; 2] {
+    [a[0] * b[0] - a[1] * b[1], a[0] * b[1] + a[1] * b[0]]
+}
+
+fn add(a: [f64; 2], b: [f64; 2]) -> [f64; 2] {
+    [a[0] + b[0], a[1] + b[1]]
+}
+
+#[test]
+fn series_skips_more_of_deeper_views() {
+    let skip = |half_extent: f64| {
+        Reference::new(center(), [half_extent; 2], 10_000)
+            .orbit()
+            .series
+            .skip
+    };
+
+    let shallow = skip(1e-3);
+    let deep = skip(1e-12);
+    assert!(deep > 100, "skipped {deep}");
+    assert!(deep > shallow, "skipped {deep} deep and {shallow} shallow");
+}
+
+#[test]
+fn series_matches_iterating_at_the_corner() {
+    let orbit = Reference::new(center(), [1e-12; 2], 10_000).orbit();
+    let series = orbit.series;
+    let dc = [1e-12, 1e-12];
+
+    let mut dz = [0.0, 0.0];
+    for point in &orbit.points[..series.skip as usize] {
+        let two_z = [2.0 * point[0] as f64, 2.0 * point[1] as f64];
+        dz = add(mul(add(two_z, dz), dz), dc);
+    }
+
+    let u = [dc[0] / series.scale as f64, dc[1] / series.scale as f64];
+    let [a, b, c] = series.coefficients.map(|k| [k[0] as f64, k[1] as f64]);
+    let estimate = mul(add(mul(add(mul(c, u), b), u), a), u);
+
+    let error = (estimate[0] - dz[0]).hypot(estimate[1] - dz[1]);
+    let size = dz[0].hypot(dz[1]);
+    assert!(error < 1e-5 * size, "error {error} in {size}");
+}
+
+#[test]
+fn deep_mandelbrot_draws_without_a_reference() {
+    let bundle_idx = fractals::find("mandelbrot_deep").unwrap();
+    let fractal = FRACTALS[bundle_idx];
+    let uniforms = UniformData {
+        resolution: [100.0, 100.0],
+        params: fractal.default_params(),
+        ..UniformData::from_viewport(&Viewport::new(0.0, [0.0, 0.0]).unwrap())
+    };
+    let palette = Table::default();
+    let tables = ColourTables {
+        palette: &palette,
+        equaliser: &Equaliser::default(),
+    };
+    let sample = |uv| fractal.sample(uv, &uniforms, &Orbit::default(), tables);
+
+    // Iterated from 0 as if centred there: 0 is in the set and 1.5 is not.
+    assert_eq!(sample([0.5, 0.5]), [0.0, 0.0, 0.0]);
+    assert_ne!(sample([1.0, 0.5]), [0.0, 0.0, 0.0]);
+}
+
+#[test]
+fn glitched_views_come_out_clean() {
+    let bundle_idx = fractals::find("mandelbrot_deep").unwrap();
+    let fractal = FRACTALS[bundle_idx];
+    // The nucleus is at the middle of a pixel off the centre. That pixel
+    // passes far closer to 0 than the reference and glitches against it.
+    let (side, half_extent) = (64, 1e-7);
+    let offset = 17.0 / side as f64 * half_extent;
+    let center = [NUCLEUS - offset, offset].map(|x| BigFloat::try_from(x).unwrap());
+    let viewport = Viewport::from_center(center, Scale::new(half_extent)).unwrap();
+    let mut params = fractal.default_params();
+    params[fractal.param_index("show_glitches").unwrap()] = 1.0;
+    let uniforms = UniformData {
+        resolution: [side as f32; 2],
+        params,
+        ..UniformData::from_viewport(&viewport)
+    };
+    let reference = fractal.reference(&viewport, &params, 1.0).unwrap();
+    let palette = Table::default();
+    let glitched = |pixels: Vec<u8>| {
+        pixels
+            .chunks_exact(4)
+            .filter(|pixel| pixel[..3] == [255, 0, 255])
+            .count()
+    };
+
+    let single = reference.orbit();
+    let render = |orbit| cpu::render(side, side, &uniforms, orbit, fractal, &palette);
+    assert!(glitched(render(&single)) > 0);
+    let orbit = cpu::orbit(&reference, &uniforms, fractal);
+    assert!(!orbit.secondaries.is_empty());
+    assert_eq!(glitched(render(&orbit)), 0);
+
+    let Some(mut headless) = common::headless() else {
+        return;
+    };
+    headless.set_palette(palette.into());
+    let pixels = headless.draw(side, side, uniforms, &single, bundle_idx);
+    assert!(glitched(pixels) > 0);
+    let orbit = headless.orbit(&reference, uniforms, bundle_idx);
+    let pixels = headless.draw(side, side, uniforms, &orbit, bundle_idx);
+    assert_eq!(glitched(pixels), 0);
+}
@@ -0,0 +1,36 @@
+use fractal::polynomial::{evaluate, roots};
+
+fn assert_near(a: [f64; 2], b: [f64; 2]) {
+    let error = (a[0] - b[0]).hypot(a[1] - b[1]);
+    assert!(error < 1e-9, "{a:?} is not {b:?}");
+}
+
+#[test]
+fn roots_of_unity() {
+    let mut found = roots(&[[1.0, 0.0], [0.0, 0.0], [0.0, 0.0], [-1.0, 0.0]]);
+    found.sort_by(|a, b| a[1].total_cmp(&b[1]));
+
+    let half_root_3 = 3f64.sqrt() / 2.0;
+    assert_eq!(found.len(), 3);
+    assert_near(found[0], [-0.5, -half_root_3]);
+    assert_near(found[1], [1.0, 0.0]);
+    assert_near(found[2], [-0.5, half_root_3]);
+}
+
+#[test]
+fn roots_are_zeros_of_complex_polynomials() {
+    // Leading zeros and a leading coefficient other than 1 are ignored.
+    let coefficients = [[0.0, 0.0], [2.0, -1.0], [0.5, 3.0], [-4.0, 0.0], [1.0, 1.0]];
+    let found = roots(&coefficients);
+
+    assert_eq!(found.len(), 3);
+    for root in found {
+        assert_near(evaluate(&coefficients, root), [0.0, 0.0]);
+    }
+}
+
+#[test]
+fn constants_have_no_roots() {
+    assert!(roots(&[[0.0, 0.0], [2.0, 0.0]]).is_empty());
+    assert!(roots(&[]).is_empty());
+}
@@ -0,0 +1,34 @@
+use fractal::{FRACTALS, Precision, Viewport, fractals};
+
+const RESOLUTION: [f32; 2] = [1920.0, 1080.0];
+
+#[test]
+fn double_single_takes_over_past_f32_resolution() {
+    let shallow = Viewport::new(40.0, [0.5, 0.0]).unwrap();
+    let deep = Viewport::new(80.0, [0.5, 0.0]).unwrap();
+
+    assert_eq!(Precision::required(&shallow, RESOLUTION), Precision::Single);
+    assert_eq!(
+        Precision::required(&deep, RESOLUTION),
+        Precision::DoubleSingle
+    );
+}
+
+#[test]
+fn fractals_without_double_single_stay_single() {
+    let deep = Viewport::new(80.0, [0.5, 0.0]).unwrap();
+    let burning_ship = FRACTALS[fractals::find("burning_ship").unwrap()];
+    let mandelbrot = FRACTALS[fractals::find("mandelbrot").unwrap()];
+
+    let resolve = |fractal, requested| Precision::resolve(requested, fractal, &deep, RESOLUTION);
+    assert_eq!(resolve(burning_ship, None), Precision::Single);
+    assert_eq!(
+        resolve(burning_ship, Some(Precision::DoubleSingle)),
+        Precision::Single
+    );
+    assert_eq!(resolve(mandelbrot, None), Precision::DoubleSingle);
+    assert_eq!(
+        resolve(mandelbrot, Some(Precision::Single)),
+        Precision::Single
+    );
+}
@@ -0,0 +1,74 @@
+//! Reloading shaders edited on disk, as `--watch-shaders` does.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use fractal::perturbation::Orbit;
+use fractal::{FRACTALS, HeadlessState, UniformData, fractals, shader};
+
+mod common;
+
+const SIZE: u32 = 16;
+
+fn draw(headless: &mut HeadlessState, bundle_idx: usize) -> Vec<u8> {
+    let uniform_data = UniformData {
+        resolution: [SIZE as f32, SIZE as f32],
+        params: FRACTALS[bundle_idx].default_params(),
+        ..UniformData::new()
+    };
+    headless.draw(SIZE, SIZE, uniform_data, &Orbit::default(), bundle_idx)
+}
+
+/// A directory holding a copy of the built-in shaders.
+fn shader_directory() -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("fractal_shaders_{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    for name in shader::files() {
+        fs::write(directory.join(name), shader::source(name).as_ref()).unwrap();
+    }
+    directory
+}
+
+fn edit(directory: &Path, name: &'static str, text: &str) {
+    fs::write(directory.join(name), text).unwrap();
+    assert!(shader::load(directory, name).unwrap());
+}
+
+#[test]
+fn broken_shaders_keep_the_last_good_pipeline() {
+    let Some(mut headless) = common::headless() else {
+        return;
+    };
+    let mandelbrot = fractals::find("mandelbrot").unwrap();
+    let original = draw(&mut headless, mandelbrot);
+    let directory = shader_directory();
+    let source = shader::source("mandelbrot.wgsl");
+
+    // Loading unchanged files rebuilds nothing.
+    for name in shader::files() {
+        assert!(!shader::load(&directory, name).unwrap());
+    }
+    assert!(headless.reload_shaders().is_empty());
+
+    edit(
+        &directory,
+        "mandelbrot.wgsl",
+        &format!("{source}\nnot wgsl"),
+    );
+    let errors = headless.reload_shaders();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].fractal, "mandelbrot");
+    assert_eq!(draw(&mut headless, mandelbrot), original);
+
+    let entry = "fn fs_main(vertex_out: VertexOut) -> @location(0) vec4<f32> {";
+    let red = source.replace(
+        entry,
+        &format!("{entry}\n    if (true) {{ return vec4(1.0, 0.0, 0.0, 1.0); }}"),
+    );
+    edit(&directory, "mandelbrot.wgsl", &red);
+    assert!(headless.reload_shaders().is_empty());
+    let pixels = draw(&mut headless, mandelbrot);
+    assert!(pixels.chunks(4).all(|pixel| pixel == [255, 0, 0, 255]));
+
+    fs::remove_dir_all(directory).unwrap();
+}
@@ -0,0 +1,124 @@
+//! Checks on the WGSL sources themselves.
+
+use std::mem::{offset_of, size_of, size_of_val};
+
+use fractal::shader::{PreprocessError, preprocess};
+use fractal::{FRACTALS, UniformData};
+use wgpu::naga;
+
+/// Name, offset and size of the fields of `UniformData` the shaders see.
+fn uniform_fields() -> Vec<(&'static str, usize, usize)> {
+    let data = UniformData::new();
+    macro_rules! fields {
+        ($($name:ident),*) => {
+            vec![$((
+                stringify!($name),
+                offset_of!(UniformData, $name),
+                size_of_val(&data.$name),
+            )),*]
+        };
+    }
+    fields!(
+        time,
+        zoom,
+        offset,
+        resolution,
+        offset_lo,
+        tile,
+        params,
+        precision_mode,
+        one,
+        palette_offset,
+        palette_density,
+        colouring,
+        shading,
+        light_angle
+    )
+}
+
+/// Name, offset and size of the members of `Uniforms`, and the struct's size.
+fn wgsl_fields(module: &naga::Module) -> (Vec<(String, usize, usize)>, usize) {
+    let (members, span) = module
+        .types
+        .iter()
+        .find_map(|(_, ty)| match &ty.inner {
+            naga::TypeInner::Struct { members, span } if ty.name.as_deref() == Some("Uniforms") => {
+                Some((members, *span))
+            }
+            _ => None,
+        })
+        .expect("the shader declares Uniforms");
+    let fields = members
+        .iter()
+        .map(|member| {
+            (
+                member.name.clone().unwrap_or_default(),
+                member.offset as usize,
+                module.types[member.ty].inner.size(module.to_ctx()) as usize,
+            )
+        })
+        .collect();
+    (fields, span as usize)
+}
+
+#[test]
+fn uniforms_match_uniform_data() {
+    let expected: Vec<_> = uniform_fields()
+        .into_iter()
+        .map(|(name, offset, size)| (name.to_string(), offset, size))
+        .collect();
+
+    for fractal in FRACTALS {
+        let source = preprocess(fractal.name(), &fractal.wgsl_source()).unwrap();
+        let module = naga::front::wgsl::parse_str(&source)
+            .unwrap_or_else(|e| panic!("{}: {}", fractal.name(), e.emit_to_string(&source)));
+        let (fields, size) = wgsl_fields(&module);
+
+        assert_eq!(fields, expected, "{}", fractal.name());
+        assert_eq!(size, size_of::<UniformData>(), "{}", fractal.name());
+    }
+}
+
+#[test]
+fn includes_are_pasted_once() {
+    let source = "#include \"prelude.wgsl\"\n#include \"double_single.wgsl\"\n";
+    let output = preprocess("test", source).unwrap();
+
+    // double_single.wgsl includes the prelude too.
+    assert_eq!(output.matches("struct Uniforms").count(), 1);
+    assert_eq!(output.matches("fn ds_add").count(), 1);
+}
+
+#[test]
+fn defines_replace_whole_names_outside_comments() {
+    let source = "\
+#define N 3u
+#define TWICE_N (2u * N)
+let a = N + TWICE_N + N_MORE; // N
+let b = array<vec4<f32>, PARAM_VECTORS>();
+";
+    let output = preprocess("test", source).unwrap();
+    assert_eq!(
+        output,
+        "let a = 3u + (2u * 3u) + N_MORE; // N\nlet b = array<vec4<f32>, 4>();\n"
+    );
+}
+
+#[test]
+fn bad_directives_give_their_position() {
+    let error = |source: &str| preprocess("test", source).unwrap_err();
+
+    assert_eq!(
+        error("let a = 1;\n#include \"missing.wgsl\""),
+        PreprocessError {
+            file: "test".to_string(),
+            line: 2,
+            message: "no shader file missing.wgsl".to_string(),
+        }
+    );
+    assert_eq!(error("#include prelude.wgsl").line, 1);
+    assert_eq!(error("#define A 1\n\n#define A 2").line, 3);
+    assert_eq!(error("#define 1A 1").line, 1);
+    assert_eq!(error("#define A").line, 1);
+    assert_eq!(error("#pragma once").line, 1);
+}
@@ -0,0 +1,159 @@
+use std::f32::consts::{FRAC_PI_2, PI};
+use std::sync::Arc;
+
+use fractal::cpu::ColourTables;
+use fractal::histogram::Equaliser;
+use fractal::palette::Table;
+use fractal::shading::{
+    SHADED_ESCAPE, Shading, distance_estimate, holomorphic_gradient, pixel_size,
+};
+use fractal::{FRACTALS, UniformData, Viewport, cpu, fractals};
+
+mod common;
+
+/// Distance estimate for `z0` iterated under `z^2 + c`, with the
+/// derivative taken by `c` for the Mandelbrot set or by `z0` for Julia sets.
+fn estimate(z0: [f32; 2], c: [f32; 2], julia: bool) -> f32 {
+    let (mut z, mut dz) = (z0, if julia { [1.0, 0.0] } else { [0.0, 0.0] });
+    while z[0] * z[0] + z[1] * z[1] <= SHADED_ESCAPE {
+        let extra = if julia { 0.0 } else { 1.0 };
+        dz = [
+            2.0 * (z[0] * dz[0] - z[1] * dz[1]) + extra,
+            2.0 * (z[0] * dz[1] + z[1] * dz[0]),
+        ];
+        z = [z[0] * z[0] - z[1] * z[1] + c[0], 2.0 * z[0] * z[1] + c[1]];
+    }
+    distance_estimate(z, holomorphic_gradient(z, dz))
+}
+
+fn brightness(colour: [f32; 3]) -> f32 {
+    colour.iter().sum()
+}
+
+#[test]
+fn distance_estimates_are_close_to_the_distance() {
+    // The Mandelbrot set reaches from -2 to 1/4 along the real axis, and the
+    // Julia set for c = 0 is the unit circle.
+    for (z0, c, julia, distance) in [
+        ([0.0, 0.0], [1.0, 0.0], false, 0.75),
+        ([0.0, 0.0], [-3.0, 0.0], false, 1.0),
+        ([2.0, 0.0], [0.0, 0.0], true, 1.0),
+        ([0.0, -1.5], [0.0, 0.0], true, 0.5),
+    ] {
+        let estimate = estimate(z0, c, julia);
+        assert!(
+            (0.25 * distance..=4.0 * distance).contains(&estimate),
+            "{z0:?} for c = {c:?} is {distance} away, estimated {estimate}"
+        );
+    }
+}
+
+#[test]
+fn pixels_span_the_view() {
+    let uniforms = UniformData {
+        resolution: [300.0, 200.0],
+        ..UniformData::from_viewport(&Viewport::new(0.0, [0.0, 0.0]).unwrap())
+    };
+    // The shorter side spans 3 at zoom 0.
+    assert!((pixel_size(&uniforms) - 3.0 / 200.0).abs() < 1e-6);
+}
+
+#[test]
+fn light_falls_on_the_side_facing_it() {
+    let bundle_idx = fractals::find("mandelbrot").unwrap();
+    let fractal = FRACTALS[bundle_idx];
+    let palette = Table::default();
+    let lit_from = |angle: f32| {
+        let uniforms = UniformData {
+            resolution: [100.0, 100.0],
+            params: fractal.default_params(),
+            shading: Shading::Lighting as u32,
+            light_angle: angle,
+            ..UniformData::from_viewport(&Viewport::new(0.0, [0.0, 0.0]).unwrap())
+        };
+        // Above the set at c = 1.2i.
+        let uv = [0.5, 0.5 + 0.5 * 1.2 / 1.5];
+        let tables = ColourTables {
+            palette: &palette,
+            equaliser: &Equaliser::default(),
+        };
+        brightness(fractal.sample(uv, &uniforms, &Default::default(), tables))
+    };
+    assert!(lit_from(FRAC_PI_2) > lit_from(-FRAC_PI_2));
+    assert!(lit_from(FRAC_PI_2) > lit_from(0.0));
+    assert!(lit_from(PI) > lit_from(-FRAC_PI_2));
+}
+
+#[test]
+fn boundaries_darken_only_near_the_set() {
+    let bundle_idx = fractals::find("burning_ship").unwrap();
+    let fractal = FRACTALS[bundle_idx];
+    let (width, height) = (64, 64);
+    let flat = UniformData {
+        resolution: [width as f32, height as f32],
+        params: fractal.default_params(),
+        ..UniformData::from_viewport(&fractal.default_view())
+    };
+    let boundary = UniformData {
+        shading: Shading::Boundary as u32,
+        ..flat
+    };
+
+    let (orbit, palette) = (Default::default(), Table::default());
+    let flat = cpu::render(width, height, &flat, &orbit, fractal, &palette);
+    let boundary = cpu::render(width, height, &boundary, &orbit, fractal, &palette);
+    let darker = flat
+        .chunks(4)
+        .zip(boundary.chunks(4))
+        .filter(|(flat, boundary)| boundary[..3] < flat[..3])
+        .count();
+    assert!(darker > 0, "no pixels were darkened");
+    assert!(darker * 2 < flat.len() / 4, "{darker} pixels were darkened");
+}
+
+#[test]
+fn gpu_and_cpu_shade_alike() {
+    let Some(mut headless) = common::headless() else {
+        return;
+    };
+
+    let (width, height) = (96, 64);
+    let palette = Arc::default();
+    for name in [
+        "mandelbrot",
+        "julia",
+        "mandelbrot_deep",
+        "burning_ship",
+        "tricorn_julia",
+        "multibrot",
+    ] {
+        let bundle_idx = fractals::find(name).unwrap();
+        let fractal = FRACTALS[bundle_idx];
+        let viewport = fractal.default_view();
+        let params = fractal.default_params();
+        let orbit = fractal
+            .reference(&viewport, &params, width as f32 / height as f32)
+            .map(|reference| reference.orbit())
+            .unwrap_or_default();
+
+        for shading in [Shading::Boundary, Shading::Lighting] {
+            let uniform_data = UniformData {
+                resolution: [width as f32, height as f32],
+                params,
+                shading: shading as u32,
+                light_angle: 2.0,
+                ..UniformData::from_viewport(&viewport)
+            };
+            // Near the boundary the derivative magnifies how differently the
+            // GPU rounds, most of all through multibrot's pow and atan2.
+            common::assert_gpu_matches_cpu(
+                &mut headless,
+                bundle_idx,
+                uniform_data,
+                &orbit,
+                &palette,
+                0.04,
+            );
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use fractal::Viewport;
+use fractal::viewport::{EmptyRectError, InvalidViewError};
+
+fn assert_close(a: [f64; 2], b: [f64; 2]) {
+    let error = (a[0] - b[0]).abs().max((a[1] - b[1]).abs());
+    assert!(error < 1e-6, "{a:?} != {b:?}");
+}
+
+#[test]
+fn zoom_at_keeps_the_anchor_fixed() {
+    let mut viewport = Viewport::new(2.0, [0.3, -0.1]).unwrap();
+    let anchor = viewport.point_at(0.8, 0.25, 1.0);
+
+    viewport.zoom_at(0.8, 0.25, 3.0, 1.0);
+    assert_close(viewport.point_at(0.8, 0.25, 1.0), anchor);
+
+    viewport.zoom_at(0.8, 0.25, -5.0, 1.0);
+    assert_close(viewport.point_at(0.8, 0.25, 1.0), anchor);
+}
+
+#[test]
+fn pan_moves_the_point_with_the_cursor() {
+    let mut viewport = Viewport::new(1.0, [0.0, 0.0]).unwrap();
+    let grabbed = viewport.point_at(0.5, 0.5, 1.0);
+
+    viewport.pan(0.1, -0.2, 1.0);
+    assert_close(viewport.point_at(0.6, 0.3, 1.0), grabbed);
+}
+
+#[test]
+fn fit_rect_fills_the_view_with_the_longer_side() {
+    let mut viewport = Viewport::new(1.0, [0.2, 0.4]).unwrap();
+    let top_left = viewport.point_at(0.2, 0.3, 1.0);
+    let bottom_right = viewport.point_at(0.6, 0.5, 1.0);
+
+    viewport.fit_rect(0.6, 0.5, 0.2, 0.3, 1.0).unwrap();
+
+    // The 0.4 wide selection spans the full width, the 0.2 tall one half
+    // the height, both centred.
+    assert_close(viewport.point_at(0.0, 0.25, 1.0), top_left);
+    assert_close(viewport.point_at(1.0, 0.75, 1.0), bottom_right);
+}
+
+#[test]
+fn fit_rect_rejects_empty_rects() {
+    let mut viewport = Viewport::new(1.0, [0.2, 0.4]).unwrap();
+    let before = viewport.to_string();
+
+    assert_eq!(
+        viewport.fit_rect(0.3, 0.7, 0.3, 0.7, 1.0),
+        Err(EmptyRectError)
+    );
+    assert_eq!(
+        viewport.fit_rect(0.3, f32::NAN, 0.5, 0.7, 1.0),
+        Err(EmptyRectError)
+    );
+    assert_eq!(viewport.to_string(), before);
+
+    // A line still has an extent along its length.
+    let (start, end) = (
+        viewport.point_at(0.3, 0.7, 1.0),
+        viewport.point_at(0.5, 0.7, 1.0),
+    );
+    viewport.fit_rect(0.3, 0.7, 0.5, 0.7, 1.0).unwrap();
+    assert_close(viewport.point_at(0.0, 0.5, 1.0), start);
+    assert_close(viewport.point_at(1.0, 0.5, 1.0), end);
+}
+
+#[test]
+fn wide_views_keep_square_pixels() {
+    let viewport = Viewport::new(0.0, [0.0, 0.0]).unwrap();
+    let aspect = 16.0 / 9.0;
+
+    // The shorter side spans the base extent and the longer one grows.
+    assert_close(viewport.point_at(0.5, 0.0, aspect), [0.0, 1.5]);
+    assert_close(
+        viewport.point_at(1.0, 0.5, aspect),
+        [1.5 * aspect as f64, 0.0],
+    );
+    assert_close(
+        viewport.point_at(0.5, 1.0, 1.0 / aspect),
+        [0.0, -1.5 * aspect as f64],
+    );
+}
+
+#[test]
+fn deep_views_keep_their_centre() {
+    let mut viewport = Viewport::new(0.0, [0.743643887037151, 0.131825904205330]).unwrap();
+    let center = viewport.center().clone();
+    for _ in 0..1300 {
+        viewport.zoom_at(0.5, 0.5, 1.0, 1.0);
+    }
+    assert!(viewport.scale().log2() < -100.0 / std::f64::consts::LOG10_2);
+
+    // A pan by a hundredth of the view moves the centre by exactly that, far
+    // below anything f64 can resolve next to it.
+    viewport.pan(0.005, 0.0, 1.0);
+    let moved = viewport.center()[0].clone() - &center[0];
+    let expected = -0.01 * viewport.half_extent();
+    let error = moved.to_f64().value() / expected - 1.0;
+    assert!(error.abs() < 1e-6, "moved {moved}, expected {expected}");
+}
+
+#[test]
+fn saved_views_load_to_well_within_a_pixel() {
+    let mut viewport = Viewport::new(0.0, [0.743643887037151, 0.131825904205330]).unwrap();
+    viewport.zoom_at(0.3, 0.7, 1300.0, 1.5);
+    viewport.pan(0.1, -0.2, 1.5);
+
+    let loaded: Viewport = viewport.to_string().parse().unwrap();
+    for (a, b) in loaded.center().iter().zip(viewport.center()) {
+        let error = (a.clone() - b).to_f64().value().abs();
+        assert!(
+            error < 1e-12 * viewport.half_extent(),
+            "{viewport}\n{loaded}"
+        );
+    }
+    let scale_error = loaded.scale().log2() - viewport.scale().log2();
+    assert!(scale_error.abs() < 1e-9, "{viewport}\n{loaded}");
+}
+
+#[test]
+fn hand_written_views_parse() {
+    let viewport: Viewport =
+        "# seahorse valley\ncenter_x = -0.75\ncenter_y = 0.1\nhalf_extent = 1e-3\n"
+            .parse()
+            .unwrap();
+    assert_close(viewport.offset(), [0.75, 0.1]);
+    assert!((viewport.half_extent() / 1e-3 - 1.0).abs() < 1e-12);
+
+    assert!("center_x = 0\nhalf_extent = 1".parse::<Viewport>().is_err());
+    assert!(
+        "center_x = 0\ncenter_y = 0\nhalf_extent = -1"
+            .parse::<Viewport>()
+            .is_err()
+    );
+    assert!(
+        "center_x = 0\ncenter_y = 0\nhalf_extent = 1e-99999999"
+            .parse::<Viewport>()
+            .is_err()
+    );
+}
+
+#[test]
+fn views_that_cannot_be_shown_are_rejected() {
+    assert_eq!(
+        Viewport::new(1.0, [f64::NAN, 0.0]),
+        Err(InvalidViewError::NotFinite)
+    );
+    assert_eq!(
+        Viewport::new(f32::INFINITY, [0.0, 0.0]),
+        Err(InvalidViewError::NotFinite)
+    );
+    assert_eq!(
+        Viewport::new(1e30, [0.0, 0.0]),
+        Err(InvalidViewError::OutOfRange)
+    );
+
+    let mut viewport = Viewport::new(2.0, [0.3, -0.1]).unwrap();
+    let before = viewport.clone();
+    assert_eq!(
+        viewport.set_zoom(f32::NAN),
+        Err(InvalidViewError::NotFinite)
+    );
+    assert_eq!(viewport.set_zoom(-1e30), Err(InvalidViewError::OutOfRange));
+    assert_eq!(viewport, before);
+    viewport.set_zoom(5.0).unwrap();
+    assert!((viewport.zoom() - 5.0).abs() < 1e-4);
+}